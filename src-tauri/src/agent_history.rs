@@ -0,0 +1,184 @@
+use crate::agents::Agent;
+use crate::app_data;
+use crate::schema::{self, Schema};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// 每个智能体最多保留的历史版本数
+const MAX_VERSIONS: usize = 50;
+
+pub const SCHEMA: Schema = Schema {
+  name: "agent history",
+  current: 1,
+  detect: schema::unversioned,
+  migrations: &[schema::unchanged],
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentVersion {
+  /// 从 1 开始递增，删除旧版本后也不复用
+  pub version: u32,
+  pub saved_at: String,
+  pub agent: Agent,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct HistoryFile {
+  agents: BTreeMap<String, Vec<AgentVersion>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+  Equal,
+  Insert,
+  Delete,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffLine {
+  pub op: DiffOp,
+  pub text: String,
+}
+
+fn read_file(path: &Path) -> Result<HistoryFile, String> {
+  let Some(value) = schema::read(path, &SCHEMA)? else {
+    return Ok(HistoryFile::default());
+  };
+  serde_json::from_value(value).map_err(|e| format!("parse agent history failed: {e}"))
+}
+
+fn write_file(path: &Path, file: &HistoryFile) -> Result<(), String> {
+  let raw = SCHEMA.to_string(file)?;
+  fs::write(path, raw).map_err(|e| format!("write agent history failed: {e}"))
+}
+
+/// 为内容有变化的智能体追加一个版本；与最新版本相同时不记录。
+fn record_in(file: &mut HistoryFile, agents: &[Agent], saved_at: &str) {
+  for agent in agents {
+    let versions = file.agents.entry(agent.id.clone()).or_default();
+    let unchanged = versions
+      .last()
+      .is_some_and(|last| serde_json::to_value(&last.agent).ok() == serde_json::to_value(agent).ok());
+    if unchanged {
+      continue;
+    }
+    let version = versions.last().map(|v| v.version + 1).unwrap_or(1);
+    versions.push(AgentVersion {
+      version,
+      saved_at: saved_at.to_string(),
+      agent: agent.clone(),
+    });
+    if versions.len() > MAX_VERSIONS {
+      let overflow = versions.len() - MAX_VERSIONS;
+      versions.drain(..overflow);
+    }
+  }
+}
+
+pub fn record(app: &tauri::AppHandle, agents: &[Agent]) -> Result<(), String> {
+  let path = app_data::data_file_path(app, "agent_history.json")?;
+  let mut file = read_file(&path)?;
+  record_in(&mut file, agents, &chrono::Utc::now().to_rfc3339());
+  write_file(&path, &file)
+}
+
+/// 某个智能体的全部历史版本，旧版本在前
+pub fn list(app: &tauri::AppHandle, agent_id: &str) -> Result<Vec<AgentVersion>, String> {
+  let path = app_data::data_file_path(app, "agent_history.json")?;
+  Ok(read_file(&path)?.agents.remove(agent_id).unwrap_or_default())
+}
+
+pub fn get(app: &tauri::AppHandle, agent_id: &str, version: u32) -> Result<AgentVersion, String> {
+  list(app, agent_id)?
+    .into_iter()
+    .find(|v| v.version == version)
+    .ok_or_else(|| format!("agent version not found: {agent_id}@{version}"))
+}
+
+/// 按行比较两段文本（最长公共子序列）
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+  let a: Vec<&str> = old.lines().collect();
+  let b: Vec<&str> = new.lines().collect();
+  // lcs[i][j]：a[i..] 与 b[j..] 的最长公共子序列长度
+  let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+  for i in (0..a.len()).rev() {
+    for j in (0..b.len()).rev() {
+      lcs[i][j] = if a[i] == b[j] {
+        lcs[i + 1][j + 1] + 1
+      } else {
+        lcs[i + 1][j].max(lcs[i][j + 1])
+      };
+    }
+  }
+
+  let line = |op, text: &str| DiffLine { op, text: text.to_string() };
+  let mut out = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < a.len() && j < b.len() {
+    if a[i] == b[j] {
+      out.push(line(DiffOp::Equal, a[i]));
+      i += 1;
+      j += 1;
+    } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+      out.push(line(DiffOp::Delete, a[i]));
+      i += 1;
+    } else {
+      out.push(line(DiffOp::Insert, b[j]));
+      j += 1;
+    }
+  }
+  out.extend(a[i..].iter().map(|t| line(DiffOp::Delete, t)));
+  out.extend(b[j..].iter().map(|t| line(DiffOp::Insert, t)));
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn agent(prompt: &str) -> Agent {
+    Agent {
+      id: "fantasy".to_string(),
+      system_prompt: prompt.to_string(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn records_only_changed_agents() {
+    let mut file = HistoryFile::default();
+    record_in(&mut file, &[agent("甲")], "t1");
+    record_in(&mut file, &[agent("甲")], "t2");
+    record_in(&mut file, &[agent("乙")], "t3");
+    let versions = &file.agents["fantasy"];
+    assert_eq!(versions.iter().map(|v| v.version).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(versions[1].saved_at, "t3");
+
+    for n in 0..MAX_VERSIONS {
+      record_in(&mut file, &[agent(&n.to_string())], "t");
+    }
+    let versions = &file.agents["fantasy"];
+    assert_eq!(versions.len(), MAX_VERSIONS);
+    assert_eq!(versions.last().unwrap().version, MAX_VERSIONS as u32 + 2);
+  }
+
+  #[test]
+  fn diffs_prompt_lines() {
+    let diff = diff_lines("一\n二\n三", "一\n贰\n三\n四");
+    let ops: Vec<(DiffOp, &str)> = diff.iter().map(|l| (l.op, l.text.as_str())).collect();
+    assert_eq!(
+      ops,
+      [
+        (DiffOp::Equal, "一"),
+        (DiffOp::Delete, "二"),
+        (DiffOp::Insert, "贰"),
+        (DiffOp::Equal, "三"),
+        (DiffOp::Insert, "四"),
+      ]
+    );
+  }
+}
@@ -1,11 +1,15 @@
 use crate::ai_types::ChatMessage;
 use crate::commands;
+use crate::mcp::client::McpClient;
+use crate::mcp::McpConnection;
+use crate::path_sandbox::PathSandbox;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
 use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
 
 fn ensure_default_ext(path: &str) -> String {
@@ -24,20 +28,89 @@ fn ensure_default_ext(path: &str) -> String {
   }
 }
 
+/// 把 `old` 替换为 `new`，要求 `old` 在原文中恰好出现一次。
+pub(crate) fn replace_unique(content: &str, old: &str, new: &str) -> Result<String, String> {
+  if old.is_empty() {
+    return Err("empty args.old_str".to_string());
+  }
+  match content.matches(old).count() {
+    0 => Err("old_str not found in file".to_string()),
+    1 => Ok(content.replacen(old, new, 1)),
+    n => Err(format!("old_str matches {n} times; include more surrounding text to make it unique")),
+  }
+}
+
+/// 在第 `line` 行（从 1 开始）之前插入 `text`；`line` 等于总行数 + 1 时追加到末尾。
+/// 沿用文件原有的换行符，其余内容保持不变。
+pub(crate) fn insert_at_line(content: &str, line: usize, text: &str) -> Result<String, String> {
+  let eol = if content.contains("\r\n") { "\r\n" } else { "\n" };
+  let lines = content.split_inclusive('\n').collect::<Vec<_>>();
+  if line == 0 || line > lines.len() + 1 {
+    return Err(format!("line out of range: {line} (file has {} lines)", lines.len()));
+  }
+  // 插入内容统一换成文件的换行符
+  let mut inserted = text.replace("\r\n", "\n");
+  if !inserted.ends_with('\n') {
+    inserted.push('\n');
+  }
+  if eol == "\r\n" {
+    inserted = inserted.replace('\n', eol);
+  }
+  let mut out = String::with_capacity(content.len() + inserted.len() + eol.len());
+  for l in &lines[..line - 1] {
+    out.push_str(l);
+  }
+  if line > lines.len() && !content.is_empty() && !content.ends_with('\n') {
+    out.push_str(eol);
+  }
+  out.push_str(&inserted);
+  for l in &lines[line - 1..] {
+    out.push_str(l);
+  }
+  Ok(out)
+}
+
+fn write_patched_text(ctx: &ToolContext, rel_norm: &str, target: &Path, text: &str) -> Result<(), String> {
+  if rel_norm == ".novel/.cache/outline.json" {
+    commands::validate_outline("", text)?;
+  }
+  fs::write(target, text).map_err(|e| format!("write failed: {e}"))?;
+  if rel_norm.starts_with("concept/") && rel_norm.to_lowercase().ends_with(".md") {
+    commands::update_concept_index(&ctx.workspace_root, rel_norm, text)?;
+  }
+  Ok(())
+}
+
 #[derive(Clone)]
 pub struct ToolContext {
   pub workspace_root: PathBuf,
+  pub sandbox: PathSandbox,
 }
 
 pub type ToolFn = Box<dyn Fn(&ToolContext, Value) -> Result<Value, String> + Send + Sync>;
 
 pub struct ToolRegistry {
   tools: HashMap<String, ToolFn>,
+  docs: HashMap<String, String>,
 }
 
 impl ToolRegistry {
   pub fn new() -> Self {
-    Self { tools: HashMap::new() }
+    Self {
+      tools: HashMap::new(),
+      docs: HashMap::new(),
+    }
+  }
+
+  /// 为工具附加说明，会写入 agent 的系统提示词（内置工具不需要）。
+  pub fn describe(&mut self, name: &str, doc: &str) {
+    self.docs.insert(name.to_string(), doc.to_string());
+  }
+
+  pub fn docs(&self) -> Vec<(String, String)> {
+    let mut out = self.docs.iter().map(|(k, v)| (k.clone(), v.clone())).collect::<Vec<_>>();
+    out.sort();
+    out
   }
 
   pub fn register<F>(&mut self, name: &str, f: F)
@@ -135,16 +208,23 @@ pub struct AgentPerf {
   pub tool_ms: u128,
 }
 
+/// 新建或整体重写文件的内置工具，调用成功后记录路径供后处理（如自动分章）使用；局部修改不记录
+const WHOLE_WRITE_TOOLS: [&str; 2] = ["fs_create_file", "fs_write_text"];
+
 pub struct AgentRuntime {
   ctx: ToolContext,
   tools: ToolRegistry,
   memory: MemoryStore,
+  written: Vec<String>,
+  /// MCP 工具名 → (client, server 端工具名)
+  mcp_tools: HashMap<String, (Arc<McpClient>, String)>,
 }
 
 impl AgentRuntime {
   pub fn new(workspace_root: PathBuf) -> Self {
     let ctx = ToolContext {
       workspace_root: workspace_root.clone(),
+      sandbox: PathSandbox::new(&workspace_root),
     };
     let memory = MemoryStore::load(&workspace_root);
     let mut tools = ToolRegistry::new();
@@ -156,19 +236,17 @@ impl AgentRuntime {
       if path.trim().is_empty() {
         return Err("empty path".to_string());
       }
-      let rel = commands::validate_relative_path(path)?;
-      let target = ctx.workspace_root.join(rel);
+      let target = ctx.sandbox.resolve(path)?;
       let raw = fs::read_to_string(target).map_err(|e| format!("read failed: {e}"))?;
       Ok(serde_json::json!({ "text": raw }))
     });
     tools.register("fs_list_dir", |ctx, args| {
       let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
-      let rel = if path.trim().is_empty() {
-        PathBuf::from("")
+      let target = if path.trim().is_empty() {
+        ctx.sandbox.root().to_path_buf()
       } else {
-        commands::validate_relative_path(path)?
+        ctx.sandbox.resolve(path)?
       };
-      let target = ctx.workspace_root.join(rel);
       let mut items: Vec<Value> = Vec::new();
       for e in fs::read_dir(target).map_err(|e| format!("read dir failed: {e}"))? {
         let e = e.map_err(|e| format!("read entry failed: {e}"))?;
@@ -187,8 +265,7 @@ impl AgentRuntime {
       if path.trim().is_empty() {
         return Err("empty path".to_string());
       }
-      let rel = commands::validate_relative_path(path)?;
-      let target = ctx.workspace_root.join(rel);
+      let target = ctx.sandbox.resolve(path)?;
       let md = match fs::metadata(&target) {
         Ok(v) => v,
         Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
@@ -207,8 +284,7 @@ impl AgentRuntime {
       if path.trim().is_empty() {
         return Err("empty path".to_string());
       }
-      let rel = commands::validate_relative_path(path)?;
-      let target = ctx.workspace_root.join(rel);
+      let target = ctx.sandbox.resolve(path)?;
       fs::create_dir_all(&target).map_err(|e| format!("create dir failed: {e}"))?;
       Ok(serde_json::json!({ "ok": true }))
     });
@@ -221,8 +297,7 @@ impl AgentRuntime {
         return Err("empty path".to_string());
       }
       let fixed = ensure_default_ext(path);
-      let rel = commands::validate_relative_path(fixed.as_str())?;
-      let target = ctx.workspace_root.join(rel);
+      let target = ctx.sandbox.resolve(fixed.as_str())?;
       if let Some(parent) = target.parent() {
         if !parent.exists() {
           return Err("parent directory does not exist; create it first".to_string());
@@ -241,8 +316,7 @@ impl AgentRuntime {
       if path.trim().is_empty() {
         return Err("empty path".to_string());
       }
-      let rel = commands::validate_relative_path(path)?;
-      let target = ctx.workspace_root.join(rel);
+      let target = ctx.sandbox.resolve(path)?;
       if !target.exists() {
         return Ok(serde_json::json!({ "ok": true }));
       }
@@ -267,10 +341,8 @@ impl AgentRuntime {
         return Err("empty path".to_string());
       }
       let to_fixed = ensure_default_ext(to);
-      let from_rel = commands::validate_relative_path(from)?;
-      let to_rel = commands::validate_relative_path(to_fixed.as_str())?;
-      let from_abs = ctx.workspace_root.join(from_rel);
-      let to_abs = ctx.workspace_root.join(to_rel);
+      let from_abs = ctx.sandbox.resolve(from)?;
+      let to_abs = ctx.sandbox.resolve(to_fixed.as_str())?;
       if let Some(parent) = to_abs.parent() {
         if !parent.exists() {
           return Err("parent directory does not exist; create it first".to_string());
@@ -303,8 +375,7 @@ impl AgentRuntime {
         .replace("\n\n\n", "\n\n");
       let fixed = ensure_default_ext(path);
       let rel_norm = fixed.as_str().replace('\\', "/");
-      let rel = commands::validate_relative_path(fixed.as_str())?;
-      let target = ctx.workspace_root.join(rel);
+      let target = ctx.sandbox.resolve(fixed.as_str())?;
       if rel_norm == ".novel/.cache/outline.json" {
         let existing = if target.exists() {
           fs::read_to_string(&target).unwrap_or_default()
@@ -324,17 +395,126 @@ impl AgentRuntime {
       }
       Ok(serde_json::json!({ "ok": true }))
     });
-    Self { ctx, tools, memory }
+    tools.register("fs_str_replace", |ctx, args| {
+      let path = args
+        .get("path")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "missing args.path".to_string())?;
+      let old_str = args
+        .get("old_str")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "missing args.old_str".to_string())?;
+      let new_str = args.get("new_str").and_then(|v| v.as_str()).unwrap_or("");
+      if path.trim().is_empty() {
+        return Err("empty path".to_string());
+      }
+      let fixed = ensure_default_ext(path);
+      let rel_norm = fixed.as_str().replace('\\', "/");
+      let target = ctx.sandbox.resolve(fixed.as_str())?;
+      let existing = fs::read_to_string(&target).map_err(|e| format!("read failed: {e}"))?;
+      let patched = replace_unique(&existing, old_str, new_str)?;
+      write_patched_text(ctx, &rel_norm, &target, &patched)?;
+      Ok(serde_json::json!({ "ok": true, "replacements": 1 }))
+    });
+    tools.register("fs_append_text", |ctx, args| {
+      let path = args
+        .get("path")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "missing args.path".to_string())?;
+      let text = args
+        .get("text")
+        .or_else(|| args.get("content"))
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "missing args.text".to_string())?;
+      if path.trim().is_empty() {
+        return Err("empty path".to_string());
+      }
+      let fixed = ensure_default_ext(path);
+      let rel_norm = fixed.as_str().replace('\\', "/");
+      let target = ctx.sandbox.resolve(fixed.as_str())?;
+      if let Some(parent) = target.parent() {
+        if !parent.exists() {
+          return Err("parent directory does not exist; create it first".to_string());
+        }
+      }
+      let mut content = if target.exists() {
+        fs::read_to_string(&target).map_err(|e| format!("read failed: {e}"))?
+      } else {
+        String::new()
+      };
+      content.push_str(text);
+      write_patched_text(ctx, &rel_norm, &target, &content)?;
+      Ok(serde_json::json!({ "ok": true, "chars": content.chars().count() }))
+    });
+    tools.register("fs_insert_at_line", |ctx, args| {
+      let path = args
+        .get("path")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "missing args.path".to_string())?;
+      let line = args
+        .get("line")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| "missing args.line".to_string())?;
+      let text = args
+        .get("text")
+        .or_else(|| args.get("content"))
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "missing args.text".to_string())?;
+      if path.trim().is_empty() {
+        return Err("empty path".to_string());
+      }
+      let fixed = ensure_default_ext(path);
+      let rel_norm = fixed.as_str().replace('\\', "/");
+      let target = ctx.sandbox.resolve(fixed.as_str())?;
+      let existing = fs::read_to_string(&target).map_err(|e| format!("read failed: {e}"))?;
+      let patched = insert_at_line(&existing, line as usize, text)?;
+      write_patched_text(ctx, &rel_norm, &target, &patched)?;
+      Ok(serde_json::json!({ "ok": true }))
+    });
+    Self {
+      ctx,
+      tools,
+      memory,
+      written: Vec::new(),
+      mcp_tools: HashMap::new(),
+    }
+  }
+
+  /// 把 MCP server 的工具以命名空间形式注册进工具表，调用时转发为 `tools/call`。
+  pub fn attach_mcp(&mut self, conn: &McpConnection) {
+    for tool in &conn.tools {
+      let name = crate::mcp::namespaced_tool_name(conn.client.server_id(), &tool.name);
+      self.mcp_tools.insert(name.clone(), (conn.client.clone(), tool.name.clone()));
+      let schema = serde_json::to_string(&tool.input_schema).unwrap_or_default();
+      self.tools.describe(&name, &format!("{}；参数：{}", tool.description.trim(), schema));
+    }
+  }
+
+  /// 调用工具；MCP 的 `tools/call` 是阻塞调用（最长 120s），放到阻塞线程里等待。
+  async fn call_tool(&self, name: &str, args: Value) -> Result<Value, String> {
+    let Some((client, remote_name)) = self.mcp_tools.get(name) else {
+      return self.tools.call(&self.ctx, name, args);
+    };
+    let (client, remote_name) = (client.clone(), remote_name.clone());
+    tauri::async_runtime::spawn_blocking(move || client.call_tool(&remote_name, args))
+      .await
+      .map_err(|e| format!("mcp tool call failed: {e}"))?
   }
 
   pub fn tools(&self) -> Vec<String> {
     let mut out = self.tools.list();
+    out.extend(self.mcp_tools.keys().cloned());
     out.push("memory_upsert".to_string());
     out.push("memory_search".to_string());
     out.sort();
     out
   }
 
+  /// 本次运行中新建或整体写入过的文件（相对路径，去重，按首次写入顺序）
+  pub fn written_files(&self) -> &[String] {
+    &self.written
+  }
+
   pub async fn run_react<F, Fut>(
     &mut self,
     base_messages: Vec<ChatMessage>,
@@ -348,12 +528,19 @@ impl AgentRuntime {
     let mut perf = AgentPerf::default();
     let tool_list = self.tools();
     let memory_text = self.memory.render(50);
+    let tool_docs = self.tools.docs();
     let mut messages: Vec<ChatMessage> = Vec::new();
-    let react_prompt = format!(
-      "{sys}\n\n可用工具：{tools}\n\n当你需要调用工具时，严格使用三行格式：\\nACTION: tool_name\\nINPUT: {{...json...}}\\n然后等待 OBSERVATION。若无需工具，直接给出最终回答。\n\n文件系统规则：\n1) 所有 path 必须是相对路径，禁止绝对路径与 ..。\n2) 写文件不会自动创建父目录；若目录不存在，先用 fs_exists 检查，再用 fs_create_dir 创建。\n3) 默认扩展名：stories/ 下默认 .txt；concept/ 与 outline/ 下默认 .md；如果你需要其它格式，请显式写出扩展名。\n\n文件编辑格式（用于多行编辑）：\n当你需要修改文件的特定行时，使用以下 XML 格式：\n<file_edit path=\"相对路径\">\n  <replace lines=\"起始行-结束行\">新内容</replace>\n  <insert at=\"行号\">插入内容</insert>\n  <delete lines=\"起始行-结束行\" />\n</file_edit>\n\n示例：\n<file_edit path=\"stories/chapter-001.txt\">\n  <replace lines=\"10-15\">\n  这是替换后的新内容\n  可以是多行\n  </replace>\n</file_edit>\n\n使用此格式时，用户将在 Diff 视图中看到修改对比，并可以选择接受或拒绝每个修改。",
+    let mut react_prompt = format!(
+      "{sys}\n\n可用工具：{tools}\n\n当你需要调用工具时，严格使用三行格式：\\nACTION: tool_name\\nINPUT: {{...json...}}\\n然后等待 OBSERVATION。若无需工具，直接给出最终回答。\n\n文件系统规则：\n1) 所有 path 必须是相对路径，禁止绝对路径与 ..。\n2) 写文件不会自动创建父目录；若目录不存在，先用 fs_exists 检查，再用 fs_create_dir 创建。\n3) 默认扩展名：stories/ 下默认 .txt；concept/ 与 outline/ 下默认 .md；如果你需要其它格式，请显式写出扩展名。\n4) 局部修改优先使用 fs_str_replace（path/old_str/new_str，old_str 必须在文件中唯一出现）、fs_append_text（path/text，追加到末尾）、fs_insert_at_line（path/line/text，在第 line 行之前插入，行号从 1 开始），这些工具不会改动文件其它部分；只有新建文件或整体重写时才使用 fs_write_text。\n\n文件编辑格式（用于多行编辑）：\n当你需要修改文件的特定行时，使用以下 XML 格式：\n<file_edit path=\"相对路径\">\n  <replace lines=\"起始行-结束行\">新内容</replace>\n  <insert at=\"行号\">插入内容</insert>\n  <delete lines=\"起始行-结束行\" />\n  <replace_text><find>原文片段</find><with>新内容</with></replace_text>\n  <insert_after anchor=\"原文片段\">插入内容</insert_after>\n</file_edit>\n中文长段落行号容易数错，优先使用 replace_text / insert_after：find 与 anchor 必须逐字摘自原文且在文件中唯一出现。\n新建、删除、重命名（移动）文件使用：\n<create_file path=\"相对路径\">完整内容</create_file>\n<delete_file path=\"相对路径\" />\n<rename_file from=\"旧路径\" to=\"新路径\" />\n例如拆分章节：先 rename_file 原章节为上半章并用 file_edit 删去后半部分，再用 create_file 写出下半章，所有操作会作为同一组修改供用户审阅。\n\n示例：\n<file_edit path=\"stories/chapter-001.txt\">\n  <replace lines=\"10-15\">\n  这是替换后的新内容\n  可以是多行\n  </replace>\n</file_edit>\n\n使用此格式时，用户将在 Diff 视图中看到修改对比，并可以选择接受或拒绝每个修改。",
       sys = agent_system_prompt.trim(),
       tools = tool_list.join(", ")
     );
+    if !tool_docs.is_empty() {
+      react_prompt.push_str("\n\n外部工具说明：");
+      for (name, doc) in &tool_docs {
+        react_prompt.push_str(&format!("\n- {name}：{doc}"));
+      }
+    }
     messages.push(ChatMessage {
       role: "system".to_string(),
       content: if memory_text.is_empty() {
@@ -418,7 +605,17 @@ impl AgentRuntime {
           let hits = self.memory.search(query, limit);
           Ok(serde_json::to_value(hits).unwrap_or_else(|_| serde_json::json!([])))
         } else {
-          self.tools.call(&self.ctx, &call.tool, call.args.clone())
+          let result = self.call_tool(&call.tool, call.args.clone()).await;
+          let path = call.args.get("path").and_then(|v| v.as_str());
+          if let (Ok(_), Some(path)) = (&result, path) {
+            if WHOLE_WRITE_TOOLS.contains(&call.tool.as_str()) {
+              let rel = ensure_default_ext(path).trim_start_matches("./").to_string();
+              if !self.written.contains(&rel) {
+                self.written.push(rel);
+              }
+            }
+          }
+          result
         };
         perf.tool_ms += t1.elapsed().as_millis();
         let obs = match result {
@@ -576,3 +773,74 @@ impl McpBroker {
     self.items.values().take(limit).cloned().collect()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::TempDir;
+
+  fn temp_workspace() -> TempDir {
+    let dir = TempDir::new("agent");
+    fs::create_dir_all(dir.join("stories")).unwrap();
+    dir
+  }
+
+  #[test]
+  fn replace_unique_requires_single_match() {
+    assert_eq!(replace_unique("甲乙丙", "乙", "丁").unwrap(), "甲丁丙");
+    assert!(replace_unique("甲乙乙", "乙", "丁").unwrap_err().contains("2 times"));
+    assert!(replace_unique("甲乙丙", "戊", "丁").is_err());
+    assert!(replace_unique("甲乙丙", "", "丁").is_err());
+  }
+
+  #[test]
+  fn insert_at_line_keeps_line_endings() {
+    assert_eq!(insert_at_line("a\r\nb\r\n", 2, "x").unwrap(), "a\r\nx\r\nb\r\n");
+    assert_eq!(insert_at_line("a\r\nb\r\n", 2, "x\ny\n").unwrap(), "a\r\nx\r\ny\r\nb\r\n");
+    assert_eq!(insert_at_line("a\r\nb", 3, "x\r\ny").unwrap(), "a\r\nb\r\nx\r\ny\r\n");
+    assert_eq!(insert_at_line("a\nb\n", 2, "x\r\ny").unwrap(), "a\nx\ny\nb\n");
+    assert_eq!(insert_at_line("a\nb", 3, "x").unwrap(), "a\nb\nx\n");
+    assert_eq!(insert_at_line("", 1, "x").unwrap(), "x\n");
+    assert!(insert_at_line("a\nb\n", 4, "x").is_err());
+    assert!(insert_at_line("a\n", 0, "x").is_err());
+  }
+
+  #[test]
+  fn patch_tools_preserve_rest_of_file() {
+    let root = temp_workspace();
+    let original = "第一段。  \n\n\n\n第二段，旧词。\n";
+    fs::write(root.join("stories/ch1.txt"), original).unwrap();
+    let rt = AgentRuntime::new(root.to_path_buf());
+
+    rt.tools
+      .call(&rt.ctx, "fs_str_replace", serde_json::json!({ "path": "stories/ch1.txt", "old_str": "旧词", "new_str": "新词" }))
+      .unwrap();
+    rt.tools
+      .call(&rt.ctx, "fs_append_text", serde_json::json!({ "path": "stories/ch1", "text": "尾声。\n" }))
+      .unwrap();
+    rt.tools
+      .call(&rt.ctx, "fs_insert_at_line", serde_json::json!({ "path": "stories/ch1.txt", "line": 1, "text": "标题" }))
+      .unwrap();
+
+    let raw = fs::read_to_string(root.join("stories/ch1.txt")).unwrap();
+    assert_eq!(raw, "标题\n第一段。  \n\n\n\n第二段，新词。\n尾声。\n");
+  }
+
+  #[test]
+  fn mcp_tools_are_registered_under_namespace() {
+    let root = temp_workspace();
+    let mut rt = AgentRuntime::new(root.to_path_buf());
+    let hub = std::sync::Mutex::new(crate::mcp::McpHub::default());
+    let conn = crate::mcp::connect_server(&hub, &crate::mcp::client::tests::fixture_server("glossary")).unwrap();
+    rt.attach_mcp(&conn);
+
+    assert!(rt.tools().contains(&"mcp__glossary__echo".to_string()));
+    // 在 current_thread 运行时中调用也不会 panic
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let out = runtime
+      .block_on(rt.call_tool("mcp__glossary__echo", serde_json::json!({ "text": "青云宗" })))
+      .unwrap();
+    assert_eq!(out["text"], "青云宗");
+    assert!(rt.tools.docs().iter().any(|(name, doc)| name == "mcp__glossary__echo" && doc.contains("Echo")));
+  }
+}
@@ -1,4 +1,6 @@
+use crate::agent_history;
 use crate::app_data;
+use crate::schema::{self, Schema};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
@@ -14,6 +16,12 @@ pub struct Agent {
   pub max_tokens: u32,
   /// 分章目标字数，0表示不自动分章
   pub chapter_word_target: u32,
+  /// 继承的父智能体，父级提示词排在本级之前
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub parent_id: Option<String>,
+  /// 追加在 system_prompt 之后的提示词片段 id
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub fragments: Vec<String>,
 }
 
 impl Default for Agent {
@@ -26,10 +34,146 @@ impl Default for Agent {
       temperature: 0.7,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: Vec::new(),
     }
   }
 }
 
+/// 可被多个智能体复用的提示词片段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptFragment {
+  pub id: String,
+  pub name: String,
+  pub content: String,
+}
+
+pub fn builtin_fragments() -> Vec<PromptFragment> {
+  vec![
+    PromptFragment {
+      id: "output_format".to_string(),
+      name: "输出格式".to_string(),
+      content: r#"## 输出格式
+- 不使用 Markdown 格式（除非用户开启）
+- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
+- 不使用空行或段首空格
+- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
+- 如需分章，在章节结尾用"【本章完】"标记"#
+        .to_string(),
+    },
+    PromptFragment {
+      id: "chapter_rules".to_string(),
+      name: "分章规则".to_string(),
+      content: r#"## 分章规则
+- 当单章内容接近目标字数时，自动总结本章并开启新章
+- 每章开头简要承接上文，过渡自然
+- 章节结尾要留有悬念或伏笔，吸引读者继续阅读"#
+        .to_string(),
+    },
+  ]
+}
+
+/// 用户自定义的片段，与内置片段同 id 时覆盖内置片段
+pub const FRAGMENTS_SCHEMA: Schema = Schema {
+  name: "prompt fragments",
+  current: 1,
+  detect: schema::unversioned,
+  migrations: &[schema::unchanged],
+};
+
+#[derive(Default, Serialize, Deserialize)]
+struct FragmentsFile {
+  fragments: Vec<PromptFragment>,
+}
+
+pub fn load_custom_fragments(app: &tauri::AppHandle) -> Result<Vec<PromptFragment>, String> {
+  let Some(value) = schema::read(&app_data::data_file_path(app, "prompt_fragments.json")?, &FRAGMENTS_SCHEMA)? else {
+    return Ok(Vec::new());
+  };
+  let file: FragmentsFile = serde_json::from_value(value).map_err(|e| format!("parse prompt fragments failed: {e}"))?;
+  Ok(file.fragments)
+}
+
+pub fn save_custom_fragments(app: &tauri::AppHandle, fragments: &[PromptFragment]) -> Result<(), String> {
+  let raw = FRAGMENTS_SCHEMA.to_string(&FragmentsFile { fragments: fragments.to_vec() })?;
+  fs::write(app_data::data_file_path(app, "prompt_fragments.json")?, raw).map_err(|e| format!("write prompt fragments failed: {e}"))
+}
+
+/// 内置片段与自定义片段合并后的列表
+pub fn load_fragments(app: &tauri::AppHandle) -> Result<Vec<PromptFragment>, String> {
+  let mut out = builtin_fragments();
+  for f in load_custom_fragments(app)? {
+    match out.iter_mut().find(|b| b.id == f.id) {
+      Some(slot) => *slot = f,
+      None => out.push(f),
+    }
+  }
+  Ok(out)
+}
+
+/// 展开父智能体与片段，得到实际发送给模型的 system prompt。
+///
+/// 顺序为：祖先在前，每一级先写自身提示词再追加片段；同一片段只出现一次。
+pub fn resolve_system_prompt(agent: &Agent, agents: &[Agent], fragments: &[PromptFragment]) -> Result<String, String> {
+  let mut chain = vec![agent];
+  let mut current = agent;
+  while let Some(parent_id) = current.parent_id.as_deref().filter(|p| !p.trim().is_empty()) {
+    if chain.iter().any(|a| a.id == parent_id) {
+      return Err(format!("agent inheritance cycle at {parent_id}"));
+    }
+    current = agents
+      .iter()
+      .find(|a| a.id == parent_id)
+      .ok_or_else(|| format!("parent agent not found: {parent_id}"))?;
+    chain.push(current);
+  }
+
+  let mut parts: Vec<&str> = Vec::new();
+  let mut used: Vec<&str> = Vec::new();
+  for a in chain.iter().rev() {
+    if !a.system_prompt.trim().is_empty() {
+      parts.push(a.system_prompt.trim());
+    }
+    for id in &a.fragments {
+      if used.contains(&id.as_str()) {
+        continue;
+      }
+      let fragment = fragments
+        .iter()
+        .find(|f| f.id == *id)
+        .ok_or_else(|| format!("prompt fragment not found: {id}"))?;
+      parts.push(fragment.content.trim());
+      used.push(id);
+    }
+  }
+  Ok(parts.join("\n\n"))
+}
+
+/// 把 `agent.system_prompt` 替换为展开后的完整提示词
+pub fn flatten(app: &tauri::AppHandle, agent: &mut Agent, agents: &[Agent]) -> Result<(), String> {
+  agent.system_prompt = resolve_system_prompt(agent, agents, &load_fragments(app)?)?;
+  agent.parent_id = None;
+  agent.fragments.clear();
+  Ok(())
+}
+
+/// 版本 0：顶层直接是智能体数组。
+pub const SCHEMA: Schema = Schema {
+  name: "agents",
+  current: 1,
+  detect: schema::detect_array,
+  migrations: &[wrap_agents],
+};
+
+fn wrap_agents(value: serde_json::Value) -> Result<serde_json::Value, String> {
+  schema::wrap_array(value, "agents")
+}
+
+#[derive(Serialize, Deserialize)]
+struct AgentsFile {
+  agents: Vec<Agent>,
+}
+
 pub fn load(app: &tauri::AppHandle) -> Result<Vec<Agent>, String> {
   let path = agents_path(app)?;
   if !path.exists() {
@@ -37,9 +181,11 @@ pub fn load(app: &tauri::AppHandle) -> Result<Vec<Agent>, String> {
     save(app, &defaults)?;
     return Ok(defaults);
   }
-  let raw = fs::read_to_string(&path).map_err(|e| format!("read agents failed: {e}"))?;
-  let agents: Vec<Agent> = serde_json::from_str(&raw).map_err(|e| format!("parse agents failed: {e}"))?;
-  Ok(agents)
+  let Some(value) = schema::read(&path, &SCHEMA)? else {
+    return Ok(Vec::new());
+  };
+  let file: AgentsFile = serde_json::from_value(value).map_err(|e| format!("parse agents failed: {e}"))?;
+  Ok(file.agents)
 }
 
 pub fn save(app: &tauri::AppHandle, agents: &[Agent]) -> Result<(), String> {
@@ -47,8 +193,9 @@ pub fn save(app: &tauri::AppHandle, agents: &[Agent]) -> Result<(), String> {
   if let Some(parent) = path.parent() {
     fs::create_dir_all(parent).map_err(|e| format!("create agents dir failed: {e}"))?;
   }
-  let raw = serde_json::to_string_pretty(agents).map_err(|e| format!("serialize agents failed: {e}"))?;
-  fs::write(path, raw).map_err(|e| format!("write agents failed: {e}"))
+  let raw = SCHEMA.to_string(&AgentsFile { agents: agents.to_vec() })?;
+  fs::write(path, raw).map_err(|e| format!("write agents failed: {e}"))?;
+  agent_history::record(app, agents)
 }
 
 pub fn default_agents() -> Vec<Agent> {
@@ -66,10 +213,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 控制剧情节奏，爽点密集
 - 智能分章，每章 2000-4000 字（根据用户设置）
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
-- 每章开头简要承接上文，过渡自然
-- 章节结尾要留有悬念或伏笔，吸引读者继续阅读
+## 本类型分章要点
 - 在适当情节转折点分章（如大战前、秘境开启、功法突破等）
 
 ## 写作风格
@@ -77,17 +221,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 注重主角成长曲线
 - 设定丰富但不堆砌
 - 对话精简有力，符合人物性格
-- 避免冗长的心理描写和环境描写
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 避免冗长的心理描写和环境描写"#.to_string(),
       temperature: 0.8,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 科幻 ====================
@@ -102,9 +241,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 保持科学设定的逻辑严谨
 - 智能分章，每章 2000-4000 字（根据用户设置）
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
-- 每章开头简要承接上文，过渡自然
+## 本类型分章要点
 - 章节结尾要留有悬念或开放性问题
 - 在关键科学发现、飞船抵达、危机爆发等情节分章
 
@@ -112,17 +249,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 强调科学感与逻辑闭环
 - 概念阐释清晰但不过度科普
 - 人物塑造立体，情感真实
-- 剧情推进有序，伏笔回收巧妙
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 剧情推进有序，伏笔回收巧妙"#.to_string(),
       temperature: 0.7,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 言情 ====================
@@ -137,8 +269,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 细腻描写人物情感变化
 - 智能分章，每章 2000-4000 字（根据用户设置）
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
+## 本类型分章要点
 - 每章开头简要承接上文，情感延续自然
 - 章节结尾要制造悬念或情感高潮
 - 在关键感情节点分章（告白、误会、和好、离别等）
@@ -147,17 +278,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 重视人物情绪与内心变化
 - 台词自然，符合人物性格
 - 节奏张弛有度，甜虐交织
-- 环境描写服务于情感氛围
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 环境描写服务于情感氛围"#.to_string(),
       temperature: 0.75,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 都市 ====================
@@ -172,9 +298,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 贴近现实又高于现实
 - 智能分章，每章 2000-4000 字（根据用户设置）
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
-- 每章开头简要承接上文，过渡自然
+## 本类型分章要点
 - 章节结尾要制造悬念或期待感
 - 在关键情节转折点分章
 
@@ -183,17 +307,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 生活流：烟火气，人情冷暖
 - 情感线：细腻真实
 - 金手指：合理适度
-- 装逼打脸：节奏干脆
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 装逼打脸：节奏干脆"#.to_string(),
       temperature: 0.7,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 悬疑推理 ====================
@@ -209,8 +328,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 气氛渲染到位
 - 智能分章，每章 2000-3000 字
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
+## 本类型分章要点
 - 每章结尾必须留有悬念
 - 在关键线索揭示、案件突破、惊人真相时分章
 - 让读者忍不住想看下一章
@@ -220,17 +338,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 埋线索要自然，回收要精彩
 - 气氛紧张压抑或诡异
 - 对话少而精，都是信息
-- 结局反转再反转
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 结局反转再反转"#.to_string(),
       temperature: 0.65,
       max_tokens: 32000,
       chapter_word_target: 2500,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 历史 ====================
@@ -246,8 +359,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 展现历史人物的魅力
 - 智能分章，每章 2000-4000 字
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
+## 本类型分章要点
 - 每章开头简要承接上文
 - 章节结尾可以是小高潮或悬念
 - 在重大历史事件、人物命运转折时分章
@@ -257,17 +369,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 称谓、礼仪、习俗符合时代
 - 权谋斗争：斗智斗勇
 - 战争描写：宏大惨烈
-- 人物群像：立体鲜活
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 人物群像：立体鲜活"#.to_string(),
       temperature: 0.7,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 武侠 ====================
@@ -283,8 +390,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 武功描写有想象力
 - 智能分章，每章 2000-4000 字
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
+## 本类型分章要点
 - 每章开头承接上文，江湖过渡
 - 章节结尾或紧张或惆怅
 - 在高手对决、秘籍现世、江湖恩怨时分章
@@ -294,17 +400,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 武功描写：意境大于招数
 - 人物：侠客风采，宗师气度
 - 对话：古风简约，有弦外之音
-- 场景：河山壮美，客栈、酒楼、码头
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 场景：河山壮美，客栈、酒楼、码头"#.to_string(),
       temperature: 0.75,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 军事 ====================
@@ -320,8 +421,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 战略战术体现智慧
 - 智能分章，每章 2000-4000 字
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
+## 本类型分章要点
 - 每章开头简要承接上文
 - 章节结尾可以是紧张战斗暂停或决策时刻
 - 在战役关键节点、战术转折、战略讨论时分章
@@ -331,17 +431,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 战术描写：专业但不晦涩
 - 战斗场面：紧张激烈
 - 人物：铁血柔情
-- 装备武器：考据但不堆砌
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 装备武器：考据但不堆砌"#.to_string(),
       temperature: 0.7,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 轻小说/二次元 ====================
@@ -357,8 +452,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 贴近年轻人审美
 - 智能分章，每章 1500-3000 字
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
+## 本类型分章要点
 - 每章开头可以是吐槽或日常切入
 - 章节结尾要抛梗或留悬念
 - 在日常搞笑、感动场面、冲突爆发时分章
@@ -380,6 +474,8 @@ pub fn default_agents() -> Vec<Agent> {
       temperature: 0.8,
       max_tokens: 32000,
       chapter_word_target: 2500,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string()],
     },
 
     // ==================== 现实主义/职场 ====================
@@ -395,8 +491,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 人物真实立体
 - 智能分章，每章 2000-4000 字
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
+## 本类型分章要点
 - 每章开头承接生活流
 - 章节结尾可以是矛盾爆发或平静下的暗流
 - 在关键人生抉择、矛盾冲突、社会事件时分章
@@ -406,17 +501,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 细节：来源于生活
 - 情感：克制但深刻
 - 社会观察：敏锐深刻
-- 结局：可以开放可以圆满
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 结局：可以开放可以圆满"#.to_string(),
       temperature: 0.65,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
 
     // ==================== 通用 ====================
@@ -431,8 +521,7 @@ pub fn default_agents() -> Vec<Agent> {
 - 保持剧情连贯和人物一致性
 - 智能分章，每章 2000-4000 字（根据用户设置，可调整）
 
-## 分章规则
-- 当单章内容接近目标字数时，自动总结本章并开启新章
+## 本类型分章要点
 - 每章开头简要承接上文
 - 章节结尾要制造悬念或期待感
 - 在剧情转折点、情节高潮、人物命运变化时分章
@@ -441,17 +530,12 @@ pub fn default_agents() -> Vec<Agent> {
 - 文字流畅，叙事清晰
 - 情节丰富但不冗余
 - 人物塑造立体
-- 符合所选题材的风格要求
-
-## 输出格式
-- 不使用 Markdown 格式（除非用户开启）
-- **必须使用 fs_write_text 工具将内容写入文件**，不要直接输出到对话中
-- 不使用空行或段首空格
-- 写入文件后，在对话中简要说明已写入的内容（如"已写入第X章"）
-- 如需分章，在章节结尾用"【本章完】"标记"#.to_string(),
+- 符合所选题材的风格要求"#.to_string(),
       temperature: 0.7,
       max_tokens: 32000,
       chapter_word_target: 3000,
+      parent_id: None,
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
     },
   ]
 }
@@ -459,3 +543,67 @@ pub fn default_agents() -> Vec<Agent> {
 fn agents_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
   app_data::data_file_path(app, "agents.json")
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn resolves_parents_and_fragments() {
+    let fragments = builtin_fragments();
+    let base = Agent {
+      id: "base".to_string(),
+      system_prompt: "基础规则".to_string(),
+      fragments: vec!["output_format".to_string()],
+      ..Default::default()
+    };
+    let child = Agent {
+      id: "child".to_string(),
+      parent_id: Some("base".to_string()),
+      system_prompt: "玄幻设定".to_string(),
+      fragments: vec!["chapter_rules".to_string(), "output_format".to_string()],
+      ..Default::default()
+    };
+    let agents = vec![base.clone(), child.clone()];
+    let prompt = resolve_system_prompt(&child, &agents, &fragments).unwrap();
+    assert!(prompt.starts_with("基础规则\n\n## 输出格式"));
+    assert!(prompt.ends_with("吸引读者继续阅读"));
+    assert_eq!(prompt.matches("## 输出格式").count(), 1);
+    assert!(prompt.find("玄幻设定").unwrap() < prompt.find("## 分章规则").unwrap());
+
+    let looped = Agent {
+      parent_id: Some("child".to_string()),
+      ..base
+    };
+    assert!(resolve_system_prompt(&child, &[looped, child.clone()], &fragments).unwrap_err().contains("cycle"));
+    let orphan = Agent {
+      parent_id: Some("missing".to_string()),
+      ..child
+    };
+    assert!(resolve_system_prompt(&orphan, &[], &fragments).unwrap_err().contains("not found"));
+  }
+
+  #[test]
+  fn default_agents_resolve() {
+    let agents = default_agents();
+    for a in &agents {
+      let prompt = resolve_system_prompt(a, &agents, &builtin_fragments()).unwrap();
+      assert!(prompt.contains("## 输出格式"), "{}", a.id);
+      // 通用分章规则只来自 chapter_rules 片段，提示词中只出现一次
+      assert_eq!(prompt.matches("## 分章规则").count(), 1, "{}", a.id);
+      assert_eq!(prompt.matches("自动总结本章并开启新章").count(), 1, "{}", a.id);
+    }
+  }
+
+  #[test]
+  fn loads_every_previous_version() {
+    let raw = include_str!("../fixtures/schema/agents.v0.json");
+    let (value, from) = SCHEMA.migrate(serde_json::from_str(raw).unwrap()).unwrap();
+    assert_eq!(from, 0);
+    let file: AgentsFile = serde_json::from_value(value).unwrap();
+    assert_eq!(file.agents.len(), 2);
+    assert_eq!(file.agents[1].name, "悬疑助手");
+    // 旧文件缺少的字段取默认值
+    assert_eq!(file.agents[1].chapter_word_target, 3000);
+  }
+}
@@ -1,12 +1,13 @@
 use crate::modification_types::{
-    ChangeSet, FileModification, FileModificationStatus, Modification, ModificationStatus,
-    ModificationType,
+    ChangeSet, FileModification, FileModificationStatus, FileOperation, Modification,
+    ModificationStatus, ModificationType,
 };
-use regex::Regex;
-use std::path::PathBuf;
+use crate::path_sandbox::PathSandbox;
+use serde::Serialize;
+use std::path::Path;
 
 /// Parse AI response text to extract file modification instructions
-/// 
+///
 /// Expected format:
 /// ```
 /// <file_edit path="stories/chapter-001.txt">
@@ -15,175 +16,759 @@ use std::path::PathBuf;
 /// </replace>
 /// </file_edit>
 /// ```
-pub fn parse_ai_response(
-    response: &str,
-    workspace_root: &PathBuf,
-) -> Result<Option<ChangeSet>, String> {
-    // Check if response contains file edit instructions
-    if !response.contains("<file_edit") {
-        return Ok(None);
-    }
-
-    let file_modifications = parse_file_edits(response, workspace_root)?;
-    
-    if file_modifications.is_empty() {
-        return Ok(None);
-    }
-
-    Ok(Some(ChangeSet::new(file_modifications)))
-}
-
-fn parse_file_edits(
-    response: &str,
-    workspace_root: &PathBuf,
-) -> Result<Vec<FileModification>, String> {
-    let file_edit_regex = Regex::new(
-        r#"<file_edit\s+path="([^"]+)">(.*?)</file_edit>"#
-    ).map_err(|e| format!("Failed to compile regex: {}", e))?;
-
-    let mut file_modifications = Vec::new();
-
-    for cap in file_edit_regex.captures_iter(response) {
-        let file_path = cap.get(1)
-            .ok_or("Missing file path")?
-            .as_str()
-            .to_string();
-        
-        let edit_content = cap.get(2)
-            .ok_or("Missing edit content")?
-            .as_str();
-
-        // Read original file content
-        let full_path = workspace_root.join(&file_path);
-        let original_content = std::fs::read_to_string(&full_path)
-            .map_err(|e| format!("Failed to read file {}: {}", file_path, e))?;
-
-        // Parse modifications within this file
-        let modifications = parse_modifications(edit_content)?;
-
-        if !modifications.is_empty() {
-            file_modifications.push(FileModification {
-                file_path,
-                original_content,
-                modifications,
-                status: FileModificationStatus::Pending,
-            });
+///
+/// 也支持按文本定位的编辑（不依赖模型数行号）：
+/// ```
+/// <file_edit path="stories/chapter-001.txt">
+/// <replace_text><find>原文片段</find><with>新内容</with></replace_text>
+/// <insert_after anchor="原文片段">插入内容</insert_after>
+/// </file_edit>
+/// ```
+/// 定位片段必须在文件中唯一出现。修改保持在回复中的先后顺序；
+/// 无法解析、越界、重叠或定位失败的修改不会进入 ChangeSet，而是作为诊断信息返回。
+///
+/// 回复用 [`FileEditStream`] 逐段喂入，`edits` 是 `push` 时已产出的块；
+/// 这里收尾剩余内容并解析为 ChangeSet。
+pub fn resolve_stream(stream: FileEditStream, mut edits: Vec<RawFileEdit>, workspace_root: &Path) -> ParseOutcome {
+    let mut outcome = ParseOutcome::default();
+    let (rest, diagnostics) = stream.finish();
+    edits.extend(rest);
+    outcome.diagnostics = diagnostics;
+
+    let sandbox = PathSandbox::new(workspace_root);
+    let files = resolve_blocks(&edits, &sandbox, &mut outcome.diagnostics);
+    if !files.is_empty() {
+        outcome.change_set = Some(ChangeSet::new(files));
+    }
+    outcome
+}
+
+/// 解析结果：可用的 ChangeSet 以及供界面展示的诊断信息。
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseOutcome {
+    pub change_set: Option<ChangeSet>,
+    pub diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl ParseOutcome {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseDiagnostic {
+    pub severity: DiagnosticSeverity,
+    /// 所属 `<file_edit>` 的路径
+    pub file_path: Option<String>,
+    /// 在 AI 回复中的字节偏移
+    pub offset: usize,
+    pub message: String,
+}
+
+impl ParseDiagnostic {
+    fn error(file_path: Option<&str>, offset: usize, message: String) -> Self {
+        Self {
+            severity: DiagnosticSeverity::Error,
+            file_path: file_path.map(|p| p.to_string()),
+            offset,
+            message,
         }
     }
 
-    Ok(file_modifications)
-}
-
-fn parse_modifications(edit_content: &str) -> Result<Vec<Modification>, String> {
-    let mut modifications = Vec::new();
-    let mut mod_counter = 0;
-
-    // Parse <replace> tags
-    let replace_regex = Regex::new(
-        r#"<replace\s+lines="(\d+)-(\d+)">(.*?)</replace>"#
-    ).map_err(|e| format!("Failed to compile replace regex: {}", e))?;
-
-    for cap in replace_regex.captures_iter(edit_content) {
-        let line_start: u32 = cap.get(1)
-            .ok_or("Missing line start")?
-            .as_str()
-            .parse()
-            .map_err(|e| format!("Invalid line start: {}", e))?;
-        
-        let line_end: u32 = cap.get(2)
-            .ok_or("Missing line end")?
-            .as_str()
-            .parse()
-            .map_err(|e| format!("Invalid line end: {}", e))?;
-        
-        let modified_text = cap.get(3)
-            .ok_or("Missing modified text")?
-            .as_str()
-            .trim()
-            .to_string();
-
-        modifications.push(Modification {
-            id: format!("mod-{}-{}", chrono::Utc::now().timestamp_millis(), mod_counter),
-            mod_type: ModificationType::Modify,
-            line_start,
-            line_end,
-            original_text: None, // Will be filled by frontend
-            modified_text: Some(modified_text),
-            status: ModificationStatus::Pending,
-        });
-        
-        mod_counter += 1;
-    }
-
-    // Parse <insert> tags
-    let insert_regex = Regex::new(
-        r#"<insert\s+at="(\d+)">(.*?)</insert>"#
-    ).map_err(|e| format!("Failed to compile insert regex: {}", e))?;
-
-    for cap in insert_regex.captures_iter(edit_content) {
-        let line_at: u32 = cap.get(1)
-            .ok_or("Missing line position")?
-            .as_str()
-            .parse()
-            .map_err(|e| format!("Invalid line position: {}", e))?;
-        
-        let modified_text = cap.get(2)
-            .ok_or("Missing insert text")?
-            .as_str()
-            .trim()
-            .to_string();
-
-        modifications.push(Modification {
-            id: format!("mod-{}-{}", chrono::Utc::now().timestamp_millis(), mod_counter),
-            mod_type: ModificationType::Add,
-            line_start: line_at,
-            line_end: line_at,
-            original_text: None,
-            modified_text: Some(modified_text),
-            status: ModificationStatus::Pending,
+    fn warning(file_path: Option<&str>, offset: usize, message: String) -> Self {
+        Self {
+            severity: DiagnosticSeverity::Warning,
+            ..Self::error(file_path, offset, message)
+        }
+    }
+}
+
+/// 单个编辑操作，按出现顺序保存。
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditOp {
+    ReplaceLines { start: u32, end: u32, text: String },
+    Insert { at: u32, text: String },
+    DeleteLines { start: u32, end: u32 },
+    ReplaceText { find: String, with: String },
+    InsertAfter { anchor: String, text: String },
+}
+
+/// 一个完整的顶层文件操作块。
+#[derive(Debug, Clone)]
+pub struct RawFileEdit {
+    pub path: String,
+    /// 标签在回复中的字节偏移
+    pub offset: usize,
+    pub action: FileAction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileAction {
+    /// `<file_edit>`：操作及其在回复中的字节偏移
+    Edit(Vec<(EditOp, usize)>),
+    /// `<create_file path="…">内容</create_file>`
+    Create(String),
+    /// `<delete_file path="…" />`
+    Delete,
+    /// `<rename_file from="…" to="…" />`
+    Rename { to: String },
+}
+
+/// 增量解析顶层文件操作标签：可以按流式 token 逐段喂入，只有闭合的块才会产出，
+/// 未完整的尾部会留在缓冲区里等待后续内容。
+#[derive(Default)]
+pub struct FileEditStream {
+    buf: String,
+    /// `buf[0]` 在整段回复中的字节偏移
+    base: usize,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+const FILE_EDIT_OPEN: &str = "<file_edit";
+const BLOCK_TAGS: [&str; 4] = ["file_edit", "create_file", "delete_file", "rename_file"];
+
+impl FileEditStream {
+    pub fn push(&mut self, chunk: &str) -> Vec<RawFileEdit> {
+        self.buf.push_str(chunk);
+        let mut out = Vec::new();
+        while let Some(edit) = self.next_block(false) {
+            out.push(edit);
+        }
+        out
+    }
+
+    /// 输入结束：未闭合的块不会被应用，只报告诊断。
+    pub fn finish(mut self) -> (Vec<RawFileEdit>, Vec<ParseDiagnostic>) {
+        let mut out = Vec::new();
+        while let Some(edit) = self.next_block(true) {
+            out.push(edit);
+        }
+        (out, self.diagnostics)
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.buf.drain(..n);
+        self.base += n;
+    }
+
+    /// 缓冲区中最早出现的顶层标签（位置、标签名）。
+    fn find_block_open(&self) -> Option<(usize, &'static str)> {
+        BLOCK_TAGS
+            .iter()
+            .filter_map(|name| self.buf.find(&format!("<{name}")).map(|i| (i, *name)))
+            .min_by_key(|(i, _)| *i)
+    }
+
+    fn next_block(&mut self, eof: bool) -> Option<RawFileEdit> {
+        loop {
+            let Some((open, name)) = self.find_block_open() else {
+                // 保留可能是某个顶层标签前缀的尾部
+                let keep = if eof {
+                    0
+                } else {
+                    BLOCK_TAGS
+                        .iter()
+                        .map(|name| partial_prefix_len(&self.buf, &format!("<{name}")))
+                        .max()
+                        .unwrap_or(0)
+                };
+                self.consume(self.buf.len() - keep);
+                return None;
+            };
+            self.consume(open);
+            let name_end = name.len() + 1;
+            let after_name = &self.buf[name_end..];
+            if !after_name.is_empty() && !after_name.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+                // 例如 "<file_editor"，不是顶层标签
+                self.consume(name_end);
+                continue;
+            }
+            let Some(tag_end) = find_tag_end(&self.buf) else {
+                if eof {
+                    self.diagnostics
+                        .push(ParseDiagnostic::error(None, self.base, format!("unterminated <{name}> tag")));
+                    let n = self.buf.len();
+                    self.consume(n);
+                }
+                return None;
+            };
+            let self_closing = self.buf[..tag_end].ends_with('/');
+            let attrs = parse_attrs(&self.buf[name_end..tag_end]);
+            let path_attr = if name == "rename_file" { "from" } else { "path" };
+            let path = attr(&attrs, path_attr).map(|v| v.to_string());
+            let body_start = tag_end + 1;
+
+            let (body_end, consumed) = if self_closing {
+                (body_start, body_start)
+            } else {
+                let close_tag = format!("</{name}>");
+                let close = self.buf[body_start..].find(&close_tag).map(|i| body_start + i);
+                // 只有 file_edit 的内容是标签，其它块的内容可能是任意正文
+                let next_open = if name == "file_edit" {
+                    self.buf[body_start..].find(FILE_EDIT_OPEN).map(|i| body_start + i)
+                } else {
+                    None
+                };
+                match (close, next_open) {
+                    (Some(c), None) => (c, c + close_tag.len()),
+                    (Some(c), Some(n)) if c < n => (c, c + close_tag.len()),
+                    (_, Some(n)) => {
+                        self.diagnostics.push(ParseDiagnostic::error(
+                            path.as_deref(),
+                            self.base,
+                            "<file_edit> is not closed before the next <file_edit>; block ignored".to_string(),
+                        ));
+                        self.consume(n);
+                        continue;
+                    }
+                    (None, None) => {
+                        if eof {
+                            self.diagnostics.push(ParseDiagnostic::error(
+                                path.as_deref(),
+                                self.base,
+                                format!("missing {close_tag}; block ignored"),
+                            ));
+                            let n = self.buf.len();
+                            self.consume(n);
+                        }
+                        return None;
+                    }
+                }
+            };
+
+            let offset = self.base;
+            let Some(path) = path.filter(|p| !p.trim().is_empty()) else {
+                self.diagnostics.push(ParseDiagnostic::error(
+                    None,
+                    offset,
+                    format!("<{name}> is missing the {path_attr} attribute"),
+                ));
+                self.consume(consumed);
+                continue;
+            };
+            let body = self.buf[body_start..body_end].to_string();
+            let action = match name {
+                "file_edit" => FileAction::Edit(parse_edit_ops(&body, offset + body_start, &path, &mut self.diagnostics)),
+                "create_file" => FileAction::Create(trim_newlines(&body).to_string()),
+                "delete_file" => FileAction::Delete,
+                _ => match attr(&attrs, "to").filter(|t| !t.trim().is_empty()) {
+                    Some(to) => FileAction::Rename { to: to.to_string() },
+                    None => {
+                        self.diagnostics.push(ParseDiagnostic::error(
+                            Some(&path),
+                            offset,
+                            "<rename_file> is missing the to attribute".to_string(),
+                        ));
+                        self.consume(consumed);
+                        continue;
+                    }
+                },
+            };
+            self.consume(consumed);
+            return Some(RawFileEdit { path, offset, action });
+        }
+    }
+}
+
+/// `s` 的尾部与 `pat` 前缀重合的最大长度。
+fn partial_prefix_len(s: &str, pat: &str) -> usize {
+    (1..pat.len())
+        .rev()
+        .find(|&n| s.len() >= n && s.is_char_boundary(s.len() - n) && pat.starts_with(&s[s.len() - n..]))
+        .unwrap_or(0)
+}
+
+/// 找到标签结尾 `>` 的位置，忽略引号内的 `>`。
+fn find_tag_end(s: &str) -> Option<usize> {
+    let mut in_quote = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_quote = !in_quote,
+            '>' if !in_quote => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_attrs(s: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut rest = s;
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq].trim().trim_start_matches('/').trim().to_string();
+        let value_start = eq + 2;
+        let Some(len) = rest[value_start..].find('"') else { break };
+        out.push((key, unescape(&rest[value_start..value_start + len])));
+        rest = &rest[value_start + len + 1..];
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&quot;", "\"").replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+}
+
+fn parse_line_range(value: Option<&str>) -> Result<(u32, u32), String> {
+    let value = value.ok_or("missing lines attribute")?;
+    let (a, b) = value.split_once('-').unwrap_or((value, value));
+    let start = a.trim().parse::<u32>().map_err(|_| format!("invalid lines: \"{value}\""))?;
+    let end = b.trim().parse::<u32>().map_err(|_| format!("invalid lines: \"{value}\""))?;
+    if start == 0 || end < start {
+        return Err(format!("invalid lines: \"{value}\""));
+    }
+    Ok((start, end))
+}
+
+fn trim_newlines(s: &str) -> &str {
+    s.trim_matches(|c| c == '\n' || c == '\r')
+}
+
+/// 按顺序解析一个 `<file_edit>` 内部的操作；`base` 为 body 在回复中的偏移。
+fn parse_edit_ops(body: &str, base: usize, path: &str, diagnostics: &mut Vec<ParseDiagnostic>) -> Vec<(EditOp, usize)> {
+    let mut ops = Vec::new();
+    let mut pos = 0usize;
+    while let Some(lt) = body[pos..].find('<').map(|i| pos + i) {
+        let offset = base + lt;
+        let stray = body[pos..lt].trim();
+        if !stray.is_empty() {
+            diagnostics.push(ParseDiagnostic::warning(
+                Some(path),
+                base + pos,
+                format!("ignored text outside edit tags: \"{}\"", stray.chars().take(30).collect::<String>()),
+            ));
+        }
+        let Some(tag_end) = find_tag_end(&body[lt..]).map(|i| lt + i) else {
+            diagnostics.push(ParseDiagnostic::error(Some(path), offset, "unterminated tag".to_string()));
+            return ops;
+        };
+        let tag = &body[lt + 1..tag_end];
+        let self_closing = tag.ends_with('/');
+        let name_len = tag.find(|c: char| c.is_whitespace() || c == '/').unwrap_or(tag.len());
+        let name = &tag[..name_len];
+        let attrs = parse_attrs(&tag[name_len..]);
+        let content_start = tag_end + 1;
+
+        // 取出 `</name>` 之前的内容；自闭合标签没有内容
+        let element = |close_required: bool| -> Result<(&str, usize), String> {
+            if self_closing {
+                return if close_required { Err(format!("<{name}> cannot be self-closing")) } else { Ok(("", content_start)) };
+            }
+            let close = format!("</{name}>");
+            match body[content_start..].find(&close) {
+                Some(i) => Ok((&body[content_start..content_start + i], content_start + i + close.len())),
+                None => Err(format!("missing </{name}>")),
+            }
+        };
+
+        let parsed: Result<(EditOp, usize), (String, usize)> = match name {
+            "replace" => element(true).map_err(|e| (e, body.len())).and_then(|(text, next)| {
+                parse_line_range(attr(&attrs, "lines"))
+                    .map(|(start, end)| (EditOp::ReplaceLines { start, end, text: trim_newlines(text).to_string() }, next))
+                    .map_err(|e| (e, next))
+            }),
+            "insert" => element(true).map_err(|e| (e, body.len())).and_then(|(text, next)| {
+                attr(&attrs, "at")
+                    .and_then(|v| v.trim().parse::<u32>().ok())
+                    .filter(|at| *at > 0)
+                    .map(|at| (EditOp::Insert { at, text: trim_newlines(text).to_string() }, next))
+                    .ok_or_else(|| ("invalid or missing at attribute".to_string(), next))
+            }),
+            "delete" => element(false).map_err(|e| (e, body.len())).and_then(|(_, next)| {
+                parse_line_range(attr(&attrs, "lines"))
+                    .map(|(start, end)| (EditOp::DeleteLines { start, end }, next))
+                    .map_err(|e| (e, next))
+            }),
+            "replace_text" => element(true).map_err(|e| (e, body.len())).and_then(|(inner, next)| {
+                let find = inner_element(inner, "find");
+                let with = inner_element(inner, "with");
+                match (find, with) {
+                    (Some(find), Some(with)) => Ok((
+                        EditOp::ReplaceText { find: trim_newlines(find).to_string(), with: trim_newlines(with).to_string() },
+                        next,
+                    )),
+                    _ => Err(("<replace_text> needs <find> and <with>".to_string(), next)),
+                }
+            }),
+            "insert_after" => element(true).map_err(|e| (e, body.len())).and_then(|(text, next)| {
+                attr(&attrs, "anchor")
+                    .map(|anchor| (EditOp::InsertAfter { anchor: anchor.to_string(), text: trim_newlines(text).to_string() }, next))
+                    .ok_or_else(|| ("missing anchor attribute".to_string(), next))
+            }),
+            _ => Err((format!("unknown tag <{name}>"), content_start)),
+        };
+
+        match parsed {
+            Ok((op, next)) => {
+                ops.push((op, offset));
+                pos = next;
+            }
+            Err((message, next)) => {
+                diagnostics.push(ParseDiagnostic::error(Some(path), offset, message));
+                pos = next;
+            }
+        }
+    }
+    let stray = body[pos..].trim();
+    if !stray.is_empty() {
+        diagnostics.push(ParseDiagnostic::warning(
+            Some(path),
+            base + pos,
+            format!("ignored text outside edit tags: \"{}\"", stray.chars().take(30).collect::<String>()),
+        ));
+    }
+    ops
+}
+
+fn inner_element<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{name}>");
+    let close = format!("</{name}>");
+    let start = s.find(&open)? + open.len();
+    let len = s[start..].find(&close)?;
+    Some(&s[start..start + len])
+}
+
+/// 解析过程中的单个文件条目；同一文件上的多个 `<file_edit>` 会合并到一起。
+struct PendingFile {
+    offset: usize,
+    path: String,
+    operation: FileOperation,
+    new_path: Option<String>,
+    original: String,
+    created: String,
+    ops: Vec<(EditOp, usize)>,
+}
+
+/// 校验路径、读取原文，把各个块换算成带原文的 FileModification；不合法的块只记诊断。
+///
+/// 重命名的文件上的 `<file_edit>`（无论写旧路径还是新路径）归入该重命名条目，
+/// 行号以重命名前的原文为准。
+fn resolve_blocks(edits: &[RawFileEdit], sandbox: &PathSandbox, diagnostics: &mut Vec<ParseDiagnostic>) -> Vec<FileModification> {
+    let mut pending: Vec<PendingFile> = Vec::new();
+    let mut claimed: Vec<String> = Vec::new();
+
+    // 先处理整文件操作，确定哪些路径被占用
+    for edit in edits {
+        let path = edit.path.replace('\\', "/");
+        let fail = |diagnostics: &mut Vec<ParseDiagnostic>, message: String| {
+            diagnostics.push(ParseDiagnostic::error(Some(&path), edit.offset, message));
+        };
+        let file = match &edit.action {
+            FileAction::Edit(_) => continue,
+            FileAction::Create(content) => match check_absent(sandbox, &path, &claimed) {
+                Ok(()) => PendingFile {
+                    offset: edit.offset,
+                    path: path.clone(),
+                    operation: FileOperation::Create,
+                    new_path: None,
+                    original: String::new(),
+                    created: content.clone(),
+                    ops: Vec::new(),
+                },
+                Err(e) => {
+                    fail(diagnostics, e);
+                    continue;
+                }
+            },
+            FileAction::Delete | FileAction::Rename { .. } => {
+                let original = match read_existing(sandbox, &path, &claimed) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        fail(diagnostics, e);
+                        continue;
+                    }
+                };
+                let new_path = match &edit.action {
+                    FileAction::Rename { to } => {
+                        let to = to.replace('\\', "/");
+                        if let Err(e) = check_absent(sandbox, &to, &claimed) {
+                            fail(diagnostics, format!("rename target: {e}"));
+                            continue;
+                        }
+                        Some(to)
+                    }
+                    _ => None,
+                };
+                PendingFile {
+                    offset: edit.offset,
+                    path: path.clone(),
+                    operation: if new_path.is_some() { FileOperation::Rename } else { FileOperation::Delete },
+                    new_path,
+                    original,
+                    created: String::new(),
+                    ops: Vec::new(),
+                }
+            }
+        };
+        claimed.push(file.path.clone());
+        claimed.extend(file.new_path.clone());
+        pending.push(file);
+    }
+
+    for edit in edits {
+        let FileAction::Edit(ops) = &edit.action else { continue };
+        let path = edit.path.replace('\\', "/");
+        let target = pending
+            .iter()
+            .position(|f| f.path == path || f.new_path.as_deref() == Some(path.as_str()));
+        match target.map(|i| &mut pending[i]) {
+            Some(f) if f.operation == FileOperation::Rename || f.operation == FileOperation::Edit => {
+                f.ops.extend(ops.iter().cloned());
+            }
+            Some(f) => diagnostics.push(ParseDiagnostic::error(
+                Some(&path),
+                edit.offset,
+                format!("cannot edit a file that is {} in the same response", if f.operation == FileOperation::Create { "created" } else { "deleted" }),
+            )),
+            None => match read_existing(sandbox, &path, &[]) {
+                Ok(original) => pending.push(PendingFile {
+                    offset: edit.offset,
+                    path,
+                    operation: FileOperation::Edit,
+                    new_path: None,
+                    original,
+                    created: String::new(),
+                    ops: ops.clone(),
+                }),
+                Err(e) => diagnostics.push(ParseDiagnostic::error(Some(&path), edit.offset, e)),
+            },
+        }
+    }
+
+    pending.sort_by_key(|f| f.offset);
+    let mut counter = 0usize;
+    let mut files = Vec::new();
+    for f in pending {
+        let mut modifications = Vec::new();
+        let line_end = |text: &str| (text.lines().count() as u32).max(1);
+        let file_level = match f.operation {
+            FileOperation::Edit => None,
+            FileOperation::Create => Some((ModificationType::CreateFile, line_end(&f.created), None, Some(f.created.clone()))),
+            FileOperation::Delete => Some((ModificationType::DeleteFile, line_end(&f.original), Some(f.original.clone()), None)),
+            FileOperation::Rename => Some((ModificationType::RenameFile, line_end(&f.original), Some(f.path.clone()), f.new_path.clone())),
+        };
+        if let Some((mod_type, end, original_text, modified_text)) = file_level {
+            modifications.push(Modification {
+                id: format!("mod-{}-{}", chrono::Utc::now().timestamp_millis(), counter),
+                mod_type,
+                line_start: 1,
+                line_end: end,
+                original_text,
+                modified_text,
+                status: ModificationStatus::Pending,
+            });
+            counter += 1;
+        }
+        modifications.extend(resolve_ops(&f.ops, &f.original, &f.path, &mut counter, diagnostics));
+        if modifications.is_empty() {
+            continue;
+        }
+        files.push(FileModification {
+            file_path: f.path,
+            original_content: f.original,
+            modifications,
+            status: FileModificationStatus::Pending,
+            operation: f.operation,
+            new_path: f.new_path,
         });
-        
-        mod_counter += 1;
-    }
-
-    // Parse <delete> tags
-    let delete_regex = Regex::new(
-        r#"<delete\s+lines="(\d+)-(\d+)"\s*/>"#
-    ).map_err(|e| format!("Failed to compile delete regex: {}", e))?;
-
-    for cap in delete_regex.captures_iter(edit_content) {
-        let line_start: u32 = cap.get(1)
-            .ok_or("Missing line start")?
-            .as_str()
-            .parse()
-            .map_err(|e| format!("Invalid line start: {}", e))?;
-        
-        let line_end: u32 = cap.get(2)
-            .ok_or("Missing line end")?
-            .as_str()
-            .parse()
-            .map_err(|e| format!("Invalid line end: {}", e))?;
-
-        modifications.push(Modification {
-            id: format!("mod-{}-{}", chrono::Utc::now().timestamp_millis(), mod_counter),
-            mod_type: ModificationType::Delete,
+    }
+    files
+}
+
+fn read_existing(sandbox: &PathSandbox, path: &str, claimed: &[String]) -> Result<String, String> {
+    if claimed.iter().any(|c| c == path) {
+        return Err(format!("{path} is already used by another file operation"));
+    }
+    let full = sandbox.resolve(path)?;
+    if !full.is_file() {
+        return Err(format!("file {path} does not exist; use <create_file> for new files"));
+    }
+    std::fs::read_to_string(&full).map_err(|e| format!("Failed to read file {}: {}", path, e))
+}
+
+fn check_absent(sandbox: &PathSandbox, path: &str, claimed: &[String]) -> Result<(), String> {
+    if claimed.iter().any(|c| c == path) {
+        return Err(format!("{path} is already used by another file operation"));
+    }
+    if sandbox.resolve(path)?.exists() {
+        return Err(format!("{path} already exists"));
+    }
+    Ok(())
+}
+
+/// 类型、起止行、原文、新内容
+type ResolvedOp = (ModificationType, u32, u32, Option<String>, Option<String>);
+
+fn resolve_ops(
+    ops: &[(EditOp, usize)],
+    content: &str,
+    path: &str,
+    counter: &mut usize,
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> Vec<Modification> {
+    let lines: Vec<&str> = content.lines().collect();
+    let line_count = lines.len() as u32;
+    let original_lines = |start: u32, end: u32| lines[(start - 1) as usize..end as usize].join("\n");
+
+    let mut modifications: Vec<Modification> = Vec::new();
+    for (op, offset) in ops {
+        let resolved: Result<ResolvedOp, String> = match op {
+            EditOp::ReplaceLines { start, end, text } => {
+                if *end > line_count {
+                    Err(format!("lines {start}-{end} out of range (file has {line_count} lines)"))
+                } else {
+                    Ok((ModificationType::Modify, *start, *end, Some(original_lines(*start, *end)), Some(text.clone())))
+                }
+            }
+            EditOp::DeleteLines { start, end } => {
+                if *end > line_count {
+                    Err(format!("lines {start}-{end} out of range (file has {line_count} lines)"))
+                } else {
+                    Ok((ModificationType::Delete, *start, *end, Some(original_lines(*start, *end)), None))
+                }
+            }
+            EditOp::Insert { at, text } => {
+                if *at > line_count + 1 {
+                    Err(format!("insert position {at} out of range (file has {line_count} lines)"))
+                } else {
+                    Ok((ModificationType::Add, *at, *at, None, Some(text.clone())))
+                }
+            }
+            EditOp::ReplaceText { find, with } => locate_anchor(content, find).map(|start| {
+                let (line_start, line_end, first_line_start, last_line_end) =
+                    line_span(content, start, start + find.len());
+                let prefix = &content[first_line_start..start];
+                let suffix = &content[start + find.len()..last_line_end];
+                (
+                    ModificationType::Modify,
+                    line_start,
+                    line_end,
+                    Some(content[first_line_start..last_line_end].to_string()),
+                    Some(format!("{prefix}{with}{suffix}")),
+                )
+            }).map_err(|e| format!("replace_text: {e}")),
+            EditOp::InsertAfter { anchor, text } => locate_anchor(content, anchor).map(|start| {
+                let (_, line_end, _, _) = line_span(content, start, start + anchor.len());
+                (ModificationType::Add, line_end + 1, line_end + 1, None, Some(text.clone()))
+            }).map_err(|e| format!("insert_after: {e}")),
+        };
+
+        let (mod_type, line_start, line_end, original_text, modified_text) = match resolved {
+            Ok(v) => v,
+            Err(e) => {
+                diagnostics.push(ParseDiagnostic::error(Some(path), *offset, e));
+                continue;
+            }
+        };
+        let candidate = Modification {
+            id: format!("mod-{}-{}", chrono::Utc::now().timestamp_millis(), counter),
+            mod_type,
             line_start,
             line_end,
-            original_text: None, // Will be filled by frontend
-            modified_text: None,
+            original_text,
+            modified_text,
             status: ModificationStatus::Pending,
-        });
-        
-        mod_counter += 1;
+        };
+        if let Some(other) = modifications.iter().find(|m| overlaps(m, &candidate)) {
+            diagnostics.push(ParseDiagnostic::error(
+                Some(path),
+                *offset,
+                format!(
+                    "lines {}-{} overlap an earlier edit at lines {}-{}; edit ignored",
+                    candidate.line_start, candidate.line_end, other.line_start, other.line_end
+                ),
+            ));
+            continue;
+        }
+        *counter += 1;
+        modifications.push(candidate);
+    }
+    modifications
+}
+
+/// 插入点位于第 n 行之前，与覆盖该位置内部的修改/删除冲突；两个区间有交集即冲突。
+fn overlaps(a: &Modification, b: &Modification) -> bool {
+    let is_add = |m: &Modification| matches!(m.mod_type, ModificationType::Add);
+    match (is_add(a), is_add(b)) {
+        (true, true) => false,
+        (true, false) => b.line_start < a.line_start && a.line_start <= b.line_end,
+        (false, true) => a.line_start < b.line_start && b.line_start <= a.line_end,
+        (false, false) => a.line_start <= b.line_end && b.line_start <= a.line_end,
+    }
+}
+
+/// 返回定位片段在文件中的字节偏移；片段必须唯一出现。
+fn locate_anchor(content: &str, anchor: &str) -> Result<usize, String> {
+    if anchor.trim().is_empty() {
+        return Err("empty anchor".to_string());
+    }
+    let preview: String = anchor.chars().take(30).collect();
+    let mut matches = content.match_indices(anchor);
+    let first = matches.next().ok_or_else(|| format!("anchor not found: \"{preview}\""))?;
+    let extra = matches.count();
+    if extra > 0 {
+        return Err(format!("anchor is ambiguous ({} matches): \"{preview}\"", extra + 1));
     }
+    Ok(first.0)
+}
 
-    Ok(modifications)
+/// 字节区间 [start, end) 覆盖的行号（1 起）以及这些整行的字节范围（不含末尾换行）。
+fn line_span(content: &str, start: usize, end: usize) -> (u32, u32, usize, usize) {
+    let bytes = content.as_bytes();
+    // 片段最后一个字节所在的行；按字节统计换行，避免在多字节字符中间切片
+    let last = end.saturating_sub(1).max(start);
+    let count_newlines = |upto: usize| bytes[..upto].iter().filter(|b| **b == b'\n').count() as u32;
+    let line_start = count_newlines(start) + 1;
+    let line_end = count_newlines(last) + 1;
+    let first_line_start = bytes[..start].iter().rposition(|b| *b == b'\n').map(|i| i + 1).unwrap_or(0);
+    let mut last_line_end = bytes[last..].iter().position(|b| *b == b'\n').map(|i| last + i).unwrap_or(bytes.len());
+    if last_line_end > first_line_start && bytes[last_line_end - 1] == b'\r' && last_line_end > end {
+        last_line_end -= 1;
+    }
+    (line_start, line_end, first_line_start, last_line_end)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn parse_ai_response(response: &str, workspace_root: &Path) -> ParseOutcome {
+        let mut stream = FileEditStream::default();
+        let edits = stream.push(response);
+        resolve_stream(stream, edits, workspace_root)
+    }
+    use crate::test_util::TempDir;
+
+    /// 针对一个 40 行的文件解析单个 `<file_edit>` 的内容，有错误诊断时返回 Err。
+    fn parse_modifications(content: &str) -> Result<Vec<Modification>, String> {
+        let file = (1..=40).map(|i| format!("line {i}")).collect::<Vec<_>>().join("\n");
+        let (mods, diagnostics) = resolve(content, &file);
+        match diagnostics.iter().find(|d| d.severity == DiagnosticSeverity::Error) {
+            Some(d) => Err(d.message.clone()),
+            None => Ok(mods),
+        }
+    }
+
+    fn resolve(content: &str, file: &str) -> (Vec<Modification>, Vec<ParseDiagnostic>) {
+        let mut diagnostics = Vec::new();
+        let ops = parse_edit_ops(content, 0, "test.txt", &mut diagnostics);
+        let mods = resolve_ops(&ops, file, "test.txt", &mut 0, &mut diagnostics);
+        (mods, diagnostics)
+    }
+
     #[test]
     fn test_parse_modifications_replace() {
         let content = r#"<replace lines="10-15">
@@ -235,4 +820,157 @@ Inserted line
         let mods = parse_modifications(content).unwrap();
         assert_eq!(mods.len(), 3);
     }
+
+    const CHAPTER: &str = "第一行。\n林渊推开门，看见师父。\n第三行。\n林渊推开窗。\n";
+
+    #[test]
+    fn test_replace_text_resolves_to_lines() {
+        let content = "<replace_text><find>看见师父</find><with>看见一道剑光</with></replace_text>";
+        let (mods, diagnostics) = resolve(content, CHAPTER);
+        assert!(diagnostics.is_empty());
+        assert_eq!(mods.len(), 1);
+        assert_eq!((mods[0].line_start, mods[0].line_end), (2, 2));
+        assert_eq!(mods[0].original_text.as_deref(), Some("林渊推开门，看见师父。"));
+        assert_eq!(mods[0].modified_text.as_deref(), Some("林渊推开门，看见一道剑光。"));
+    }
+
+    #[test]
+    fn test_replace_text_spanning_lines() {
+        let content = "<replace_text>\n<find>\n师父。\n第三行\n</find>\n<with>师父。</with>\n</replace_text>";
+        let (mods, _) = resolve(content, CHAPTER);
+        assert_eq!((mods[0].line_start, mods[0].line_end), (2, 3));
+        assert_eq!(mods[0].modified_text.as_deref(), Some("林渊推开门，看见师父。。"));
+    }
+
+    #[test]
+    fn test_insert_after_anchor() {
+        let content = "<insert_after anchor=\"第三行\">\n新的一行\n</insert_after>";
+        let (mods, _) = resolve(content, CHAPTER);
+        assert!(matches!(mods[0].mod_type, ModificationType::Add));
+        assert_eq!(mods[0].line_start, 4);
+        assert_eq!(mods[0].modified_text.as_deref(), Some("新的一行"));
+    }
+
+    #[test]
+    fn test_missing_and_ambiguous_anchors_are_errors() {
+        let missing = "<insert_after anchor=\"不存在\">x</insert_after>";
+        let (mods, diagnostics) = resolve(missing, CHAPTER);
+        assert!(mods.is_empty());
+        assert!(diagnostics[0].message.contains("not found"));
+        let ambiguous = "<replace_text><find>林渊推开</find><with>x</with></replace_text>";
+        let (_, diagnostics) = resolve(ambiguous, CHAPTER);
+        assert!(diagnostics[0].message.contains("ambiguous (2 matches)"));
+    }
+
+    #[test]
+    fn test_keeps_document_order_and_fills_original_text() {
+        let content = r#"<delete lines="3-3" /><insert at="1">首</insert><replace lines="2-2">二</replace>"#;
+        let (mods, diagnostics) = resolve(content, CHAPTER);
+        assert!(diagnostics.is_empty());
+        let types = mods.iter().map(|m| m.mod_type.clone()).collect::<Vec<_>>();
+        assert!(matches!(types[..], [ModificationType::Delete, ModificationType::Add, ModificationType::Modify]));
+        assert_eq!(mods[0].original_text.as_deref(), Some("第三行。"));
+        assert_eq!(mods[2].original_text.as_deref(), Some("林渊推开门，看见师父。"));
+    }
+
+    #[test]
+    fn test_flags_overlaps_and_out_of_range() {
+        let content = r#"<replace lines="1-2">x</replace><delete lines="2-3" /><insert at="2">y</insert><delete lines="4-9" /><replace lines="x-2">z</replace>"#;
+        let (mods, diagnostics) = resolve(content, CHAPTER);
+        assert_eq!(mods.len(), 1);
+        let messages = diagnostics.iter().map(|d| d.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages.len(), 4);
+        // 标签本身无法解析的错误在读取文件之前就会报告
+        assert!(messages[0].contains("invalid lines"));
+        assert!(messages[1].contains("overlap"));
+        assert!(messages[2].contains("overlap"));
+        assert!(messages[3].contains("out of range"));
+        assert_eq!(diagnostics[2].offset, content.find("<insert").unwrap());
+    }
+
+    #[test]
+    fn test_stream_yields_blocks_only_when_closed() {
+        let response = "好的。<file_edit path=\"a.txt\"><insert at=\"1\">甲</insert></file_edit>中间<file_edit path=\"b.txt\"><delete lines=\"1-1\" /></file_edit>";
+        let mut stream = FileEditStream::default();
+        let mut blocks = Vec::new();
+        for ch in response.chars() {
+            let mut buf = [0u8; 4];
+            blocks.extend(stream.push(ch.encode_utf8(&mut buf)));
+        }
+        let (rest, diagnostics) = stream.finish();
+        assert!(rest.is_empty());
+        assert!(diagnostics.is_empty());
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].path, "a.txt");
+        assert_eq!(blocks[0].offset, response.find("<file_edit").unwrap());
+        assert_eq!(blocks[1].action, FileAction::Edit(vec![(EditOp::DeleteLines { start: 1, end: 1 }, response.find("<delete").unwrap())]));
+    }
+
+    #[test]
+    fn test_line_edits_keep_paragraph_indent() {
+        let mut stream = FileEditStream::default();
+        let blocks = stream.push("<file_edit path=\"a.txt\"><replace lines=\"1-1\">\n　　新的开头。\n</replace><insert at=\"2\">　　插入段。</insert></file_edit>");
+        let FileAction::Edit(ops) = &blocks[0].action else { panic!("expected edit") };
+        assert_eq!(ops[0].0, EditOp::ReplaceLines { start: 1, end: 1, text: "　　新的开头。".to_string() });
+        assert_eq!(ops[1].0, EditOp::Insert { at: 2, text: "　　插入段。".to_string() });
+    }
+
+    #[test]
+    fn test_unclosed_block_is_reported_not_applied() {
+        let mut stream = FileEditStream::default();
+        assert!(stream.push("<file_edit path=\"a.txt\"><replace lines=\"1-1\">半截").is_empty());
+        let (rest, diagnostics) = stream.finish();
+        assert!(rest.is_empty());
+        assert!(diagnostics[0].message.contains("missing </file_edit>"));
+    }
+
+    #[test]
+    fn test_parse_ai_response_reports_bad_paths() {
+        let root = TempDir::new("parser");
+        std::fs::create_dir_all(root.join("stories")).unwrap();
+        std::fs::write(root.join("stories/ch1.txt"), CHAPTER).unwrap();
+        let response = r#"<file_edit path="../secret.txt"><delete lines="1-1" /></file_edit>
+<create_file path=".git/hooks/post-checkout">echo pwned</create_file>
+<file_edit path="stories/ch1.txt"><replace_text><find>第三行</find><with>第三段</with></replace_text></file_edit>"#;
+        let outcome = parse_ai_response(response, &root);
+        assert!(outcome.has_errors());
+        let bad: Vec<_> = outcome.diagnostics.iter().filter_map(|d| d.file_path.as_deref()).collect();
+        assert!(bad.contains(&"../secret.txt"));
+        assert!(outcome.diagnostics.iter().any(|d| d.message.contains("protected")));
+        let cs = outcome.change_set.unwrap();
+        assert_eq!(cs.files.len(), 1);
+        assert_eq!(cs.files[0].modifications[0].modified_text.as_deref(), Some("第三段。"));
+    }
+
+    #[test]
+    fn test_split_chapter_as_file_operations() {
+        let root = TempDir::new("parser");
+        std::fs::create_dir_all(root.join("stories")).unwrap();
+        std::fs::write(root.join("stories/ch5.txt"), CHAPTER).unwrap();
+        std::fs::write(root.join("stories/ch6.txt"), "旧的第六章").unwrap();
+        let response = r#"<rename_file from="stories/ch5.txt" to="stories/ch5a.txt" />
+<file_edit path="stories/ch5a.txt"><delete lines="3-4" /></file_edit>
+<create_file path="stories/ch5b.txt">
+第三行。
+林渊推开窗。
+</create_file>
+<create_file path="stories/ch6.txt">重复</create_file>
+<file_edit path="stories/missing.txt"><insert at="1">x</insert></file_edit>"#;
+        let outcome = parse_ai_response(response, &root);
+        let messages = outcome.diagnostics.iter().map(|d| d.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("already exists"));
+        assert!(messages[1].contains("does not exist"));
+
+        let cs = outcome.change_set.unwrap();
+        assert_eq!(cs.files.len(), 2);
+        let renamed = &cs.files[0];
+        assert_eq!(renamed.operation, FileOperation::Rename);
+        assert_eq!(renamed.new_path.as_deref(), Some("stories/ch5a.txt"));
+        assert!(matches!(renamed.modifications[0].mod_type, ModificationType::RenameFile));
+        assert!(matches!(renamed.modifications[1].mod_type, ModificationType::Delete));
+        let created = &cs.files[1];
+        assert_eq!(created.operation, FileOperation::Create);
+        assert_eq!(created.modifications[0].modified_text.as_deref(), Some("第三行。\n林渊推开窗。"));
+    }
 }
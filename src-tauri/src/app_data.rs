@@ -1,19 +1,163 @@
-use std::fs;
-use std::path::PathBuf;
+use crate::branding;
+use serde::Serialize;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::Manager;
+
+/// 可执行文件旁存在该文件时进入便携模式，数据保存在可执行文件旁的 data 目录
+pub const PORTABLE_MARKER: &str = "portable";
+
+/// 旧数据目录中至少要有其中一个文件才会被迁移，避免把无关的 `data/` 目录复制进来
+const KNOWN_FILES: [&str; 4] = ["settings.json", "agents.json", "secrets.json", "chat_history.json"];
+/// 迁移（或确认无需迁移）完成后写入数据目录
+const MIGRATED_MARKER: &str = ".migrated";
+/// 复制开始前写入，复制完成后删除；存在时说明上次迁移中途中断
+const MIGRATING_MARKER: &str = ".migrating";
+
+static DATA_DIR: OnceLock<DataDirInfo> = OnceLock::new();
+/// 串行化首次解析与迁移，避免同时启动的命令重复迁移到同一目录
+static DATA_DIR_INIT: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataDirInfo {
+  pub path: String,
+  pub portable: bool,
+}
+
+pub fn data_file_path(app: &tauri::AppHandle, file_name: &str) -> Result<PathBuf, String> {
+  Ok(PathBuf::from(data_dir_info(app)?.path).join(file_name))
+}
+
+/// 解析数据目录；首次运行时从旧位置迁移已有数据，结果在进程内缓存。
+pub fn data_dir_info(app: &tauri::AppHandle) -> Result<DataDirInfo, String> {
+  if let Some(info) = DATA_DIR.get() {
+    return Ok(info.clone());
+  }
+  let _guard = DATA_DIR_INIT.lock().map_err(|_| "data dir lock poisoned")?;
+  if let Some(info) = DATA_DIR.get() {
+    return Ok(info.clone());
+  }
+  let exe = env::current_exe().map_err(|e| format!("get current exe failed: {e}"))?;
+  let exe_dir = exe.parent().ok_or("get exe dir failed")?;
+  let platform_dir = app.path().data_dir().map_err(|e| format!("get platform data dir failed: {e}"))?;
+  let (data_dir, portable) = resolve_data_dir(exe_dir, &platform_dir);
+
+  // 旧版本把数据写在启动时工作目录下的 data 中，更早的版本使用旧的目录名
+  let mut legacy = Vec::new();
+  if let Ok(cwd) = env::current_dir() {
+    legacy.push(cwd.join("data"));
+  }
+  legacy.push(exe_dir.join("data"));
+  legacy.push(platform_dir.join(branding::LEGACY_DATA_DIR_NAME));
+  if let Some(from) = migrate_legacy(&data_dir, &legacy)? {
+    eprintln!("migrated data dir from {}", from.display());
+  }
 
-pub fn data_file_path(_app: &tauri::AppHandle, file_name: &str) -> Result<PathBuf, String> {
-  // 使用当前工作目录下的 data 文件夹（便携式设计）
-  let base = env::current_dir()
-    .map_err(|e| format!("get current dir failed: {e}"))?;
+  let info = DataDirInfo {
+    path: data_dir.to_string_lossy().to_string(),
+    portable,
+  };
+  Ok(DATA_DIR.get_or_init(|| info).clone())
+}
+
+/// 便携模式使用 `<可执行文件目录>/data`，否则使用 `<平台数据目录>/<DATA_DIR_NAME>`。
+fn resolve_data_dir(exe_dir: &Path, platform_dir: &Path) -> (PathBuf, bool) {
+  if exe_dir.join(PORTABLE_MARKER).is_file() {
+    (exe_dir.join("data"), true)
+  } else {
+    (platform_dir.join(branding::DATA_DIR_NAME), false)
+  }
+}
 
-  let data_dir = base.join("data");
-  let new_path = data_dir.join(file_name);
+/// 把第一个含有本应用数据文件的旧目录复制到 `target`，返回被迁移的目录；旧目录保留不动。
+///
+/// 完成后写入标记，之后不再迁移；上次复制中断时重新迁移。已有数据但没有标记的目录（旧版本创建）视为已迁移。
+fn migrate_legacy(target: &Path, candidates: &[PathBuf]) -> Result<Option<PathBuf>, String> {
+  let done = target.join(MIGRATED_MARKER);
+  if done.exists() {
+    return Ok(None);
+  }
+  let interrupted = target.join(MIGRATING_MARKER).exists();
+  let has_data = fs::read_dir(target).map(|mut it| it.next().is_some()).unwrap_or(false);
+  let from = if has_data && !interrupted {
+    None
+  } else {
+    candidates
+      .iter()
+      .find(|dir| *dir != target && KNOWN_FILES.iter().any(|f| dir.join(f).is_file()))
+  };
 
-  // 确保 data 目录存在
-  if !data_dir.exists() {
-    fs::create_dir_all(&data_dir).map_err(|e| format!("create data dir failed: {e}"))?;
+  fs::create_dir_all(target).map_err(|e| format!("create data dir failed: {e}"))?;
+  if let Some(dir) = from {
+    let marker = target.join(MIGRATING_MARKER);
+    fs::write(&marker, "").map_err(|e| format!("write migration marker failed: {e}"))?;
+    copy_dir(dir, target).map_err(|e| format!("migrate data dir from {} failed: {e}", dir.display()))?;
+    fs::remove_file(&marker).map_err(|e| format!("remove migration marker failed: {e}"))?;
   }
+  fs::write(&done, "").map_err(|e| format!("write migration marker failed: {e}"))?;
+  Ok(from.cloned())
+}
 
-  Ok(new_path)
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+  fs::create_dir_all(to)?;
+  for entry in fs::read_dir(from)? {
+    let entry = entry?;
+    let dest = to.join(entry.file_name());
+    if entry.file_type()?.is_dir() {
+      copy_dir(&entry.path(), &dest)?;
+    } else {
+      fs::copy(entry.path(), dest)?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::TempDir;
+
+  #[test]
+  fn portable_marker_and_legacy_migration() {
+    let root = TempDir::new("data");
+    let exe_dir = root.join("bin");
+    let platform = root.join("share");
+    fs::create_dir_all(&exe_dir).unwrap();
+
+    let (dir, portable) = resolve_data_dir(&exe_dir, &platform);
+    assert_eq!(dir, platform.join(branding::DATA_DIR_NAME));
+    assert!(!portable);
+    fs::write(exe_dir.join(PORTABLE_MARKER), "").unwrap();
+    assert_eq!(resolve_data_dir(&exe_dir, &platform), (exe_dir.join("data"), true));
+
+    let empty = root.join("empty");
+    let unrelated = root.join("home").join("data");
+    let legacy = platform.join(branding::LEGACY_DATA_DIR_NAME);
+    fs::create_dir_all(&empty).unwrap();
+    fs::create_dir_all(&unrelated).unwrap();
+    fs::write(unrelated.join("photo.jpg"), "").unwrap();
+    fs::create_dir_all(legacy.join("nested")).unwrap();
+    fs::write(legacy.join("settings.json"), "{}").unwrap();
+    fs::write(legacy.join("nested").join("a.txt"), "甲").unwrap();
+
+    let target = platform.join(branding::DATA_DIR_NAME);
+    let candidates = [root.join("missing"), empty, unrelated, legacy.clone()];
+    let from = migrate_legacy(&target, &candidates).unwrap();
+    assert_eq!(from, Some(legacy.clone()));
+    assert_eq!(fs::read_to_string(target.join("nested").join("a.txt")).unwrap(), "甲");
+    assert!(!target.join("photo.jpg").exists());
+    assert!(legacy.join("settings.json").exists());
+    assert_eq!(migrate_legacy(&target, &candidates).unwrap(), None);
+
+    // 中断的迁移会重来；旧版本留下的、没有标记的数据目录保持原样
+    fs::remove_file(target.join(MIGRATED_MARKER)).unwrap();
+    fs::write(target.join(MIGRATING_MARKER), "").unwrap();
+    assert_eq!(migrate_legacy(&target, &candidates).unwrap(), Some(legacy.clone()));
+    assert!(!target.join(MIGRATING_MARKER).exists());
+    fs::remove_file(target.join(MIGRATED_MARKER)).unwrap();
+    assert_eq!(migrate_legacy(&target, &candidates).unwrap(), None);
+    assert!(target.join(MIGRATED_MARKER).exists());
+  }
 }
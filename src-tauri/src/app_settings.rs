@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fs;
 use std::path::PathBuf;
 
 use crate::app_data;
+use crate::schema::{self, Schema};
 use crate::secrets;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -168,73 +170,88 @@ fn ensure_sane(mut s: AppSettings) -> AppSettings {
   s
 }
 
+/// 版本 0：`providers` 为按厂商分组的对象；版本 1：`providers` 为列表，没有版本号。
+pub const SCHEMA: Schema = Schema {
+  name: "settings",
+  current: 2,
+  detect: detect_version,
+  migrations: &[migrate_legacy_providers, schema::unchanged],
+};
+
+fn detect_version(value: &Value) -> u32 {
+  if value.get("providers").is_some_and(|p| p.is_object()) {
+    0
+  } else {
+    1
+  }
+}
+
+fn migrate_legacy_providers(value: Value) -> Result<Value, String> {
+  let legacy: LegacyAppSettings = serde_json::from_value(value).map_err(|e| format!("parse legacy settings failed: {e}"))?;
+  let mut providers = vec![
+    ModelProvider {
+      id: "openai".to_string(),
+      name: "OpenAI".to_string(),
+      kind: ProviderKind::OpenAI,
+      api_key: legacy.providers.openai.api_key.clone(),
+      base_url: legacy.providers.openai.base_url.clone(),
+      model_name: legacy.providers.openai.model.clone(),
+    },
+    ModelProvider {
+      id: "claude".to_string(),
+      name: "Claude".to_string(),
+      kind: ProviderKind::Anthropic,
+      api_key: legacy.providers.claude.api_key.clone(),
+      base_url: "https://api.anthropic.com".to_string(),
+      model_name: legacy.providers.claude.model.clone(),
+    },
+    ModelProvider {
+      id: "wenxin".to_string(),
+      name: "文心一言".to_string(),
+      kind: ProviderKind::OpenAICompatible,
+      api_key: legacy.providers.wenxin.api_key.clone(),
+      base_url: legacy.providers.wenxin.base_url.clone(),
+      model_name: legacy.providers.wenxin.model.clone(),
+    },
+  ];
+  if !providers.iter().any(|p| p.id == "deepseek") {
+    providers.push(ModelProvider {
+      id: "deepseek".to_string(),
+      name: "DeepSeek".to_string(),
+      kind: ProviderKind::OpenAICompatible,
+      api_key: String::new(),
+      base_url: "https://api.deepseek.com".to_string(),
+      model_name: "deepseek-chat".to_string(),
+    });
+  }
+
+  let migrated = ensure_sane(AppSettings {
+    output: legacy.output,
+    providers,
+    active_provider_id: legacy.providers.active,
+    active_agent_id: legacy.active_agent_id,
+  });
+  serde_json::to_value(migrated).map_err(|e| format!("serialize settings failed: {e}"))
+}
+
 pub fn load(app: &tauri::AppHandle) -> Result<AppSettings, String> {
   let path = settings_path(app)?;
-  if !path.exists() {
+  let Some(value) = schema::read(&path, &SCHEMA)? else {
     return Ok(ensure_sane(AppSettings::default()));
-  }
-  let raw = fs::read_to_string(&path).map_err(|e| format!("read settings failed: {e}"))?;
-  match serde_json::from_str::<AppSettings>(&raw) {
-    Ok(v) => Ok(ensure_sane(v)),
-    Err(new_err) => match serde_json::from_str::<LegacyAppSettings>(&raw) {
-      Ok(legacy) => {
-        let mut providers = vec![
-          ModelProvider {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            kind: ProviderKind::OpenAI,
-            api_key: legacy.providers.openai.api_key.clone(),
-            base_url: legacy.providers.openai.base_url.clone(),
-            model_name: legacy.providers.openai.model.clone(),
-          },
-          ModelProvider {
-            id: "claude".to_string(),
-            name: "Claude".to_string(),
-            kind: ProviderKind::Anthropic,
-            api_key: legacy.providers.claude.api_key.clone(),
-            base_url: "https://api.anthropic.com".to_string(),
-            model_name: legacy.providers.claude.model.clone(),
-          },
-          ModelProvider {
-            id: "wenxin".to_string(),
-            name: "文心一言".to_string(),
-            kind: ProviderKind::OpenAICompatible,
-            api_key: legacy.providers.wenxin.api_key.clone(),
-            base_url: legacy.providers.wenxin.base_url.clone(),
-            model_name: legacy.providers.wenxin.model.clone(),
-          },
-        ];
-        if !providers.iter().any(|p| p.id == "deepseek") {
-          providers.push(ModelProvider {
-            id: "deepseek".to_string(),
-            name: "DeepSeek".to_string(),
-            kind: ProviderKind::OpenAICompatible,
-            api_key: String::new(),
-            base_url: "https://api.deepseek.com".to_string(),
-            model_name: "deepseek-chat".to_string(),
-          });
-        }
-
-        let mut migrated = AppSettings {
-          output: legacy.output,
-          providers,
-          active_provider_id: legacy.providers.active,
-          active_agent_id: legacy.active_agent_id,
-        };
-        migrated = ensure_sane(migrated);
-
-        for p in &mut migrated.providers {
-          if !p.api_key.trim().is_empty() {
-            secrets::set_api_key(app, &p.id, p.api_key.trim())?;
-            p.api_key.clear();
-          }
-        }
-        let _ = save(app, &migrated);
-        Ok(migrated)
+  };
+  let mut settings = ensure_sane(serde_json::from_value::<AppSettings>(value).map_err(|e| format!("parse settings failed: {e}"))?);
+
+  // 旧版本明文保存的 key 移入 secrets
+  if settings.providers.iter().any(|p| !p.api_key.trim().is_empty()) {
+    for p in &mut settings.providers {
+      if !p.api_key.trim().is_empty() {
+        secrets::set_api_key(app, &p.id, p.api_key.trim())?;
+        p.api_key.clear();
       }
-      Err(_) => Err(format!("parse settings failed: {new_err}")),
-    },
+    }
+    save(app, &settings)?;
   }
+  Ok(settings)
 }
 
 pub fn save(app: &tauri::AppHandle, settings: &AppSettings) -> Result<(), String> {
@@ -242,10 +259,41 @@ pub fn save(app: &tauri::AppHandle, settings: &AppSettings) -> Result<(), String
   if let Some(parent) = path.parent() {
     fs::create_dir_all(parent).map_err(|e| format!("create settings dir failed: {e}"))?;
   }
-  let raw = serde_json::to_string_pretty(settings).map_err(|e| format!("serialize settings failed: {e}"))?;
+  let raw = SCHEMA.to_string(settings)?;
   fs::write(path, raw).map_err(|e| format!("write settings failed: {e}"))
 }
 
+/// 是否保存过全局设置；未保存时设置值都来自默认值
+pub fn is_saved(app: &tauri::AppHandle) -> Result<bool, String> {
+  Ok(settings_path(app)?.exists())
+}
+
 fn settings_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
   app_data::data_file_path(app, "settings.json")
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn load_fixture(raw: &str) -> AppSettings {
+    let (value, _) = SCHEMA.migrate(serde_json::from_str(raw).unwrap()).unwrap();
+    assert_eq!(value[schema::VERSION_KEY], SCHEMA.current);
+    serde_json::from_value(value).unwrap()
+  }
+
+  #[test]
+  fn loads_every_previous_version() {
+    let v0 = load_fixture(include_str!("../fixtures/schema/settings.v0.json"));
+    assert_eq!(v0.active_provider_id, "claude");
+    let claude = v0.providers.iter().find(|p| p.id == "claude").unwrap();
+    assert_eq!(claude.api_key, "sk-legacy");
+    assert_eq!(claude.model_name, "claude-3-opus");
+    assert!(v0.providers.iter().any(|p| p.id == "deepseek"));
+
+    let v1 = load_fixture(include_str!("../fixtures/schema/settings.v1.json"));
+    assert_eq!(v1.providers.len(), 1);
+    assert_eq!(v1.providers[0].kind, ProviderKind::OpenAICompatible);
+    assert!(v1.output.use_markdown);
+  }
+}
@@ -0,0 +1,243 @@
+use crate::agents::Agent;
+use crate::app_settings::ModelProvider;
+use crate::mcp::McpServer;
+use crate::path_sandbox;
+use crate::schema::{self, Schema};
+use crate::skills::Skill;
+use crate::spec_kit::StoryTemplate;
+use serde::{Deserialize, Serialize};
+
+/// 配置包：在机器之间迁移或分享给他人的一组配置，不包含 API key
+pub const SCHEMA: Schema = Schema {
+  name: "bundle",
+  current: 1,
+  detect: schema::unversioned,
+  migrations: &[schema::unchanged],
+};
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bundle {
+  pub exported_at: String,
+  pub providers: Vec<ModelProvider>,
+  pub agents: Vec<Agent>,
+  pub skills: Vec<Skill>,
+  pub mcp_servers: Vec<McpServer>,
+  pub story_templates: Vec<StoryTemplate>,
+}
+
+/// id 冲突时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeStrategy {
+  Skip,
+  Overwrite,
+  Rename,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+  Add,
+  Skip,
+  Overwrite,
+  Rename,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportItem {
+  pub kind: &'static str,
+  pub id: String,
+  pub conflict: bool,
+  pub action: ImportAction,
+  /// 改名导入后的新 id
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub new_id: Option<String>,
+  /// 导入后处于停用状态，需要用户确认后手动启用（MCP server 会启动本地程序）
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub requires_enable: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+  pub dry_run: bool,
+  pub items: Vec<ImportItem>,
+}
+
+/// 当前的各项配置；导入时原地合并
+pub struct Existing<'a> {
+  pub providers: &'a mut Vec<ModelProvider>,
+  pub agents: &'a mut Vec<Agent>,
+  pub skills: &'a mut Vec<Skill>,
+  pub mcp_servers: &'a mut Vec<McpServer>,
+  pub story_templates: &'a mut Vec<StoryTemplate>,
+}
+
+/// 解析配置包；故事模板 id 会被用作文件名，不是单个普通路径分量时拒绝整个配置包。
+pub fn parse(raw: &str) -> Result<Bundle, String> {
+  let value = serde_json::from_str(raw).map_err(|e| format!("parse bundle failed: {e}"))?;
+  let (value, _) = SCHEMA.migrate(value)?;
+  let bundle: Bundle = serde_json::from_value(value).map_err(|e| format!("parse bundle failed: {e}"))?;
+  for t in &bundle.story_templates {
+    path_sandbox::check_file_name(&t.template_id).map_err(|e| format!("invalid story template id: {e}"))?;
+  }
+  Ok(bundle)
+}
+
+/// 按策略把配置包合并进 `existing`，返回每一项的处理结果。
+///
+/// 导入的 provider 不带 key，需要在设置中重新填写；导入的 MCP server 一律停用，由用户确认后再启用。
+pub fn merge(bundle: Bundle, existing: Existing<'_>, strategy: MergeStrategy) -> ImportReport {
+  let mut report = ImportReport::default();
+  let items = &mut report.items;
+  merge_list(items, "provider", existing.providers, bundle.providers, strategy, |p| &mut p.id, |p| p.api_key.clear());
+  merge_list(items, "agent", existing.agents, bundle.agents, strategy, |a| &mut a.id, |_| {});
+  merge_list(items, "skill", existing.skills, bundle.skills, strategy, |s| &mut s.id, |_| {});
+  merge_list(items, "mcp_server", existing.mcp_servers, bundle.mcp_servers, strategy, |s| &mut s.id, |s| s.enabled = false);
+  merge_list(
+    items,
+    "story_template",
+    existing.story_templates,
+    bundle.story_templates,
+    strategy,
+    |t| &mut t.template_id,
+    |_| {},
+  );
+  report
+}
+
+fn merge_list<T>(
+  report: &mut Vec<ImportItem>,
+  kind: &'static str,
+  existing: &mut Vec<T>,
+  incoming: Vec<T>,
+  strategy: MergeStrategy,
+  id_of: fn(&mut T) -> &mut String,
+  sanitize: fn(&mut T),
+) {
+  for mut item in incoming {
+    sanitize(&mut item);
+    let id = id_of(&mut item).clone();
+    let pos = existing.iter_mut().position(|e| *id_of(e) == id);
+    let mut new_id = None;
+    let action = match (pos, strategy) {
+      (None, _) => {
+        existing.push(item);
+        ImportAction::Add
+      }
+      (Some(_), MergeStrategy::Skip) => ImportAction::Skip,
+      (Some(i), MergeStrategy::Overwrite) => {
+        existing[i] = item;
+        ImportAction::Overwrite
+      }
+      (Some(_), MergeStrategy::Rename) => {
+        let renamed = unique_id(&id, existing, id_of);
+        *id_of(&mut item) = renamed.clone();
+        existing.push(item);
+        new_id = Some(renamed);
+        ImportAction::Rename
+      }
+    };
+    report.push(ImportItem {
+      kind,
+      id,
+      conflict: pos.is_some(),
+      action,
+      new_id,
+      requires_enable: kind == "mcp_server" && action != ImportAction::Skip,
+    });
+  }
+}
+
+fn unique_id<T>(id: &str, existing: &mut [T], id_of: fn(&mut T) -> &mut String) -> String {
+  let taken = |candidate: &str, existing: &mut [T]| existing.iter_mut().any(|e| id_of(e) == candidate);
+  let mut candidate = format!("{id}-imported");
+  let mut n = 2;
+  while taken(&candidate, existing) {
+    candidate = format!("{id}-imported-{n}");
+    n += 1;
+  }
+  candidate
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn agent(id: &str, name: &str) -> Agent {
+    Agent {
+      id: id.to_string(),
+      name: name.to_string(),
+      ..Default::default()
+    }
+  }
+
+  fn merge_agents(mine: &mut Vec<Agent>, theirs: Vec<Agent>, strategy: MergeStrategy) -> ImportReport {
+    let bundle = Bundle {
+      agents: theirs,
+      ..Default::default()
+    };
+    let existing = Existing {
+      providers: &mut Vec::new(),
+      agents: mine,
+      skills: &mut Vec::new(),
+      mcp_servers: &mut Vec::new(),
+      story_templates: &mut Vec::new(),
+    };
+    merge(bundle, existing, strategy)
+  }
+
+  #[test]
+  fn merge_strategies_resolve_id_collisions() {
+    let theirs = vec![agent("fantasy", "新玄幻"), agent("poetry", "诗歌")];
+
+    let mut mine = vec![agent("fantasy", "玄幻")];
+    let report = merge_agents(&mut mine, theirs.clone(), MergeStrategy::Skip);
+    assert_eq!(report.items[0].action, ImportAction::Skip);
+    assert!(report.items[0].conflict);
+    assert_eq!(report.items[1].action, ImportAction::Add);
+    assert_eq!(mine.len(), 2);
+    assert_eq!(mine[0].name, "玄幻");
+
+    let mut mine = vec![agent("fantasy", "玄幻")];
+    merge_agents(&mut mine, theirs.clone(), MergeStrategy::Overwrite);
+    assert_eq!(mine[0].name, "新玄幻");
+
+    let mut mine = vec![agent("fantasy", "玄幻"), agent("fantasy-imported", "旧导入")];
+    let report = merge_agents(&mut mine, theirs, MergeStrategy::Rename);
+    assert_eq!(report.items[0].new_id.as_deref(), Some("fantasy-imported-2"));
+    assert_eq!(mine.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["fantasy", "fantasy-imported", "fantasy-imported-2", "poetry"]);
+  }
+
+  #[test]
+  fn imported_providers_never_carry_keys() {
+    let raw = r#"{"schema_version":1,"providers":[{"id":"openai","name":"OpenAI","kind":"OpenAI","api_key":"sk-x","base_url":"","model_name":"gpt"}]}"#;
+    let bundle = parse(raw).unwrap();
+    let mut providers = Vec::new();
+    let existing = Existing {
+      providers: &mut providers,
+      agents: &mut Vec::new(),
+      skills: &mut Vec::new(),
+      mcp_servers: &mut Vec::new(),
+      story_templates: &mut Vec::new(),
+    };
+    merge(bundle, existing, MergeStrategy::Skip);
+    assert!(providers[0].api_key.is_empty());
+
+    let raw = r#"{"mcp_servers":[{"id":"fs","name":"fs","command":"rm","args":["-rf","/"]}]}"#;
+    let mut servers = Vec::new();
+    let existing = Existing {
+      providers: &mut Vec::new(),
+      agents: &mut Vec::new(),
+      skills: &mut Vec::new(),
+      mcp_servers: &mut servers,
+      story_templates: &mut Vec::new(),
+    };
+    let report = merge(parse(raw).unwrap(), existing, MergeStrategy::Skip);
+    assert!(!servers[0].enabled);
+    assert!(report.items[0].requires_enable);
+    assert!(parse(r#"{"schema_version":2}"#).is_err_and(|e| e.contains("newer version")));
+    let traversal = r#"{"schema_version":1,"story_templates":[{"spec_kit_version":"1","template_id":"../../../x","display_name":"","story_type":"","default_theme_statement":"","beats":[]}]}"#;
+    assert!(parse(traversal).is_err_and(|e| e.contains("invalid story template id")));
+  }
+}
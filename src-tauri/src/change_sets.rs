@@ -0,0 +1,781 @@
+use crate::modification_types::{
+    ChangeSet, ChangeSetStatus, FileModificationStatus, FileOperation, Modification, ModificationStatus,
+    ModificationType,
+};
+use crate::path_sandbox::PathSandbox;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 应用 ChangeSet 时记录的撤销信息，保存在 `.novel/.undo/<change_set_id>.json`。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoEntry {
+    pub change_set_id: String,
+    pub applied_at: i64,
+    pub files: Vec<UndoFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoFile {
+    pub file_path: String,
+    /// 应用前的完整内容；`None` 表示应用前文件不存在
+    pub before: Option<String>,
+    /// 应用后内容的哈希，撤销时用来确认文件没有被再次修改；`None` 表示应用后文件不存在
+    pub after_hash: Option<String>,
+}
+
+pub fn content_hash(content: &str) -> String {
+    blake3::hash(content.as_bytes()).to_hex().to_string()
+}
+
+/// 把一组修改应用到文本上。
+///
+/// 行号均以原文为准（1 起），按 `line_start` 从大到小应用，保证前面的行号不受影响；
+/// `add` 插入到 `line_start` 之前，`line_start` 等于行数 + 1 时追加到末尾。
+/// 保留原文的换行风格以及末尾是否有换行。
+pub fn apply_modifications(content: &str, mods: &[&Modification]) -> Result<String, String> {
+    let eol = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let had_trailing_eol = content.is_empty() || content.ends_with('\n');
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(|l| l.to_string()).collect();
+    if let Some(last) = lines.last_mut() {
+        if !had_trailing_eol {
+            last.push_str(eol);
+        }
+    }
+    let line_count = lines.len() as u32;
+
+    let mut ordered = mods.to_vec();
+    for m in &ordered {
+        check_range(m, line_count)?;
+    }
+    // 同一行上的插入排在修改之前，倒序应用时先改原行再在其前插入
+    ordered.sort_by_key(|m| (m.line_start, !matches!(m.mod_type, ModificationType::Add), m.line_end));
+    for pair in ordered.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let a_end = if matches!(a.mod_type, ModificationType::Add) { a.line_start - 1 } else { a.line_end };
+        if b.line_start <= a_end {
+            return Err(format!("modifications {} and {} overlap", a.id, b.id));
+        }
+    }
+
+    for m in ordered.iter().rev() {
+        let start = (m.line_start - 1) as usize;
+        let new_lines = m
+            .modified_text
+            .as_deref()
+            .unwrap_or("")
+            .lines()
+            .map(|l| format!("{l}{eol}"))
+            .collect::<Vec<_>>();
+        match m.mod_type {
+            ModificationType::Add => {
+                lines.splice(start..start, new_lines);
+            }
+            ModificationType::Delete => {
+                lines.drain(start..m.line_end as usize);
+            }
+            ModificationType::Modify => {
+                lines.splice(start..m.line_end as usize, new_lines);
+            }
+            // 文件级操作在 check_range 中已被拒绝
+            ModificationType::CreateFile | ModificationType::DeleteFile | ModificationType::RenameFile => {}
+        }
+    }
+
+    let mut out = lines.concat();
+    if !had_trailing_eol && out.ends_with(eol) {
+        out.truncate(out.len() - eol.len());
+    }
+    Ok(out)
+}
+
+fn check_range(m: &Modification, line_count: u32) -> Result<(), String> {
+    let ok = match m.mod_type {
+        ModificationType::Add => m.line_start >= 1 && m.line_start <= line_count + 1,
+        ModificationType::Delete | ModificationType::Modify => {
+            m.line_start >= 1 && m.line_start <= m.line_end && m.line_end <= line_count
+        }
+        ModificationType::CreateFile | ModificationType::DeleteFile | ModificationType::RenameFile => {
+            return Err(format!("modification {} is a file operation, not a line edit", m.id));
+        }
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(format!(
+            "modification {} has invalid lines {}-{} (file has {line_count} lines)",
+            m.id, m.line_start, m.line_end
+        ))
+    }
+}
+
+/// 应用 `accepted_ids` 中的修改，其余修改标记为 rejected。
+///
+/// 任一文件的当前内容与 `original_content` 不一致（新建文件时目标已存在）时整体拒绝，不写任何文件。
+/// 有实际写入时在 `.novel/.undo/` 下记录撤销信息，可用 [`revert_change_set`] 整体还原。
+pub fn apply_change_set(root: &Path, mut change_set: ChangeSet, accepted_ids: &[String]) -> Result<ChangeSet, String> {
+    // (目标路径, 新内容)；新内容为 None 表示删除
+    let mut writes: Vec<(PathBuf, Option<String>)> = Vec::new();
+    let mut undo_files: Vec<UndoFile> = Vec::new();
+    let sandbox = PathSandbox::new(root);
+    for file in change_set.files.iter_mut() {
+        let target = sandbox.resolve(&file.file_path)?;
+        let current = if file.operation == FileOperation::Create {
+            if target.exists() {
+                return Err(format!("{} already exists", file.file_path));
+            }
+            String::new()
+        } else {
+            let current = fs::read_to_string(&target).map_err(|e| format!("read {} failed: {e}", file.file_path))?;
+            if content_hash(&current) != content_hash(&file.original_content) {
+                return Err(format!("{} has changed since the change set was created", file.file_path));
+            }
+            current
+        };
+        let new_target = match (&file.operation, &file.new_path) {
+            (FileOperation::Rename, Some(p)) => {
+                let t = sandbox.resolve(p)?;
+                if t.exists() {
+                    return Err(format!("{p} already exists"));
+                }
+                Some(t)
+            }
+            (FileOperation::Rename, None) => return Err(format!("{} is missing the rename target", file.file_path)),
+            _ => None,
+        };
+
+        for m in file.modifications.iter_mut() {
+            m.status = if accepted_ids.contains(&m.id) {
+                ModificationStatus::Accepted
+            } else {
+                ModificationStatus::Rejected
+            };
+        }
+        let accepted_count = file
+            .modifications
+            .iter()
+            .filter(|m| matches!(m.status, ModificationStatus::Accepted))
+            .count();
+        file.status = if accepted_count == 0 {
+            FileModificationStatus::Rejected
+        } else if accepted_count == file.modifications.len() {
+            FileModificationStatus::Accepted
+        } else {
+            FileModificationStatus::Partial
+        };
+        if accepted_count == 0 {
+            continue;
+        }
+
+        let file_op = file
+            .modifications
+            .iter()
+            .find(|m| m.mod_type.is_file_level() && matches!(m.status, ModificationStatus::Accepted));
+        let line_mods = file
+            .modifications
+            .iter()
+            .filter(|m| !m.mod_type.is_file_level() && matches!(m.status, ModificationStatus::Accepted))
+            .collect::<Vec<_>>();
+        let updated = if line_mods.is_empty() {
+            current.clone()
+        } else {
+            apply_modifications(&current, &line_mods).map_err(|e| format!("{}: {e}", file.file_path))?
+        };
+
+        match (file_op.map(|m| &m.mod_type), new_target) {
+            (Some(ModificationType::CreateFile), _) => {
+                let content = file_op.and_then(|m| m.modified_text.clone()).unwrap_or_default();
+                undo_files.push(UndoFile {
+                    file_path: file.file_path.clone(),
+                    before: None,
+                    after_hash: Some(content_hash(&content)),
+                });
+                writes.push((target, Some(content)));
+            }
+            (Some(ModificationType::DeleteFile), _) => {
+                undo_files.push(UndoFile {
+                    file_path: file.file_path.clone(),
+                    before: Some(current),
+                    after_hash: None,
+                });
+                writes.push((target, None));
+            }
+            (Some(ModificationType::RenameFile), Some(new_target)) => {
+                let new_path = file.new_path.clone().unwrap_or_default();
+                undo_files.push(UndoFile {
+                    file_path: new_path,
+                    before: None,
+                    after_hash: Some(content_hash(&updated)),
+                });
+                undo_files.push(UndoFile {
+                    file_path: file.file_path.clone(),
+                    before: Some(current),
+                    after_hash: None,
+                });
+                writes.push((new_target, Some(updated)));
+                writes.push((target, None));
+            }
+            _ if line_mods.is_empty() => {}
+            _ => {
+                undo_files.push(UndoFile {
+                    file_path: file.file_path.clone(),
+                    before: Some(current),
+                    after_hash: Some(content_hash(&updated)),
+                });
+                writes.push((target, Some(updated)));
+            }
+        }
+    }
+
+    let file_statuses = change_set.files.iter().map(|f| &f.status).collect::<Vec<_>>();
+    change_set.status = if file_statuses.iter().all(|s| matches!(s, FileModificationStatus::Accepted)) {
+        ChangeSetStatus::Accepted
+    } else if file_statuses.iter().all(|s| matches!(s, FileModificationStatus::Rejected)) {
+        ChangeSetStatus::Rejected
+    } else {
+        ChangeSetStatus::Partial
+    };
+
+    if writes.is_empty() {
+        return Ok(change_set);
+    }
+    let undo = undo_path(root, &change_set.id)?;
+    if undo.exists() {
+        return Err(format!("change set {} has already been applied", change_set.id));
+    }
+    let entry = UndoEntry {
+        change_set_id: change_set.id.clone(),
+        applied_at: chrono::Utc::now().timestamp_millis(),
+        files: undo_files,
+    };
+    // 先写撤销记录，再把新内容写到同目录的临时文件；全部就绪后才替换目标文件
+    save_undo(root, &entry)?;
+    let staged = match stage_writes(&writes, &change_set.id) {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = fs::remove_file(&undo);
+            return Err(e);
+        }
+    };
+    for (i, ((target, _), tmp)) in writes.iter().zip(&staged).enumerate() {
+        let result = match tmp {
+            Some(tmp) => fs::rename(tmp, target).map_err(|e| format!("write {} failed: {e}", target.display())),
+            None => fs::remove_file(target).map_err(|e| format!("remove {} failed: {e}", target.display())),
+        };
+        if let Err(e) = result {
+            // 回滚已替换的文件；`writes` 与撤销记录中的文件一一对应
+            for (j, f) in entry.files.iter().enumerate().take(i).rev() {
+                let _ = write_or_remove(&writes[j].0, f.before.as_deref());
+            }
+            for tmp in staged.iter().skip(i).flatten() {
+                let _ = fs::remove_file(tmp);
+            }
+            let _ = fs::remove_file(&undo);
+            return Err(e);
+        }
+    }
+    Ok(change_set)
+}
+
+/// 把每个待写入的内容写到目标旁的临时文件；任一失败时清理已写的临时文件。
+fn stage_writes(writes: &[(PathBuf, Option<String>)], change_set_id: &str) -> Result<Vec<Option<PathBuf>>, String> {
+    let mut staged: Vec<Option<PathBuf>> = Vec::new();
+    for (target, content) in writes {
+        let Some(text) = content else {
+            staged.push(None);
+            continue;
+        };
+        let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let tmp = target.with_file_name(format!(".{name}.{change_set_id}.tmp"));
+        let result = match target.parent() {
+            Some(parent) => fs::create_dir_all(parent).map_err(|e| format!("create dir failed: {e}")),
+            None => Ok(()),
+        }
+        .and_then(|_| fs::write(&tmp, text).map_err(|e| format!("write {} failed: {e}", target.display())));
+        if let Err(e) = result {
+            for tmp in staged.iter().flatten() {
+                let _ = fs::remove_file(tmp);
+            }
+            return Err(e);
+        }
+        staged.push(Some(tmp));
+    }
+    Ok(staged)
+}
+
+fn write_or_remove(target: &Path, content: Option<&str>) -> Result<(), String> {
+    match content {
+        Some(text) => {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("create dir failed: {e}"))?;
+            }
+            fs::write(target, text).map_err(|e| format!("write {} failed: {e}", target.display()))
+        }
+        None => fs::remove_file(target).map_err(|e| format!("remove {} failed: {e}", target.display())),
+    }
+}
+
+/// 还原已应用的 ChangeSet；文件在应用后又被修改过时拒绝还原，避免覆盖后续编辑。
+pub fn revert_change_set(root: &Path, change_set_id: &str) -> Result<Vec<String>, String> {
+    let path = undo_path(root, change_set_id)?;
+    let raw = fs::read_to_string(&path).map_err(|e| format!("read undo entry failed: {e}"))?;
+    let entry: UndoEntry = serde_json::from_str(&raw).map_err(|e| format!("parse undo entry failed: {e}"))?;
+
+    let sandbox = PathSandbox::new(root);
+    let mut targets = Vec::new();
+    for f in &entry.files {
+        let target = sandbox.resolve(&f.file_path)?;
+        let current_hash = fs::read_to_string(&target).ok().map(|c| content_hash(&c));
+        if current_hash != f.after_hash {
+            return Err(format!("{} has been edited after the change set was applied", f.file_path));
+        }
+        targets.push(target);
+    }
+    // 倒序还原：重命名时先恢复旧文件再删除新文件
+    for (target, f) in targets.iter().zip(&entry.files).rev() {
+        write_or_remove(target, f.before.as_deref())?;
+    }
+    fs::remove_file(&path).map_err(|e| format!("remove undo entry failed: {e}"))?;
+    Ok(entry.files.into_iter().map(|f| f.file_path).collect())
+}
+
+fn check_id(change_set_id: &str) -> Result<(), String> {
+    if change_set_id.is_empty() || !change_set_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("invalid change set id".to_string());
+    }
+    Ok(())
+}
+
+fn undo_path(root: &Path, change_set_id: &str) -> Result<PathBuf, String> {
+    check_id(change_set_id)?;
+    Ok(root.join(".novel").join(".undo").join(format!("{change_set_id}.json")))
+}
+
+fn save_undo(root: &Path, entry: &UndoEntry) -> Result<(), String> {
+    let path = undo_path(root, &entry.change_set_id)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create undo dir failed: {e}"))?;
+    }
+    let raw = serde_json::to_string_pretty(entry).map_err(|e| format!("serialize undo entry failed: {e}"))?;
+    fs::write(path, raw).map_err(|e| format!("write undo entry failed: {e}"))
+}
+
+// ============ 历史记录 ============
+
+/// 持久化在 `.novel/.changesets/<id>.json` 的 ChangeSet 及其状态变更记录。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSetRecord {
+    pub change_set: ChangeSet,
+    /// 产生该 ChangeSet 的来源，如聊天的 stream id
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub history: Vec<ChangeSetEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSetEvent {
+    pub action: ChangeSetAction,
+    pub status: ChangeSetStatus,
+    pub at: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accepted_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeSetAction {
+    Created,
+    Applied,
+    Rejected,
+    Reverted,
+}
+
+/// 收件箱列表项，不含文件内容。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSetSummary {
+    pub id: String,
+    pub timestamp: u64,
+    pub status: ChangeSetStatus,
+    pub source: Option<String>,
+    pub files: Vec<String>,
+    pub modification_count: usize,
+    pub last_action: ChangeSetAction,
+    pub updated_at: i64,
+}
+
+impl ChangeSetRecord {
+    pub fn is_pending(&self) -> bool {
+        matches!(self.change_set.status, ChangeSetStatus::Pending)
+    }
+
+    fn summary(&self) -> ChangeSetSummary {
+        let last = self.history.last();
+        ChangeSetSummary {
+            id: self.change_set.id.clone(),
+            timestamp: self.change_set.timestamp,
+            status: self.change_set.status.clone(),
+            source: self.source.clone(),
+            files: self.change_set.files.iter().map(|f| f.file_path.clone()).collect(),
+            modification_count: self.change_set.files.iter().map(|f| f.modifications.len()).sum(),
+            last_action: last.map(|e| e.action.clone()).unwrap_or(ChangeSetAction::Created),
+            updated_at: last.map(|e| e.at).unwrap_or(0),
+        }
+    }
+
+    fn push_event(&mut self, action: ChangeSetAction, accepted_ids: &[String]) {
+        self.history.push(ChangeSetEvent {
+            action,
+            status: self.change_set.status.clone(),
+            at: chrono::Utc::now().timestamp_millis(),
+            accepted_ids: accepted_ids.to_vec(),
+        });
+    }
+}
+
+fn record_path(root: &Path, change_set_id: &str) -> Result<PathBuf, String> {
+    check_id(change_set_id)?;
+    Ok(root.join(".novel").join(".changesets").join(format!("{change_set_id}.json")))
+}
+
+fn save_record(root: &Path, record: &ChangeSetRecord) -> Result<(), String> {
+    let path = record_path(root, &record.change_set.id)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create changesets dir failed: {e}"))?;
+    }
+    let raw = serde_json::to_string_pretty(record).map_err(|e| format!("serialize change set failed: {e}"))?;
+    fs::write(path, raw).map_err(|e| format!("write change set failed: {e}"))
+}
+
+pub fn load_record(root: &Path, change_set_id: &str) -> Result<ChangeSetRecord, String> {
+    let path = record_path(root, change_set_id)?;
+    let raw = fs::read_to_string(&path).map_err(|e| format!("read change set failed: {e}"))?;
+    serde_json::from_str(&raw).map_err(|e| format!("parse change set failed: {e}"))
+}
+
+/// 保存新产生的 ChangeSet，进入待审阅收件箱。
+pub fn record_change_set(root: &Path, change_set: &ChangeSet, source: Option<&str>) -> Result<(), String> {
+    let mut record = ChangeSetRecord {
+        change_set: change_set.clone(),
+        source: source.map(|s| s.to_string()),
+        history: Vec::new(),
+    };
+    record.push_event(ChangeSetAction::Created, &[]);
+    save_record(root, &record)
+}
+
+/// 列出保存的 ChangeSet，新的在前；`pending_only` 时只返回待审阅的。
+pub fn list_change_sets(root: &Path, pending_only: bool) -> Result<Vec<ChangeSetSummary>, String> {
+    let dir = root.join(".novel").join(".changesets");
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut out = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Ok(raw) = fs::read_to_string(&path) else { continue };
+        let Ok(record) = serde_json::from_str::<ChangeSetRecord>(&raw) else {
+            eprintln!("skip unreadable change set record: {}", path.display());
+            continue;
+        };
+        if pending_only && !record.is_pending() {
+            continue;
+        }
+        out.push(record.summary());
+    }
+    out.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.id.cmp(&a.id)));
+    Ok(out)
+}
+
+/// 接受收件箱中的 ChangeSet（`accepted_ids` 之外的修改视为拒绝）并写入文件。
+pub fn accept_recorded(root: &Path, change_set_id: &str, accepted_ids: &[String]) -> Result<ChangeSetRecord, String> {
+    let mut record = load_record(root, change_set_id)?;
+    if !record.is_pending() {
+        return Err(format!("change set {change_set_id} has already been reviewed"));
+    }
+    record.change_set = apply_change_set(root, record.change_set, accepted_ids)?;
+    record.push_event(ChangeSetAction::Applied, accepted_ids);
+    save_record(root, &record)?;
+    Ok(record)
+}
+
+/// 整体拒绝收件箱中的 ChangeSet，不修改任何文件。
+pub fn reject_recorded(root: &Path, change_set_id: &str) -> Result<ChangeSetRecord, String> {
+    let mut record = load_record(root, change_set_id)?;
+    if !record.is_pending() {
+        return Err(format!("change set {change_set_id} has already been reviewed"));
+    }
+    for file in record.change_set.files.iter_mut() {
+        for m in file.modifications.iter_mut() {
+            m.status = ModificationStatus::Rejected;
+        }
+        file.status = FileModificationStatus::Rejected;
+    }
+    record.change_set.status = ChangeSetStatus::Rejected;
+    record.push_event(ChangeSetAction::Rejected, &[]);
+    save_record(root, &record)?;
+    Ok(record)
+}
+
+/// 记录一次直接传入 ChangeSet 的应用（不经过收件箱）；没有对应记录时新建。
+pub fn note_applied(root: &Path, change_set: &ChangeSet, accepted_ids: &[String]) -> Result<(), String> {
+    let mut record = load_record(root, &change_set.id).unwrap_or_else(|_| ChangeSetRecord {
+        change_set: change_set.clone(),
+        source: None,
+        history: Vec::new(),
+    });
+    record.change_set = change_set.clone();
+    record.push_event(ChangeSetAction::Applied, accepted_ids);
+    save_record(root, &record)
+}
+
+/// 在历史中记录撤销，并把 ChangeSet 恢复为待审阅，可在收件箱中重新审阅；
+/// 没有对应记录（例如旧版本应用的）时忽略。
+pub fn note_reverted(root: &Path, change_set_id: &str) -> Result<(), String> {
+    let Ok(mut record) = load_record(root, change_set_id) else {
+        return Ok(());
+    };
+    for file in record.change_set.files.iter_mut() {
+        for m in file.modifications.iter_mut() {
+            m.status = ModificationStatus::Pending;
+        }
+        file.status = FileModificationStatus::Pending;
+    }
+    record.change_set.status = ChangeSetStatus::Pending;
+    record.push_event(ChangeSetAction::Reverted, &[]);
+    save_record(root, &record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::modification_types::FileModification;
+
+    fn modification(id: &str, mod_type: ModificationType, start: u32, end: u32, text: Option<&str>) -> Modification {
+        Modification {
+            id: id.to_string(),
+            mod_type,
+            line_start: start,
+            line_end: end,
+            original_text: None,
+            modified_text: text.map(|t| t.to_string()),
+            status: ModificationStatus::Pending,
+        }
+    }
+
+    fn temp_workspace() -> TempDir {
+        let root = TempDir::new("changeset");
+        fs::create_dir_all(root.join("stories")).unwrap();
+        root
+    }
+
+    #[test]
+    fn applies_bottom_up_against_original_line_numbers() {
+        let content = "一\r\n二\r\n三\r\n四\r\n五";
+        let mods = [
+            modification("a", ModificationType::Modify, 1, 1, Some("壹")),
+            modification("b", ModificationType::Add, 3, 3, Some("二点五\n二点七")),
+            modification("c", ModificationType::Delete, 4, 5, None),
+        ];
+        let out = apply_modifications(content, &mods.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(out, "壹\r\n二\r\n二点五\r\n二点七\r\n三");
+    }
+
+    #[test]
+    fn rejects_overlaps_and_out_of_range() {
+        let content = "a\nb\nc\n";
+        let overlap = [
+            modification("a", ModificationType::Modify, 1, 2, Some("x")),
+            modification("b", ModificationType::Delete, 2, 3, None),
+        ];
+        assert!(apply_modifications(content, &overlap.iter().collect::<Vec<_>>()).unwrap_err().contains("overlap"));
+        let out_of_range = [modification("a", ModificationType::Delete, 3, 4, None)];
+        assert!(apply_modifications(content, &out_of_range.iter().collect::<Vec<_>>()).is_err());
+        let append = [modification("a", ModificationType::Add, 4, 4, Some("d"))];
+        assert_eq!(apply_modifications(content, &append.iter().collect::<Vec<_>>()).unwrap(), "a\nb\nc\nd\n");
+    }
+
+    #[test]
+    fn partial_apply_then_revert() {
+        let root = temp_workspace();
+        let original = "第一行\n第二行\n第三行\n";
+        fs::write(root.join("stories/ch1.txt"), original).unwrap();
+        let cs = ChangeSet::new(vec![FileModification {
+            file_path: "stories/ch1.txt".to_string(),
+            original_content: original.to_string(),
+            modifications: vec![
+                modification("m1", ModificationType::Modify, 1, 1, Some("新第一行")),
+                modification("m2", ModificationType::Delete, 3, 3, None),
+            ],
+            status: FileModificationStatus::Pending,
+            operation: FileOperation::Edit,
+            new_path: None,
+        }]);
+        let id = cs.id.clone();
+
+        let applied = apply_change_set(&root, cs.clone(), &["m1".to_string()]).unwrap();
+        assert!(matches!(applied.status, ChangeSetStatus::Partial));
+        assert!(matches!(applied.files[0].modifications[1].status, ModificationStatus::Rejected));
+        assert_eq!(fs::read_to_string(root.join("stories/ch1.txt")).unwrap(), "新第一行\n第二行\n第三行\n");
+
+        // 文件已变化，再次应用同一个 ChangeSet 会被拒绝
+        assert!(apply_change_set(&root, cs.clone(), &["m2".to_string()]).unwrap_err().contains("has changed"));
+        // 内容恰好一致时也不会覆盖已有的撤销记录
+        let mut again = cs.clone();
+        again.files[0].original_content = fs::read_to_string(root.join("stories/ch1.txt")).unwrap();
+        assert!(apply_change_set(&root, again, &["m1".to_string()]).unwrap_err().contains("already been applied"));
+
+        assert_eq!(revert_change_set(&root, &id).unwrap(), ["stories/ch1.txt"]);
+        assert_eq!(fs::read_to_string(root.join("stories/ch1.txt")).unwrap(), original);
+        assert!(revert_change_set(&root, &id).is_err());
+    }
+
+    #[test]
+    fn file_operations_apply_and_revert() {
+        let root = temp_workspace();
+        let chapter = "甲\n乙\n丙\n丁\n";
+        fs::write(root.join("stories/ch5.txt"), chapter).unwrap();
+        fs::write(root.join("stories/old.txt"), "废稿").unwrap();
+        let file_op = |id: &str, mod_type, text: Option<&str>| modification(id, mod_type, 1, 1, text);
+        let cs = ChangeSet::new(vec![
+            FileModification {
+                file_path: "stories/ch5.txt".to_string(),
+                original_content: chapter.to_string(),
+                modifications: vec![
+                    file_op("rename", ModificationType::RenameFile, Some("stories/ch5a.txt")),
+                    modification("cut", ModificationType::Delete, 3, 4, None),
+                ],
+                status: FileModificationStatus::Pending,
+                operation: FileOperation::Rename,
+                new_path: Some("stories/ch5a.txt".to_string()),
+            },
+            FileModification {
+                file_path: "stories/ch5b/part.txt".to_string(),
+                original_content: String::new(),
+                modifications: vec![file_op("create", ModificationType::CreateFile, Some("丙\n丁\n"))],
+                status: FileModificationStatus::Pending,
+                operation: FileOperation::Create,
+                new_path: None,
+            },
+            FileModification {
+                file_path: "stories/old.txt".to_string(),
+                original_content: "废稿".to_string(),
+                modifications: vec![file_op("drop", ModificationType::DeleteFile, None)],
+                status: FileModificationStatus::Pending,
+                operation: FileOperation::Delete,
+                new_path: None,
+            },
+        ]);
+        let ids = ["rename", "cut", "create", "drop"].map(String::from);
+        let applied = apply_change_set(&root, cs.clone(), &ids).unwrap();
+        assert!(matches!(applied.status, ChangeSetStatus::Accepted));
+        assert!(!root.join("stories/ch5.txt").exists());
+        assert_eq!(fs::read_to_string(root.join("stories/ch5a.txt")).unwrap(), "甲\n乙\n");
+        assert_eq!(fs::read_to_string(root.join("stories/ch5b/part.txt")).unwrap(), "丙\n丁\n");
+        assert!(!root.join("stories/old.txt").exists());
+
+        revert_change_set(&root, &cs.id).unwrap();
+        assert_eq!(fs::read_to_string(root.join("stories/ch5.txt")).unwrap(), chapter);
+        assert!(!root.join("stories/ch5a.txt").exists());
+        assert!(!root.join("stories/ch5b/part.txt").exists());
+        assert_eq!(fs::read_to_string(root.join("stories/old.txt")).unwrap(), "废稿");
+
+        // 只接受行级修改、拒绝重命名时在原文件上修改
+        let applied = apply_change_set(&root, cs, &["cut".to_string()]).unwrap();
+        assert!(matches!(applied.status, ChangeSetStatus::Partial));
+        assert_eq!(fs::read_to_string(root.join("stories/ch5.txt")).unwrap(), "甲\n乙\n");
+        assert!(!root.join("stories/ch5a.txt").exists());
+    }
+
+    #[test]
+    fn failed_apply_leaves_workspace_untouched() {
+        let root = temp_workspace();
+        let original = "甲\n乙\n";
+        fs::write(root.join("stories/ch1.txt"), original).unwrap();
+        // `stories/blocker` 是文件，无法在其下创建目录
+        fs::write(root.join("stories/blocker"), "").unwrap();
+        let cs = ChangeSet::new(vec![
+            FileModification {
+                file_path: "stories/ch1.txt".to_string(),
+                original_content: original.to_string(),
+                modifications: vec![modification("edit", ModificationType::Modify, 1, 1, Some("甲改"))],
+                status: FileModificationStatus::Pending,
+                operation: FileOperation::Edit,
+                new_path: None,
+            },
+            FileModification {
+                file_path: "stories/blocker/new.txt".to_string(),
+                original_content: String::new(),
+                modifications: vec![modification("create", ModificationType::CreateFile, 1, 1, Some("新"))],
+                status: FileModificationStatus::Pending,
+                operation: FileOperation::Create,
+                new_path: None,
+            },
+        ]);
+        assert!(apply_change_set(&root, cs.clone(), &["edit", "create"].map(String::from)).is_err());
+        assert_eq!(fs::read_to_string(root.join("stories/ch1.txt")).unwrap(), original);
+        assert!(!undo_path(&root, &cs.id).unwrap().exists());
+        let leftovers: Vec<_> = fs::read_dir(root.join("stories")).unwrap().flatten().filter(|e| e.file_name().to_string_lossy().ends_with(".tmp")).collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn recorded_change_sets_survive_review() {
+        let root = temp_workspace();
+        let original = "第一行\n第二行\n";
+        fs::write(root.join("stories/ch1.txt"), original).unwrap();
+        let edit = |id: &str| {
+            ChangeSet::new(vec![FileModification {
+                file_path: "stories/ch1.txt".to_string(),
+                original_content: original.to_string(),
+                modifications: vec![modification(id, ModificationType::Modify, 2, 2, Some("新第二行"))],
+                status: FileModificationStatus::Pending,
+                operation: FileOperation::Edit,
+                new_path: None,
+            }])
+        };
+        let (first, second) = (edit("m1"), edit("m2"));
+        record_change_set(&root, &first, Some("stream-1")).unwrap();
+        record_change_set(&root, &second, None).unwrap();
+        assert_eq!(list_change_sets(&root, true).unwrap().len(), 2);
+
+        let rejected = reject_recorded(&root, &second.id).unwrap();
+        assert!(matches!(rejected.change_set.status, ChangeSetStatus::Rejected));
+        assert_eq!(fs::read_to_string(root.join("stories/ch1.txt")).unwrap(), original);
+        assert!(reject_recorded(&root, &second.id).unwrap_err().contains("already been reviewed"));
+
+        let accepted = accept_recorded(&root, &first.id, &["m1".to_string()]).unwrap();
+        assert!(matches!(accepted.change_set.status, ChangeSetStatus::Accepted));
+        assert_eq!(fs::read_to_string(root.join("stories/ch1.txt")).unwrap(), "第一行\n新第二行\n");
+        assert!(list_change_sets(&root, true).unwrap().is_empty());
+
+        revert_change_set(&root, &first.id).unwrap();
+        note_reverted(&root, &first.id).unwrap();
+        let record = load_record(&root, &first.id).unwrap();
+        let actions = record.history.iter().map(|e| e.action.clone()).collect::<Vec<_>>();
+        assert_eq!(actions, [ChangeSetAction::Created, ChangeSetAction::Applied, ChangeSetAction::Reverted]);
+        assert_eq!(record.source.as_deref(), Some("stream-1"));
+
+        let all = list_change_sets(&root, false).unwrap();
+        assert_eq!(all.len(), 2);
+        let summary = all.iter().find(|s| s.id == first.id).unwrap();
+        assert_eq!(summary.last_action, ChangeSetAction::Reverted);
+        assert!(matches!(summary.status, ChangeSetStatus::Pending));
+        assert!(record.change_set.files[0].modifications.iter().all(|m| matches!(m.status, ModificationStatus::Pending)));
+
+        // 撤销后回到收件箱，可以重新接受
+        assert_eq!(list_change_sets(&root, true).unwrap().len(), 1);
+        accept_recorded(&root, &first.id, &["m1".to_string()]).unwrap();
+        assert_eq!(fs::read_to_string(root.join("stories/ch1.txt")).unwrap(), "第一行\n新第二行\n");
+        assert!(load_record(&root, "../escape").is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use crate::change_sets;
+use crate::modification_types::{
+    ChangeSet, FileModification, FileModificationStatus, FileOperation, Modification, ModificationStatus, ModificationType,
+};
+use crate::path_sandbox::PathSandbox;
+use crate::project_settings::{SettingSource, Sourced};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// 章节元数据（前端 ChapterService 维护，字段为 camelCase）
+pub const CHAPTERS_META_PATH: &str = ".novel/.settings/chapters.json";
+
+const CHAPTER_END_MARK: &str = "【本章完】";
+
+/// 统计字数：每个中日韩文字计一字，连续的字母数字计一词，标点与空白不计。
+pub fn count_words(text: &str) -> usize {
+    let mut count = 0;
+    let mut in_word = false;
+    for c in text.chars() {
+        if is_cjk(c) {
+            count += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                count += 1;
+            }
+            in_word = true;
+        } else {
+            in_word = false;
+        }
+    }
+    count
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF       // 平假名、片假名
+        | 0x3400..=0x4DBF     // 扩展 A
+        | 0x4E00..=0x9FFF     // 基本汉字
+        | 0xAC00..=0xD7AF     // 韩文音节
+        | 0xF900..=0xFAFF     // 兼容汉字
+        | 0x20000..=0x2FA1F)  // 扩展 B 及以后
+}
+
+/// 场景分隔行，如 `***`、`* * *`、`---`、`＊＊＊`、`◇◇◇`
+fn is_scene_break(line: &str) -> bool {
+    let t: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    t.chars().count() >= 3 && t.chars().all(|c| matches!(c, '*' | '＊' | '-' | '—' | '◇' | '◆' | '§' | '#' | '~' | '～'))
+}
+
+/// 选择分章位置，返回下半章起始行的下标（0 起）。
+///
+/// 优先选字数最接近目标、且上半章至少达到目标一半的场景分隔行；没有时取不超过目标的最后一个段落边界。
+pub fn find_split_line(text: &str, target: usize) -> Option<usize> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut prefix = Vec::with_capacity(lines.len() + 1);
+    prefix.push(0usize);
+    for line in &lines {
+        prefix.push(prefix.last().copied().unwrap_or(0) + count_words(line));
+    }
+    let total = *prefix.last()?;
+    if target == 0 || total <= target {
+        return None;
+    }
+
+    let scene = (1..lines.len())
+        .filter(|&i| is_scene_break(lines[i]) && prefix[i] * 2 >= target && prefix[i] < total)
+        .min_by_key(|&i| prefix[i].abs_diff(target));
+    if scene.is_some() {
+        return scene;
+    }
+    let paragraph = (1..lines.len())
+        .rev()
+        .find(|&i| !lines[i - 1].trim().is_empty() && prefix[i] <= target && prefix[i] > 0 && prefix[i] < total);
+    paragraph.or_else(|| (1..lines.len()).find(|&i| prefix[i] > 0 && prefix[i] < total))
+}
+
+/// 下一章的文件名：文件名中最后一段数字加一并保持补零宽度，没有数字时追加 `-2`；跳过已存在的文件。
+pub fn next_chapter_path(path: &str, exists: impl Fn(&str) -> bool) -> String {
+    let (dir, file) = match path.rsplit_once('/') {
+        Some((d, f)) => (format!("{d}/"), f),
+        None => (String::new(), path),
+    };
+    let (stem, ext) = match file.rsplit_once('.') {
+        Some((s, e)) if !s.is_empty() => (s, format!(".{e}")),
+        _ => (file, String::new()),
+    };
+    // 取文件名中最后一段数字，如 `chapter-009`、`第3章`
+    let (head, width, mut n, tail) = match stem.rfind(|c: char| c.is_ascii_digit()) {
+        Some(last) => {
+            let end = last + 1;
+            let start = stem[..end].trim_end_matches(|c: char| c.is_ascii_digit()).len();
+            let n = stem[start..end].parse::<u64>().unwrap_or(0) + 1;
+            (stem[..start].to_string(), end - start, n, &stem[end..])
+        }
+        None => (format!("{stem}-"), 1, 2, ""),
+    };
+    loop {
+        let candidate = format!("{dir}{head}{n:0width$}{tail}{ext}");
+        if !exists(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// 章节超过目标字数时生成分章提案：截断当前章、依次新建后续章节直到每章都不超过目标，
+/// 并更新 `chapters.json`。
+///
+/// 不超过目标或找不到合适分割点时返回 `None`。
+pub fn propose_split(root: &Path, chapter_path: &str, target: u32) -> Result<Option<ChangeSet>, String> {
+    let sandbox = PathSandbox::new(root);
+    let full = sandbox.resolve(chapter_path)?;
+    if !full.is_file() {
+        return Ok(None);
+    }
+    let original = fs::read_to_string(&full).map_err(|e| format!("read chapter failed: {e}"))?;
+    let Some(split) = find_split_line(&original, target as usize) else {
+        return Ok(None);
+    };
+    let lines: Vec<&str> = original.lines().collect();
+    let ends_with_mark = lines.iter().rev().find(|l| !l.trim().is_empty()).is_some_and(|l| l.trim() == CHAPTER_END_MARK);
+
+    // 后续各章在原文中的行范围：去掉开头的空行与场景分隔行，剩余部分仍超出目标时继续分割
+    let mut parts: Vec<(usize, usize)> = Vec::new();
+    let mut start = split;
+    loop {
+        while start < lines.len() && (lines[start].trim().is_empty() || is_scene_break(lines[start])) {
+            start += 1;
+        }
+        match find_split_line(&lines[start..].join("\n"), target as usize) {
+            Some(i) => {
+                parts.push((start, start + i));
+                start += i;
+            }
+            None => {
+                parts.push((start, lines.len()));
+                break;
+            }
+        }
+    }
+    let trailing_newline = if original.ends_with('\n') { "\n" } else { "" };
+    let mut taken: Vec<String> = Vec::new();
+    let mut prev_path = chapter_path.to_string();
+    let next_parts: Vec<(String, String)> = parts
+        .iter()
+        .map(|&(from, to)| {
+            let mut part = lines[from..to].to_vec();
+            // 中间各章与上半章一样以结束标记收尾；最后一章沿用原文结尾
+            if ends_with_mark && to < lines.len() {
+                part.push(CHAPTER_END_MARK);
+            }
+            let path = next_chapter_path(&prev_path, |p| {
+                taken.iter().any(|t| t == p) || sandbox.resolve(p).map(|f| f.exists()).unwrap_or(true)
+            });
+            taken.push(path.clone());
+            prev_path = path.clone();
+            (path, part.join("\n") + trailing_newline)
+        })
+        .collect();
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut counter = 0usize;
+    let mut modification = |mod_type, line_start: usize, line_end: usize, original_text: Option<String>, modified_text: Option<String>| {
+        counter += 1;
+        Modification {
+            id: format!("mod-{now}-{counter}"),
+            mod_type,
+            line_start: line_start as u32,
+            line_end: line_end as u32,
+            original_text,
+            modified_text,
+            status: ModificationStatus::Pending,
+        }
+    };
+    let file = |file_path: &str, operation, original_content: String, modifications| FileModification {
+        file_path: file_path.to_string(),
+        original_content,
+        modifications,
+        status: FileModificationStatus::Pending,
+        operation,
+        new_path: None,
+    };
+
+    // 上半章删去分割点之后的内容；原文以结束标记收尾时保留标记
+    let cut = lines[split..].join("\n");
+    let (cut_type, replacement) = if ends_with_mark {
+        (ModificationType::Modify, Some(CHAPTER_END_MARK.to_string()))
+    } else {
+        (ModificationType::Delete, None)
+    };
+    let truncate = modification(cut_type, split + 1, lines.len(), Some(cut), replacement);
+    let line_count = |text: &str| text.lines().count().max(1);
+    let mut files = vec![file(chapter_path, FileOperation::Edit, original.clone(), vec![truncate])];
+    for (path, content) in &next_parts {
+        let create = modification(ModificationType::CreateFile, 1, line_count(content), None, Some(content.clone()));
+        files.push(file(path, FileOperation::Create, String::new(), vec![create]));
+    }
+
+    let meta_full = sandbox.resolve(CHAPTERS_META_PATH)?;
+    let meta_original = if meta_full.is_file() {
+        Some(fs::read_to_string(&meta_full).map_err(|e| format!("read chapters meta failed: {e}"))?)
+    } else {
+        None
+    };
+    let first_words = count_words(&lines[..split].join("\n"));
+    let next_meta: Vec<(&str, usize)> = next_parts.iter().map(|(p, c)| (p.as_str(), count_words(c))).collect();
+    let meta = insert_chapter_meta(meta_original.as_deref(), chapter_path, first_words, &next_meta, now)?;
+    files.push(match meta_original {
+        Some(old) => {
+            let m = modification(ModificationType::Modify, 1, line_count(&old), Some(old.clone()), Some(meta));
+            file(CHAPTERS_META_PATH, FileOperation::Edit, old, vec![m])
+        }
+        None => {
+            let m = modification(ModificationType::CreateFile, 1, line_count(&meta), None, Some(meta));
+            file(CHAPTERS_META_PATH, FileOperation::Create, String::new(), vec![m])
+        }
+    });
+    Ok(Some(ChangeSet::new(files)))
+}
+
+/// 生效的目标字数：项目或本次请求显式设置时优先，否则使用智能体自身的设置；0 表示不限制。
+pub fn word_target(agent_target: Option<u32>, setting: &Sourced<u32>) -> u32 {
+    match setting.source {
+        SettingSource::Workspace | SettingSource::Request => setting.value,
+        SettingSource::Default | SettingSource::Global => agent_target.unwrap_or(setting.value),
+    }
+}
+
+/// 分章提案在收件箱中的来源前缀，后接 stream id 或任务 id
+const SPLIT_SOURCE_PREFIX: &str = "chapter_split:";
+
+/// 检查本轮新建或整体写入的章节（`stories/` 下），超出目标的生成分章提案并保存到收件箱。
+///
+/// 收件箱中已有该章待审阅的分章提案时跳过，不重复提出。
+pub fn propose_for_written(root: &Path, written: &[String], target: u32, source: &str) -> Vec<ChangeSet> {
+    if target == 0 {
+        return Vec::new();
+    }
+    let pending = change_sets::list_change_sets(root, true).unwrap_or_else(|e| {
+        eprintln!("list change sets failed: {e}");
+        Vec::new()
+    });
+    let source = format!("{SPLIT_SOURCE_PREFIX}{source}");
+    let mut out = Vec::new();
+    for path in written.iter().filter(|p| p.starts_with("stories/")) {
+        let proposed = pending.iter().any(|s| {
+            s.source.as_deref().is_some_and(|src| src.starts_with(SPLIT_SOURCE_PREFIX)) && s.files.first() == Some(path)
+        });
+        if proposed {
+            continue;
+        }
+        match propose_split(root, path, target) {
+            Ok(Some(cs)) => {
+                if let Err(e) = change_sets::record_change_set(root, &cs, Some(&source)) {
+                    eprintln!("Failed to record chapter split: {e}");
+                }
+                out.push(cs);
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("propose chapter split for {path} failed: {e}"),
+        }
+    }
+    out
+}
+
+/// 在 `chapters.json` 中把新章节按顺序插到当前章之后，后续章节的 order 依次后移；保留未知字段。
+fn insert_chapter_meta(
+    raw: Option<&str>,
+    current: &str,
+    current_words: usize,
+    next: &[(&str, usize)],
+    now: i64,
+) -> Result<String, String> {
+    let mut meta: Value = match raw {
+        Some(raw) if !raw.trim().is_empty() => serde_json::from_str(raw).map_err(|e| format!("parse chapters meta failed: {e}"))?,
+        _ => serde_json::json!({ "chapters": [] }),
+    };
+    let chapters = meta
+        .get_mut("chapters")
+        .and_then(|c| c.as_array_mut())
+        .ok_or("chapters meta has no chapters list")?;
+    let order_of = |c: &Value| c.get("order").and_then(|o| o.as_i64()).unwrap_or(0);
+
+    let title_of = |path: &str| {
+        let file = path.rsplit('/').next().unwrap_or(path);
+        file.rsplit_once('.').map(|(s, _)| s).unwrap_or(file).to_string()
+    };
+    let entry = |path: &str, words: usize, order: i64| {
+        serde_json::json!({
+            "id": format!("chapter-{now}-{}", &uuid::Uuid::new_v4().simple().to_string()[..7]),
+            "filePath": path,
+            "title": title_of(path),
+            "status": "draft",
+            "order": order,
+            "wordCount": words,
+            "lastModified": now,
+        })
+    };
+
+    let pos = chapters.iter().position(|c| c.get("filePath").and_then(|p| p.as_str()) == Some(current));
+    let current_order = match pos {
+        Some(i) => {
+            chapters[i]["wordCount"] = Value::from(current_words);
+            chapters[i]["lastModified"] = Value::from(now);
+            order_of(&chapters[i])
+        }
+        None => {
+            let order = chapters.iter().map(order_of).max().map(|o| o + 1).unwrap_or(0);
+            chapters.push(entry(current, current_words, order));
+            order
+        }
+    };
+    let shift = next.len() as i64;
+    for c in chapters.iter_mut() {
+        let order = order_of(c);
+        if order > current_order {
+            c["order"] = Value::from(order + shift);
+        }
+    }
+    for (i, (path, words)) in next.iter().enumerate() {
+        chapters.push(entry(path, *words, current_order + 1 + i as i64));
+    }
+    serde_json::to_string_pretty(&meta).map_err(|e| format!("serialize chapters meta failed: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::change_sets::apply_change_set;
+
+    #[test]
+    fn counts_cjk_and_latin_words() {
+        assert_eq!(count_words("他说：“Hello world！”"), 4);
+        assert_eq!(count_words("第3章 AI觉醒"), 6);
+        assert_eq!(count_words("  \n，。"), 0);
+    }
+
+    #[test]
+    fn prefers_scene_breaks_then_paragraphs() {
+        let text = "一二三四\n五六七八\n***\n九十\n十一十二";
+        assert_eq!(find_split_line(text, 6), Some(2));
+        assert_eq!(find_split_line("一二三\n四五六\n七八九", 7), Some(2));
+        assert_eq!(find_split_line("一二三四五六七八九十", 5), None);
+        assert_eq!(find_split_line("短\n章", 10), None);
+    }
+
+    #[test]
+    fn numbers_the_next_chapter() {
+        assert_eq!(next_chapter_path("stories/chapter-009.txt", |_| false), "stories/chapter-010.txt");
+        assert_eq!(next_chapter_path("stories/第3章.txt", |p| p.ends_with("第4章.txt")), "stories/第5章.txt");
+        assert_eq!(next_chapter_path("stories/开篇.txt", |_| false), "stories/开篇-2.txt");
+    }
+
+    #[test]
+    fn split_proposal_applies_cleanly() {
+        let root = TempDir::new("split");
+        fs::create_dir_all(root.join("stories")).unwrap();
+        fs::create_dir_all(root.join(".novel/.settings")).unwrap();
+        fs::write(root.join("stories/chapter-001.txt"), "甲乙丙丁\n戊己庚辛\n* * *\n壬癸子丑\n【本章完】\n").unwrap();
+        fs::write(root.join("stories/chapter-002.txt"), "后一章\n").unwrap();
+        fs::write(
+            root.join(CHAPTERS_META_PATH),
+            r#"{"chapters":[{"id":"a","filePath":"stories/chapter-001.txt","title":"一","order":0,"extra":1},{"id":"b","filePath":"stories/chapter-002.txt","title":"二","order":1}]}"#,
+        )
+        .unwrap();
+
+        let cs = propose_split(&root, "stories/chapter-001.txt", 8).unwrap().unwrap();
+        assert_eq!(cs.files[1].file_path, "stories/chapter-003.txt");
+        let ids: Vec<String> = cs.files.iter().flat_map(|f| f.modifications.iter().map(|m| m.id.clone())).collect();
+        apply_change_set(&root, cs, &ids).unwrap();
+
+        assert_eq!(fs::read_to_string(root.join("stories/chapter-001.txt")).unwrap(), "甲乙丙丁\n戊己庚辛\n【本章完】\n");
+        assert_eq!(fs::read_to_string(root.join("stories/chapter-003.txt")).unwrap(), "壬癸子丑\n【本章完】\n");
+        let meta: Value = serde_json::from_str(&fs::read_to_string(root.join(CHAPTERS_META_PATH)).unwrap()).unwrap();
+        let chapters = meta["chapters"].as_array().unwrap();
+        assert_eq!(chapters[0]["extra"], 1);
+        assert_eq!(chapters[0]["wordCount"], 8);
+        assert_eq!(chapters[1]["order"], 2);
+        assert_eq!(chapters[2]["filePath"], "stories/chapter-003.txt");
+        assert_eq!(chapters[2]["order"], 1);
+    }
+
+    #[test]
+    fn long_chapter_is_split_until_every_part_fits() {
+        let root = TempDir::new("split");
+        fs::create_dir_all(root.join("stories")).unwrap();
+        fs::write(root.join("stories/chapter-001.txt"), "甲乙丙丁\n戊己庚辛\n* * *\n壬癸子丑\n寅卯辰巳\n午未申酉\n【本章完】\n").unwrap();
+
+        let cs = propose_split(&root, "stories/chapter-001.txt", 8).unwrap().unwrap();
+        let paths: Vec<&str> = cs.files.iter().map(|f| f.file_path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["stories/chapter-001.txt", "stories/chapter-002.txt", "stories/chapter-003.txt", CHAPTERS_META_PATH]
+        );
+        let ids: Vec<String> = cs.files.iter().flat_map(|f| f.modifications.iter().map(|m| m.id.clone())).collect();
+        apply_change_set(&root, cs, &ids).unwrap();
+
+        for (path, expected) in [
+            ("stories/chapter-001.txt", "甲乙丙丁\n戊己庚辛\n【本章完】\n"),
+            ("stories/chapter-002.txt", "壬癸子丑\n寅卯辰巳\n【本章完】\n"),
+            ("stories/chapter-003.txt", "午未申酉\n【本章完】\n"),
+        ] {
+            let text = fs::read_to_string(root.join(path)).unwrap();
+            assert_eq!(text, expected);
+            assert!(count_words(&text.replace(CHAPTER_END_MARK, "")) <= 8);
+        }
+        let meta: Value = serde_json::from_str(&fs::read_to_string(root.join(CHAPTERS_META_PATH)).unwrap()).unwrap();
+        let orders: Vec<(&str, i64)> = meta["chapters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| (c["filePath"].as_str().unwrap(), c["order"].as_i64().unwrap()))
+            .collect();
+        assert_eq!(orders, [("stories/chapter-001.txt", 0), ("stories/chapter-002.txt", 1), ("stories/chapter-003.txt", 2)]);
+    }
+
+    #[test]
+    fn does_not_repeat_pending_split_proposals() {
+        let root = TempDir::new("split");
+        fs::create_dir_all(root.join("stories")).unwrap();
+        fs::write(root.join("stories/chapter-001.txt"), "甲乙丙丁\n戊己庚辛\n壬癸子丑\n").unwrap();
+        let written = ["stories/chapter-001.txt".to_string(), "outline/plan.md".to_string()];
+
+        assert!(propose_for_written(&root, &written, 0, "s1").is_empty());
+        assert_eq!(propose_for_written(&root, &written, 8, "s1").len(), 1);
+        assert!(propose_for_written(&root, &written, 8, "s2").is_empty());
+        let pending = change_sets::list_change_sets(&root, true).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].source.as_deref(), Some("chapter_split:s1"));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::app_data;
+use crate::schema::{self, Schema};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
@@ -49,13 +50,30 @@ pub struct ChatSessionSummary {
   pub message_count: usize,
 }
 
+/// 版本 0：顶层直接是会话数组。
+pub const SCHEMA: Schema = Schema {
+  name: "chat history",
+  current: 1,
+  detect: schema::detect_array,
+  migrations: &[wrap_sessions],
+};
+
+fn wrap_sessions(value: serde_json::Value) -> Result<serde_json::Value, String> {
+  schema::wrap_array(value, "sessions")
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryFile {
+  sessions: Vec<ChatSession>,
+}
+
 pub fn load(app: &tauri::AppHandle) -> Result<Vec<ChatSession>, String> {
   let path = history_path(app)?;
-  if !path.exists() {
+  let Some(value) = schema::read(&path, &SCHEMA)? else {
     return Ok(Vec::new());
-  }
-  let raw = fs::read_to_string(&path).map_err(|e| format!("read history failed: {e}"))?;
-  serde_json::from_str(&raw).map_err(|e| format!("parse history failed: {e}"))
+  };
+  let file: HistoryFile = serde_json::from_value(value).map_err(|e| format!("parse history failed: {e}"))?;
+  Ok(file.sessions)
 }
 
 pub fn save(app: &tauri::AppHandle, sessions: &[ChatSession]) -> Result<(), String> {
@@ -63,10 +81,25 @@ pub fn save(app: &tauri::AppHandle, sessions: &[ChatSession]) -> Result<(), Stri
   if let Some(parent) = path.parent() {
     fs::create_dir_all(parent).map_err(|e| format!("create history dir failed: {e}"))?;
   }
-  let raw = serde_json::to_string_pretty(sessions).map_err(|e| format!("serialize history failed: {e}"))?;
+  let raw = SCHEMA.to_string(&HistoryFile { sessions: sessions.to_vec() })?;
   fs::write(path, raw).map_err(|e| format!("write history failed: {e}"))
 }
 
 fn history_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
   app_data::data_file_path(app, "chat_history.json")
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn loads_every_previous_version() {
+    let raw = include_str!("../fixtures/schema/chat_history.v0.json");
+    let (value, from) = SCHEMA.migrate(serde_json::from_str(raw).unwrap()).unwrap();
+    assert_eq!(from, 0);
+    let file: HistoryFile = serde_json::from_value(value).unwrap();
+    assert_eq!(file.sessions.len(), 1);
+    assert_eq!(file.sessions[0].messages[1].content, "好的，先从开篇说起。");
+  }
+}
@@ -1,23 +1,37 @@
 use crate::app_settings;
 use crate::agents;
 use crate::agent_system;
+use crate::agent_history;
+use crate::ai_response_parser::{self, FileEditStream};
 use crate::ai_types::ChatMessage;
 use crate::app_data;
 use crate::branding;
+use crate::bundle;
+use crate::change_sets;
+use crate::chapter_split;
 use crate::chat_history;
+use crate::jobs;
+use crate::mcp;
+use crate::modification_types::ChangeSet;
+use crate::project_settings;
+use crate::schema;
 use crate::secrets;
-use crate::skills::{Skill, SkillManager};
+use crate::skills::{self, Skill, SkillManager, SkillSource, SourcedSkill};
 use crate::spec_kit;
 use crate::spec_kit_export;
+use crate::unified_diff;
+use crate::workspace_agents;
 use crate::state::AppState;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
 use tauri::AppHandle;
 use tauri::Emitter;
+use tauri::Manager;
 use tauri::State;
 use notify::{EventKind, RecursiveMode, Watcher};
 
@@ -42,9 +56,21 @@ pub fn set_workspace(app: AppHandle, state: State<'_, AppState>, path: String) -
     eprintln!("fs_watcher_start_error: {e}");
     let _ = app.emit("fs_watch_error", serde_json::json!({ "message": e }));
   }
+  // MCP server 可能依赖工作区（如文件系统 server），切换时全部关闭，下次使用时再启动
+  if let Ok(mut hub) = state.mcp.lock() {
+    hub.shutdown_all();
+  }
   if let Err(e) = save_last_workspace(&app, &root) {
     eprintln!("save_last_workspace_failed: {e}");
   }
+  let active_jobs = state
+    .jobs
+    .lock()
+    .map(|j| j.keys().cloned().collect::<Vec<_>>())
+    .unwrap_or_default();
+  if let Err(e) = jobs::recover_interrupted(&root, &active_jobs) {
+    eprintln!("recover_jobs_failed: {e}");
+  }
   Ok(WorkspaceInfo {
     root: root.to_string_lossy().to_string(),
   })
@@ -53,15 +79,10 @@ pub fn set_workspace(app: AppHandle, state: State<'_, AppState>, path: String) -
 #[tauri::command]
 pub fn get_last_workspace(app: AppHandle) -> Result<Option<String>, String> {
   let path = last_workspace_path(&app)?;
-  if !path.exists() {
+  let Some(value) = schema::read(&path, &LAST_WORKSPACE_SCHEMA)? else {
     return Ok(None);
-  }
-  let raw = fs::read_to_string(&path).map_err(|e| format!("read last workspace failed: {e}"))?;
-  #[derive(Deserialize)]
-  struct LastWorkspace {
-    path: String,
-  }
-  let v: LastWorkspace = serde_json::from_str(&raw).map_err(|e| format!("parse last workspace failed: {e}"))?;
+  };
+  let v: LastWorkspace = serde_json::from_value(value).map_err(|e| format!("parse last workspace failed: {e}"))?;
   let p = v.path.trim().to_string();
   if p.is_empty() {
     return Ok(None);
@@ -72,6 +93,19 @@ pub fn get_last_workspace(app: AppHandle) -> Result<Option<String>, String> {
   Ok(Some(p))
 }
 
+/// 版本 0：没有版本号的 `{ path }`
+const LAST_WORKSPACE_SCHEMA: schema::Schema = schema::Schema {
+  name: "last workspace",
+  current: 1,
+  detect: schema::unversioned,
+  migrations: &[schema::unchanged],
+};
+
+#[derive(Serialize, Deserialize)]
+struct LastWorkspace {
+  path: String,
+}
+
 fn last_workspace_path(app: &AppHandle) -> Result<PathBuf, String> {
   app_data::data_file_path(app, "last_workspace.json")
 }
@@ -81,7 +115,9 @@ fn save_last_workspace(app: &AppHandle, root: &Path) -> Result<(), String> {
   if let Some(parent) = path.parent() {
     fs::create_dir_all(parent).map_err(|e| format!("create last workspace dir failed: {e}"))?;
   }
-  let payload = serde_json::json!({ "path": root.to_string_lossy().to_string() }).to_string();
+  let payload = LAST_WORKSPACE_SCHEMA.to_string(&LastWorkspace {
+    path: root.to_string_lossy().to_string(),
+  })?;
   fs::write(path, payload).map_err(|e| format!("write last workspace failed: {e}"))
 }
 
@@ -116,13 +152,12 @@ pub fn init_novel(state: State<'_, AppState>) -> Result<(), String> {
     fs::write(outline_path, raw).map_err(|e| format!("write outline failed: {e}"))?;
   }
 
-  let project_settings = novel_dir.join(".settings").join("project.json");
-  if !project_settings.exists() {
-    let raw = serde_json::json!({
-      "chapter_word_target": 2000
-    })
-    .to_string();
-    fs::write(project_settings, raw).map_err(|e| format!("write project settings failed: {e}"))?;
+  if !novel_dir.join(".settings").join("project.json").exists() {
+    let layer = project_settings::SettingsLayer {
+      chapter_word_target: Some(project_settings::DEFAULT_CHAPTER_WORD_TARGET),
+      ..Default::default()
+    };
+    project_settings::save(&root, &layer)?;
   }
 
   let characters_path = novel_dir.join(".cache").join("characters.json");
@@ -254,6 +289,11 @@ pub fn rename_entry(state: State<'_, AppState>, from_relative_path: String, to_r
   fs::rename(from, to).map_err(|e| format!("rename failed: {e}"))
 }
 
+#[tauri::command]
+pub fn get_data_dir_info(app: AppHandle) -> Result<app_data::DataDirInfo, String> {
+  app_data::data_dir_info(&app)
+}
+
 #[tauri::command]
 pub fn get_app_settings(app: AppHandle) -> Result<app_settings::AppSettings, String> {
   let mut s = app_settings::load(&app)?;
@@ -286,17 +326,36 @@ pub fn set_app_settings(app: AppHandle, settings: app_settings::AppSettings) ->
   app_settings::save(&app, &s)
 }
 
+/// 合并默认值、全局设置、当前工作区与 `overrides` 后的设置，附带每个值的来源
+#[tauri::command]
+pub fn get_effective_settings(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  overrides: Option<project_settings::SettingsLayer>,
+) -> Result<project_settings::EffectiveSettings, String> {
+  let root = get_workspace_root(&state).ok();
+  project_settings::effective(&app, root.as_deref(), &overrides.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_project_settings(state: State<'_, AppState>) -> Result<project_settings::SettingsLayer, String> {
+  let root = get_workspace_root(&state)?;
+  project_settings::load(&root)
+}
+
+#[tauri::command]
+pub fn set_project_settings(state: State<'_, AppState>, settings: project_settings::SettingsLayer) -> Result<(), String> {
+  let root = get_workspace_root(&state)?;
+  project_settings::save(&root, &settings)
+}
+
 #[allow(non_snake_case)]
 #[tauri::command]
 pub fn get_api_key_status(app: AppHandle, providerId: Option<String>, provider_id: Option<String>) -> Result<bool, String> {
   let pid = providerId
     .or(provider_id)
     .unwrap_or_default();
-  match secrets::get_api_key(&app, pid.trim()) {
-    Ok(Some(v)) => Ok(!v.trim().is_empty()),
-    Ok(None) => Ok(false),
-    Err(e) => Err(e),
-  }
+  secrets::has_api_key(&app, pid.trim())
 }
 
 #[allow(non_snake_case)]
@@ -307,6 +366,7 @@ pub fn set_api_key(
   provider_id: Option<String>,
   apiKey: Option<String>,
   api_key: Option<String>,
+  name: Option<String>,
 ) -> Result<(), String> {
   let pid = providerId.or(provider_id).unwrap_or_default();
   let pid = pid.trim();
@@ -318,17 +378,125 @@ pub fn set_api_key(
   if key.is_empty() {
     return Err("API Key 不能为空".to_string());
   }
-  secrets::set_api_key(&app, pid, key)
+  secrets::set_named_api_key(&app, pid, name.as_deref(), key)
+}
+
+/// 删除 provider 的一个 key；`name` 为空时删除默认 key。
+#[tauri::command]
+pub fn delete_api_key(app: AppHandle, provider_id: String, name: Option<String>) -> Result<(), String> {
+  secrets::delete_api_key(&app, &provider_id, name.as_deref())
+}
+
+#[tauri::command]
+pub fn list_api_keys(app: AppHandle, provider_id: String) -> Result<secrets::ApiKeysInfo, String> {
+  secrets::list_api_keys(&app, &provider_id)
+}
+
+#[tauri::command]
+pub fn set_api_key_policy(app: AppHandle, provider_id: String, policy: secrets::KeyPolicy) -> Result<(), String> {
+  secrets::set_key_policy(&app, &provider_id, policy)
+}
+
+/// 密钥存储后端以及文件后端是否需要输入口令。
+#[tauri::command]
+pub fn get_secrets_status(app: AppHandle) -> Result<secrets::SecretsStatus, String> {
+  secrets::status(&app)
+}
+
+/// 解锁加密文件后端；首次调用时用该口令初始化。
+#[tauri::command]
+pub fn unlock_secrets(app: AppHandle, passphrase: String) -> Result<(), String> {
+  secrets::unlock(&app, &passphrase)
+}
+
+/// 全局智能体与当前工作区 `.novel/agents` 下的智能体，标明各自来源
+#[tauri::command]
+pub fn get_agents(app: AppHandle, state: State<'_, AppState>) -> Result<Vec<workspace_agents::SourcedAgent>, String> {
+  let root = get_workspace_root(&state).ok();
+  workspace_agents::load_sourced(&app, root.as_deref())
+}
+
+/// 只保存全局智能体；工作区智能体在各自的定义文件中修改，这里保留同 id 的原全局版本
+#[tauri::command]
+pub fn set_agents(app: AppHandle, state: State<'_, AppState>, agents_list: Vec<agents::Agent>) -> Result<(), String> {
+  let workspace_ids: Vec<String> = match get_workspace_root(&state) {
+    Ok(root) => workspace_agents::load_dir(&root).into_iter().map(|(a, _)| a.id).collect(),
+    Err(_) => Vec::new(),
+  };
+  let previous = agents::load(&app)?;
+  let list: Vec<agents::Agent> = agents_list
+    .into_iter()
+    .filter_map(|a| {
+      if !workspace_ids.contains(&a.id) {
+        return Some(a);
+      }
+      previous.iter().find(|p| p.id == a.id).cloned()
+    })
+    .collect();
+  agents::save(&app, &list)
+}
+
+/// 展开父智能体与提示词片段后的完整 system prompt，用于预览
+#[tauri::command]
+pub fn get_agent_prompt(app: AppHandle, state: State<'_, AppState>, agent_id: String) -> Result<String, String> {
+  let root = get_workspace_root(&state).ok();
+  let list = workspace_agents::load(&app, root.as_deref())?;
+  let agent = list
+    .iter()
+    .find(|a| a.id == agent_id)
+    .ok_or_else(|| format!("agent not found: {agent_id}"))?;
+  agents::resolve_system_prompt(agent, &list, &agents::load_fragments(&app)?)
+}
+
+#[tauri::command]
+pub fn get_prompt_fragments(app: AppHandle) -> Result<Vec<agents::PromptFragment>, String> {
+  agents::load_fragments(&app)
+}
+
+/// 保存自定义片段；与内置片段同 id 的条目会覆盖内置内容
+#[tauri::command]
+pub fn set_prompt_fragments(app: AppHandle, fragments: Vec<agents::PromptFragment>) -> Result<(), String> {
+  if let Some(f) = fragments.iter().find(|f| f.id.trim().is_empty()) {
+    return Err(format!("prompt fragment id is empty: {}", f.name));
+  }
+  agents::save_custom_fragments(&app, &fragments)
+}
+
+#[tauri::command]
+pub fn list_agent_versions(app: AppHandle, agent_id: String) -> Result<Vec<agent_history::AgentVersion>, String> {
+  agent_history::list(&app, &agent_id)
 }
 
+/// 比较两个版本的 system_prompt；`to_version` 省略时与当前保存的智能体比较
 #[tauri::command]
-pub fn get_agents(app: AppHandle) -> Result<Vec<agents::Agent>, String> {
-  agents::load(&app)
+pub fn diff_agent_versions(
+  app: AppHandle,
+  agent_id: String,
+  from_version: u32,
+  to_version: Option<u32>,
+) -> Result<Vec<agent_history::DiffLine>, String> {
+  let from = agent_history::get(&app, &agent_id, from_version)?.agent;
+  let to = match to_version {
+    Some(v) => agent_history::get(&app, &agent_id, v)?.agent,
+    None => agents::load(&app)?
+      .into_iter()
+      .find(|a| a.id == agent_id)
+      .ok_or_else(|| format!("agent not found: {agent_id}"))?,
+  };
+  Ok(agent_history::diff_lines(&from.system_prompt, &to.system_prompt))
 }
 
+/// 恢复到指定版本；恢复本身也会记录为一个新版本，已删除的智能体会被重新加入
 #[tauri::command]
-pub fn set_agents(app: AppHandle, agents_list: Vec<agents::Agent>) -> Result<(), String> {
-  agents::save(&app, &agents_list)
+pub fn restore_agent_version(app: AppHandle, agent_id: String, version: u32) -> Result<agents::Agent, String> {
+  let restored = agent_history::get(&app, &agent_id, version)?.agent;
+  let mut list = agents::load(&app)?;
+  match list.iter_mut().find(|a| a.id == agent_id) {
+    Some(slot) => *slot = restored.clone(),
+    None => list.push(restored.clone()),
+  }
+  agents::save(&app, &list)?;
+  Ok(restored)
 }
 
 #[tauri::command]
@@ -343,6 +511,97 @@ pub fn import_agents(app: AppHandle, json: String) -> Result<(), String> {
   agents::save(&app, &list)
 }
 
+/// 导出配置包：provider（不含 key）、智能体、自定义 skill、MCP server 与当前工作区的故事模板
+#[tauri::command]
+pub fn export_bundle(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+  let mut providers = app_settings::load(&app)?.providers;
+  for p in &mut providers {
+    p.api_key.clear();
+  }
+  let story_templates = match get_workspace_root(&state) {
+    Ok(root) => spec_kit::list_story_templates(&root.join(".novel"))?,
+    Err(_) => Vec::new(),
+  };
+  let bundle = bundle::Bundle {
+    exported_at: Utc::now().to_rfc3339(),
+    providers,
+    agents: agents::load(&app)?,
+    skills: skills::load_custom(&app)?,
+    mcp_servers: mcp::load(&app)?,
+    story_templates,
+  };
+  bundle::SCHEMA.to_string(&bundle)
+}
+
+/// 导入配置包；`dry_run` 时只返回每一项的冲突与处理方式，不做任何修改
+#[tauri::command]
+pub fn import_bundle(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  json: String,
+  strategy: bundle::MergeStrategy,
+  dry_run: Option<bool>,
+) -> Result<bundle::ImportReport, String> {
+  let dry_run = dry_run.unwrap_or(false);
+  let incoming = bundle::parse(&json)?;
+  let novel_dir = if incoming.story_templates.is_empty() {
+    None
+  } else {
+    Some(get_workspace_root(&state)?.join(".novel"))
+  };
+
+  let mut settings = app_settings::load(&app)?;
+  let mut agent_list = agents::load(&app)?;
+  let mut custom_skills = skills::load_custom(&app)?;
+  let mut servers = mcp::load(&app)?;
+  let mut templates = match &novel_dir {
+    Some(dir) => spec_kit::list_story_templates(dir)?,
+    None => Vec::new(),
+  };
+  let existing = bundle::Existing {
+    providers: &mut settings.providers,
+    agents: &mut agent_list,
+    skills: &mut custom_skills,
+    mcp_servers: &mut servers,
+    story_templates: &mut templates,
+  };
+  let mut report = bundle::merge(incoming, existing, strategy);
+  report.dry_run = dry_run;
+  if dry_run {
+    return Ok(report);
+  }
+
+  // 写入前先校验全部输出，避免写到一半才失败
+  app_settings::SCHEMA.to_string(&settings)?;
+  agents::SCHEMA.to_string(&agent_list)?;
+  skills::SCHEMA.to_string(&custom_skills)?;
+  mcp::SCHEMA.to_string(&servers)?;
+  if let Some(dir) = &novel_dir {
+    for t in &templates {
+      spec_kit::story_template_path(dir, &t.template_id)?;
+      spec_kit::STORY_TEMPLATE_SCHEMA.to_string(t)?;
+    }
+  }
+
+  let mut written: Vec<&str> = Vec::new();
+  let mut step = |part: &'static str, result: Result<(), String>| match result {
+    Ok(()) => {
+      written.push(part);
+      Ok(())
+    }
+    Err(e) if written.is_empty() => Err(format!("import {part} failed: {e}")),
+    Err(e) => Err(format!("import {part} failed after writing {}: {e}", written.join(", "))),
+  };
+  step("settings", app_settings::save(&app, &settings))?;
+  step("agents", agents::save(&app, &agent_list))?;
+  step("skills", skills::save_custom(&app, &custom_skills))?;
+  step("mcp servers", mcp::save(&app, &servers))?;
+  if let Some(dir) = &novel_dir {
+    step("story templates", templates.iter().try_for_each(|t| spec_kit::save_story_template(dir, t)))?;
+  }
+  Ok(report)
+}
+
 #[tauri::command]
 pub fn save_chat_session(app: AppHandle, session: chat_history::ChatSession) -> Result<(), String> {
   let mut sessions = chat_history::load(&app)?;
@@ -553,6 +812,7 @@ fn format_status(st: git2::Status) -> String {
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn chat_generate_stream(
   app: AppHandle,
   window: tauri::Window,
@@ -561,6 +821,7 @@ pub fn chat_generate_stream(
   messages: Vec<ChatMessage>,
   use_markdown: bool,
   agent_id: Option<String>,
+  mcp_context: Option<Vec<mcp::McpContextRef>>,
 ) -> Result<(), String> {
   let app = app.clone();
   let workspace_root = get_workspace_root(&state)?;
@@ -584,16 +845,41 @@ pub fn chat_generate_stream(
         return;
       }
     };
-    let effective_use_markdown = use_markdown || settings.output.use_markdown;
-    let agents_list = agents::load(&app).unwrap_or_else(|_| agents::default_agents());
-    let effective_agent_id = agent_id.unwrap_or_else(|| settings.active_agent_id.clone());
+    // 请求中的 use_markdown 只能打开 Markdown 输出，关闭时沿用各层设置
+    let request = project_settings::SettingsLayer {
+      active_agent_id: agent_id,
+      use_markdown: use_markdown.then_some(true),
+      ..Default::default()
+    };
+    let workspace = project_settings::load(&workspace_root).unwrap_or_else(|e| {
+      eprintln!("load project settings failed: {e}");
+      Default::default()
+    });
+    let effective = project_settings::resolve(Some(&settings), &workspace, &request);
+    let effective_use_markdown = effective.use_markdown.value;
+    let agents_list = workspace_agents::load(&app, Some(&workspace_root)).unwrap_or_else(|_| agents::default_agents());
+    let effective_agent_id = effective.active_agent_id.value;
     let agent = agents_list.iter().find(|a| a.id == effective_agent_id);
-    let agent_system = agent.map(|a| a.system_prompt.clone()).unwrap_or_default();
+    let mut agent_system = match agent.map(|a| {
+      agents::load_fragments(&app).and_then(|fragments| agents::resolve_system_prompt(a, &agents_list, &fragments))
+    }) {
+      Some(Ok(prompt)) => prompt,
+      Some(Err(e)) => {
+        let _ = window.emit(
+          "ai_error",
+          serde_json::json!({ "streamId": stream_id, "stage": "agent", "message": e }),
+        );
+        let _ = window.emit("ai_stream_done", serde_json::json!({ "streamId": stream_id }));
+        return;
+      }
+      None => String::new(),
+    };
     let agent_temp = agent.map(|a| a.temperature);
     let agent_max = agent.map(|a| a.max_tokens);
+    let word_target = chapter_split::word_target(agent.map(|a| a.chapter_word_target), &effective.chapter_word_target);
     let client = reqwest::Client::new();
 
-    let active_provider_id = settings.active_provider_id.clone();
+    let active_provider_id = effective.active_provider_id.value;
     let providers = settings.providers.clone();
     let current_provider = providers
       .iter()
@@ -614,50 +900,32 @@ pub fn chat_generate_stream(
       }
     };
 
+    let mcp_refs = mcp_context.unwrap_or_default();
+    if !mcp_refs.is_empty() {
+      match resolve_mcp_context(app.clone(), mcp_refs).await {
+        Ok(ctx) => agent_system = format!("{agent_system}\n\n{ctx}"),
+        Err(e) => {
+          eprintln!("ai_error: {}", e);
+          let _ = window.emit(
+            "ai_error",
+            serde_json::json!({ "streamId": stream_id, "stage": "mcp", "message": e }),
+          );
+          let _ = window.emit("ai_stream_done", serde_json::json!({ "streamId": stream_id }));
+          return;
+        }
+      }
+    }
+
     let workspace_root_clone = workspace_root.clone();
     let mut runtime = agent_system::AgentRuntime::new(workspace_root);
+    attach_mcp_tools(&app, &mut runtime).await;
     let start = Instant::now();
     let (mut response, perf) = match runtime
       .run_react(messages, agent_system.clone(), |msgs| {
         let provider_cfg = current_provider.clone();
         let client = client.clone();
         let app = app.clone();
-        let agent_temp = agent_temp;
-        let agent_max = agent_max;
-        async move {
-          let mut system = String::new();
-          for m in msgs.iter().filter(|m| m.role == "system") {
-            if !system.is_empty() {
-              system.push('\n');
-            }
-            system.push_str(m.content.as_str());
-          }
-          let filtered = msgs.into_iter().filter(|m| m.role != "system").collect::<Vec<_>>();
-          
-          match provider_cfg.kind {
-            app_settings::ProviderKind::OpenAI | app_settings::ProviderKind::OpenAICompatible => {
-              call_openai_compatible(
-                &app,
-                &client,
-                &provider_cfg, // pass full provider config
-                &filtered,
-                system.as_str(),
-                agent_temp,
-                agent_max
-              ).await
-            },
-            app_settings::ProviderKind::Anthropic => {
-              call_anthropic(
-                &app,
-                &client,
-                &provider_cfg,
-                &filtered,
-                system.as_str(),
-                agent_max
-              ).await
-            },
-          }
-        }
+        async move { call_model(&app, &client, &provider_cfg, msgs, agent_temp, agent_max).await }
       })
       .await
     {
@@ -701,41 +969,37 @@ pub fn chat_generate_stream(
       response = normalize_plaintext(&response);
     }
 
-    // Parse AI response for file modification instructions
-    let _change_set = match crate::ai_response_parser::parse_ai_response(&response, &workspace_root_clone) {
-      Ok(Some(cs)) => {
-        // Emit the ChangeSet to the frontend
-        let payload = serde_json::json!({
-          "streamId": stream_id,
-          "changeSet": cs
-        });
-        let _ = window.emit("ai_change_set", payload);
-        Some(cs)
-      }
-      Ok(None) => None,
-      Err(e) => {
-        eprintln!("Failed to parse AI response for modifications: {}", e);
-        None
-      }
-    };
-
-    let step_chars = 48usize;
-    let mut buf = String::new();
-    let mut count = 0usize;
-    for ch in response.chars() {
-      buf.push(ch);
-      count += 1;
-      if count >= step_chars {
-        let payload = serde_json::json!({ "streamId": stream_id, "token": buf });
-        let _ = window.emit("ai_stream_token", payload);
-        buf = String::new();
-        count = 0;
-        tokio::time::sleep(std::time::Duration::from_millis(15)).await;
+    // 发出 token 的同时增量解析文件操作块
+    let mut edit_stream = FileEditStream::default();
+    let mut edits = Vec::new();
+    emit_stream_tokens(&window, &stream_id, &response, |chunk| edits.extend(edit_stream.push(chunk))).await;
+    let outcome = ai_response_parser::resolve_stream(edit_stream, edits, &workspace_root_clone);
+    if let Some(cs) = &outcome.change_set {
+      // 先落盘，窗口重载或崩溃后仍可在收件箱中审阅
+      if let Err(e) = change_sets::record_change_set(&workspace_root_clone, cs, Some(&stream_id)) {
+        eprintln!("Failed to record change set: {e}");
       }
+      // Emit the ChangeSet to the frontend
+      let payload = serde_json::json!({
+        "streamId": stream_id,
+        "changeSet": cs,
+        "diagnostics": outcome.diagnostics
+      });
+      let _ = window.emit("ai_change_set", payload);
+    } else if outcome.has_errors() {
+      eprintln!("Failed to parse AI response for modifications: {:?}", outcome.diagnostics);
+      let _ = window.emit(
+        "ai_change_set_error",
+        serde_json::json!({ "streamId": stream_id, "diagnostics": outcome.diagnostics }),
+      );
     }
-    if !buf.is_empty() {
-      let payload = serde_json::json!({ "streamId": stream_id, "token": buf });
-      let _ = window.emit("ai_stream_token", payload);
+
+    // 工具写入的章节超出目标字数时，提出分章方案供审阅
+    for cs in chapter_split::propose_for_written(&workspace_root_clone, runtime.written_files(), word_target, &stream_id) {
+      let _ = window.emit(
+        "ai_change_set",
+        serde_json::json!({ "streamId": stream_id, "changeSet": cs, "reason": "chapter_split" }),
+      );
     }
 
     let payload_done = serde_json::json!({ "streamId": stream_id });
@@ -745,31 +1009,156 @@ pub fn chat_generate_stream(
   Ok(())
 }
 
-async fn call_openai_compatible(
+/// 把完整回复切成小段依次发出 `ai_stream_token`，前端按流式输出展示；每段也交给 `on_chunk`
+async fn emit_stream_tokens(window: &tauri::Window, stream_id: &str, response: &str, mut on_chunk: impl FnMut(&str)) {
+  let step_chars = 48usize;
+  let mut buf = String::new();
+  let mut count = 0usize;
+  for ch in response.chars() {
+    buf.push(ch);
+    count += 1;
+    if count >= step_chars {
+      on_chunk(&buf);
+      let payload = serde_json::json!({ "streamId": stream_id, "token": buf });
+      let _ = window.emit("ai_stream_token", payload);
+      buf = String::new();
+      count = 0;
+      tokio::time::sleep(std::time::Duration::from_millis(15)).await;
+    }
+  }
+  if !buf.is_empty() {
+    on_chunk(&buf);
+    let payload = serde_json::json!({ "streamId": stream_id, "token": buf });
+    let _ = window.emit("ai_stream_token", payload);
+  }
+}
+
+/// 连接已启用的 MCP server 并把它们的工具挂到 agent 上；连接失败只记录日志。
+pub(crate) async fn attach_mcp_tools(app: &AppHandle, runtime: &mut agent_system::AgentRuntime) {
+  let app = app.clone();
+  let conns = tauri::async_runtime::spawn_blocking(move || {
+    let servers = mcp::load(&app)?;
+    let state = app.state::<AppState>();
+    mcp::connect_enabled(&state.mcp, &servers)
+  })
+  .await
+  .map_err(|e| format!("attach mcp tools failed: {e}"))
+  .and_then(|r| r);
+  match conns {
+    Ok(conns) => {
+      for conn in &conns {
+        runtime.attach_mcp(conn);
+      }
+    }
+    Err(e) => eprintln!("load_mcp_servers_failed: {e}"),
+  }
+}
+
+/// 读取对话引用的 MCP 资源与 prompt；server 进程的启动与读取都是阻塞调用，放到阻塞线程里执行。
+async fn resolve_mcp_context(app: AppHandle, refs: Vec<mcp::McpContextRef>) -> Result<String, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let servers = mcp::load(&app)?;
+    let state = app.state::<AppState>();
+    mcp::resolve_context(&state.mcp, &servers, &refs)
+  })
+  .await
+  .map_err(|e| format!("mcp context failed: {e}"))?
+}
+
+/// 合并 system 消息后按 provider 类型分发请求，供对话、后台任务等复用。
+pub(crate) async fn call_model(
   app: &AppHandle,
   client: &reqwest::Client,
   cfg: &app_settings::ModelProvider,
-  messages: &[ChatMessage],
-  system_prompt: &str,
+  msgs: Vec<ChatMessage>,
   temperature_override: Option<f32>,
   max_tokens_override: Option<u32>,
 ) -> Result<String, String> {
-  let api_key = match secrets::get_api_key(app, &cfg.id) {
-    Ok(Some(v)) => v,
-    Ok(None) => cfg.api_key.trim().to_string(),
-    Err(e) => return Err(format!("keyring read failed: {e}")),
-  };
+  let mut system = String::new();
+  for m in msgs.iter().filter(|m| m.role == "system") {
+    if !system.is_empty() {
+      system.push('\n');
+    }
+    system.push_str(m.content.as_str());
+  }
+  let filtered = msgs.into_iter().filter(|m| m.role != "system").collect::<Vec<_>>();
 
-  if api_key.trim().is_empty() {
+  match cfg.kind {
+    app_settings::ProviderKind::OpenAI | app_settings::ProviderKind::OpenAICompatible => {
+      call_openai_compatible(
+        app,
+        client,
+        cfg,
+        &filtered,
+        system.as_str(),
+        temperature_override,
+        max_tokens_override,
+      )
+      .await
+    }
+    app_settings::ProviderKind::Anthropic => {
+      call_anthropic(app, client, cfg, &filtered, system.as_str(), max_tokens_override).await
+    }
+  }
+}
+
+/// 按 key 策略依次尝试 provider 的 key：鉴权失败或被限流时换下一个。
+async fn with_provider_keys<F, Fut>(app: &AppHandle, cfg: &app_settings::ModelProvider, call: F) -> Result<String, String>
+where
+  F: Fn(String) -> Fut,
+  Fut: std::future::Future<Output = Result<String, String>>,
+{
+  let mut keys = secrets::api_key_candidates(app, &cfg.id).map_err(|e| format!("keyring read failed: {e}"))?;
+  if keys.is_empty() && !cfg.api_key.trim().is_empty() {
+    keys.push(cfg.api_key.trim().to_string());
+  }
+  if keys.is_empty() {
     return Err(format!(
       "api key not found for provider={}; 请在“设置 > 模型配置”中填写 API Key",
       cfg.id
     ));
   }
+  let mut last_err = String::new();
+  for key in keys {
+    match call(key).await {
+      Err(e) if is_key_error(&e) => last_err = e,
+      other => return other,
+    }
+  }
+  Err(last_err)
+}
+
+fn is_key_error(e: &str) -> bool {
+  ["http 401", "http 403", "http 429"].iter().any(|p| e.starts_with(p))
+}
+
+async fn call_openai_compatible(
+  app: &AppHandle,
+  client: &reqwest::Client,
+  cfg: &app_settings::ModelProvider,
+  messages: &[ChatMessage],
+  system_prompt: &str,
+  temperature_override: Option<f32>,
+  max_tokens_override: Option<u32>,
+) -> Result<String, String> {
+  with_provider_keys(app, cfg, |api_key| {
+    openai_compatible_request(client, cfg, api_key, messages, system_prompt, temperature_override, max_tokens_override)
+  })
+  .await
+}
+
+async fn openai_compatible_request(
+  client: &reqwest::Client,
+  cfg: &app_settings::ModelProvider,
+  api_key: String,
+  messages: &[ChatMessage],
+  system_prompt: &str,
+  temperature_override: Option<f32>,
+  max_tokens_override: Option<u32>,
+) -> Result<String, String> {
   let base = cfg.base_url.trim_end_matches('/');
   let url = format!("{base}/chat/completions");
   let model = cfg.model_name.clone();
-  let api_key = api_key.trim().to_string();
 
   let mut out_messages: Vec<serde_json::Value> = Vec::new();
   if !system_prompt.trim().is_empty() {
@@ -838,17 +1227,20 @@ async fn call_anthropic(
   system_prompt: &str,
   max_tokens_override: Option<u32>,
 ) -> Result<String, String> {
-  let api_key = match secrets::get_api_key(app, &cfg.id) {
-    Ok(Some(v)) => v,
-    Ok(None) => cfg.api_key.trim().to_string(),
-    Err(e) => return Err(format!("keyring read failed: {e}")),
-  };
-  if api_key.trim().is_empty() {
-    return Err(format!(
-      "api key not found for provider={}; 请在“设置 > 模型配置”中填写 API Key",
-      cfg.id
-    ));
-  }
+  with_provider_keys(app, cfg, |api_key| {
+    anthropic_request(client, cfg, api_key, messages, system_prompt, max_tokens_override)
+  })
+  .await
+}
+
+async fn anthropic_request(
+  client: &reqwest::Client,
+  cfg: &app_settings::ModelProvider,
+  api_key: String,
+  messages: &[ChatMessage],
+  system_prompt: &str,
+  max_tokens_override: Option<u32>,
+) -> Result<String, String> {
   let url = "https://api.anthropic.com/v1/messages";
   let body = serde_json::json!({
     "model": cfg.model_name,
@@ -880,13 +1272,15 @@ async fn call_anthropic(
 #[tauri::command]
 pub async fn ai_assistance_generate(
   app: AppHandle,
-  _state: State<'_, AppState>,
+  state: State<'_, AppState>,
   prompt: String,
 ) -> Result<String, String> {
   let settings = app_settings::load(&app)?;
+  let root = get_workspace_root(&state).ok();
+  let effective = project_settings::effective(&app, root.as_deref(), &Default::default())?;
   let client = reqwest::Client::new();
   
-  let active_provider_id = settings.active_provider_id.clone();
+  let active_provider_id = effective.active_provider_id.value;
   let providers = settings.providers.clone();
   let current_provider = providers
     .iter()
@@ -935,9 +1329,7 @@ pub fn spec_kit_generate_outline(state: State<'_, AppState>) -> Result<spec_kit:
   let template = spec_kit::load_story_template(&novel_dir, &config.story_type)?;
   let spec = spec_kit::generate_story_spec_from_config(&config, &template);
 
-  let spec_path = novel_dir.join(".spec-kit").join("story_spec.json");
-  let raw = serde_json::to_string_pretty(&spec).map_err(|e| format!("serialize story spec failed: {e}"))?;
-  fs::write(spec_path, raw).map_err(|e| format!("write story spec failed: {e}"))?;
+  spec_kit::save_story_spec(&novel_dir, &spec)?;
 
   let _ = append_spec_kit_log(
     &root,
@@ -955,12 +1347,29 @@ pub fn spec_kit_generate_outline(state: State<'_, AppState>) -> Result<spec_kit:
 #[tauri::command]
 pub fn spec_kit_validate_story_spec(state: State<'_, AppState>) -> Result<spec_kit::ValidationReport, String> {
   let root = get_workspace_root(&state)?;
+  let report = validate_workspace_story_spec(&root)?;
+
+  let err_count = report.issues.iter().filter(|i| i.severity == "error").count();
+  let warn_count = report.issues.iter().filter(|i| i.severity == "warning").count();
+  let _ = append_spec_kit_log(
+    &root,
+    serde_json::json!({
+      "ts": Utc::now().to_rfc3339(),
+      "event": "validate_story_spec",
+      "errors": err_count,
+      "warnings": warn_count
+    }),
+  );
+
+  Ok(report)
+}
+
+/// 校验工作区的 `story_spec.json`，并把大纲时间线冲突一并写入报告。
+pub(crate) fn validate_workspace_story_spec(root: &Path) -> Result<spec_kit::ValidationReport, String> {
   let novel_dir = root.join(".novel");
   let config = spec_kit::load_config(&novel_dir).ok();
 
-  let spec_path = novel_dir.join(".spec-kit").join("story_spec.json");
-  let raw = fs::read_to_string(&spec_path).map_err(|e| format!("read story spec failed: {e}"))?;
-  let spec: spec_kit::StorySpec = serde_json::from_str(&raw).map_err(|e| format!("parse story spec failed: {e}"))?;
+  let spec = spec_kit::load_story_spec(&novel_dir)?;
 
   let mut report = spec_kit::validate_story_spec(&spec, config.as_ref());
 
@@ -978,18 +1387,6 @@ pub fn spec_kit_validate_story_spec(state: State<'_, AppState>) -> Result<spec_k
     }
   }
 
-  let err_count = report.issues.iter().filter(|i| i.severity == "error").count();
-  let warn_count = report.issues.iter().filter(|i| i.severity == "warning").count();
-  let _ = append_spec_kit_log(
-    &root,
-    serde_json::json!({
-      "ts": Utc::now().to_rfc3339(),
-      "event": "validate_story_spec",
-      "errors": err_count,
-      "warnings": warn_count
-    }),
-  );
-
   Ok(report)
 }
 
@@ -998,18 +1395,12 @@ pub fn spec_kit_match_character_arcs(state: State<'_, AppState>) -> Result<spec_
   let root = get_workspace_root(&state)?;
   let novel_dir = root.join(".novel");
 
-  let spec_path = novel_dir.join(".spec-kit").join("story_spec.json");
-  let raw = fs::read_to_string(&spec_path).map_err(|e| format!("read story spec failed: {e}"))?;
-  let mut spec: spec_kit::StorySpec = serde_json::from_str(&raw).map_err(|e| format!("parse story spec failed: {e}"))?;
+  let mut spec = spec_kit::load_story_spec(&novel_dir)?;
 
   let arc_map = spec_kit::generate_arc_map_and_fill_defaults(&mut spec);
 
-  let spec_raw = serde_json::to_string_pretty(&spec).map_err(|e| format!("serialize story spec failed: {e}"))?;
-  fs::write(&spec_path, spec_raw).map_err(|e| format!("write story spec failed: {e}"))?;
-
-  let arc_map_path = novel_dir.join(".spec-kit").join("arc_map.json");
-  let arc_raw = serde_json::to_string_pretty(&arc_map).map_err(|e| format!("serialize arc map failed: {e}"))?;
-  fs::write(arc_map_path, arc_raw).map_err(|e| format!("write arc map failed: {e}"))?;
+  spec_kit::save_story_spec(&novel_dir, &spec)?;
+  spec_kit::save_arc_map(&novel_dir, &arc_map)?;
 
   let _ = append_spec_kit_log(
     &root,
@@ -1346,28 +1737,226 @@ fn build_tree(root: &Path, path: &Path, max_depth: usize) -> Result<FsEntry, Str
 
 // ============ Skill Commands ============
 
+/// 内置、全局与当前工作区 skill 合并后的列表，标明各自来源
+#[tauri::command]
+pub fn get_skills(app: AppHandle, state: State<'_, AppState>) -> Result<Vec<SourcedSkill>, String> {
+    let manager = load_skill_manager(&app, &state)?;
+    Ok(manager.sourced().into_iter().cloned().collect())
+}
+
+#[tauri::command]
+pub fn get_skill_categories(app: AppHandle, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(load_skill_manager(&app, &state)?.categories())
+}
+
+#[tauri::command]
+pub fn get_skills_by_category(app: AppHandle, state: State<'_, AppState>, category: String) -> Result<Vec<Skill>, String> {
+    let manager = load_skill_manager(&app, &state)?;
+    Ok(manager.get_by_category(&category).into_iter().cloned().collect())
+}
+
+#[tauri::command]
+pub fn apply_skill(app: AppHandle, state: State<'_, AppState>, skill_id: String, content: String) -> Result<String, String> {
+    Ok(load_skill_manager(&app, &state)?.apply_skill(&skill_id, &content))
+}
+
+/// 用当前 provider 与智能体执行 skill（智能体的 system 提示放在 skill 提示之前），输出与 `chat_generate_stream` 相同的流式事件，返回 stream id。
+///
+/// 内容来自文件选区时，token 发完后另以 `ai_change_set` 事件发出针对该选区的 ChangeSet。
+#[tauri::command]
+pub fn run_skill(
+    app: AppHandle,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    skill_id: String,
+    content: String,
+    options: Option<skills::RunSkillOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let root = get_workspace_root(&state).ok();
+    if let Some(selection) = &options.selection {
+        skills::validate_selection(root.as_deref().ok_or("workspace not set")?, selection)?;
+    }
+    let skill = SkillManager::load(&app, root.as_deref())?
+        .get(&skill_id)
+        .cloned()
+        .ok_or_else(|| format!("skill not found: {skill_id}"))?;
+    if !skill.enabled {
+        return Err(format!("skill disabled: {skill_id}"));
+    }
+
+    let request = project_settings::SettingsLayer {
+        active_provider_id: options.provider_id.clone(),
+        active_agent_id: options.agent_id.clone(),
+        use_markdown: options.use_markdown,
+        ..Default::default()
+    };
+    let effective = project_settings::effective(&app, root.as_deref(), &request)?;
+    // 显式指定的 provider 不存在时直接报错，不回退到其它 provider
+    let provider_id = options.provider_id.clone().unwrap_or(effective.active_provider_id.value);
+    let provider = app_settings::load(&app)?
+        .providers
+        .into_iter()
+        .find(|p| p.id == provider_id)
+        .ok_or_else(|| format!("provider not found: {provider_id}"))?;
+    let agents_list = workspace_agents::load(&app, root.as_deref()).unwrap_or_else(|_| agents::default_agents());
+    let agent_id = options.agent_id.clone().unwrap_or(effective.active_agent_id.value);
+    let agent = agents_list.iter().find(|a| a.id == agent_id);
+    if agent.is_none() && options.agent_id.is_some() {
+        return Err(format!("agent not found: {agent_id}"));
+    }
+    let agent_prompt = match agent {
+        Some(a) => agents::resolve_system_prompt(a, &agents_list, &agents::load_fragments(&app)?)?,
+        None => String::new(),
+    };
+    let agent_temp = agent.map(|a| a.temperature);
+    let agent_max = agent.map(|a| a.max_tokens);
+    let use_markdown = effective.use_markdown.value;
+    let stream_id = options
+        .stream_id
+        .clone()
+        .unwrap_or_else(|| format!("skill-{}", uuid::Uuid::new_v4()));
+
+    let returned_id = stream_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let _ = window.emit("ai_stream_start", serde_json::json!({ "streamId": stream_id, "skillId": skill.id }));
+        let client = reqwest::Client::new();
+        let messages = skills::build_messages(&agent_prompt, &skill, &content);
+        let mut response = match call_model(&app, &client, &provider, messages, agent_temp, agent_max).await {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("ai_error provider={} err={}", provider.id, e);
+                let _ = window.emit(
+                    "ai_error",
+                    serde_json::json!({ "streamId": stream_id, "provider": provider.id, "stage": "provider", "message": e }),
+                );
+                let _ = window.emit("ai_stream_done", serde_json::json!({ "streamId": stream_id }));
+                return;
+            }
+        };
+        if !use_markdown {
+            response = normalize_plaintext(&response);
+        }
+
+        emit_stream_tokens(&window, &stream_id, &response, |_| {}).await;
+
+        if let (Some(selection), Some(root)) = (&options.selection, &root) {
+            match skills::selection_change_set(root, selection, &response) {
+                Ok(cs) => {
+                    if let Err(e) = change_sets::record_change_set(root, &cs, Some(&stream_id)) {
+                        eprintln!("Failed to record change set: {e}");
+                    }
+                    let _ = window.emit(
+                        "ai_change_set",
+                        serde_json::json!({ "streamId": stream_id, "changeSet": cs, "reason": "skill" }),
+                    );
+                }
+                Err(e) => {
+                    let _ = window.emit(
+                        "ai_error",
+                        serde_json::json!({ "streamId": stream_id, "stage": "change_set", "message": e }),
+                    );
+                }
+            }
+        }
+        let _ = window.emit("ai_stream_done", serde_json::json!({ "streamId": stream_id }));
+    });
+
+    Ok(returned_id)
+}
+
+/// 新建自定义 skill；id 为空时自动生成，与已有 skill（含内置）重名时报错
 #[tauri::command]
-pub fn get_skills() -> Vec<Skill> {
-    let manager = SkillManager::new();
-    manager.get_all().into_iter().cloned().collect()
+pub fn create_skill(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mut skill: Skill,
+    scope: Option<SkillSource>,
+) -> Result<SourcedSkill, String> {
+    let root = get_workspace_root(&state).ok();
+    let scope = scope.unwrap_or(SkillSource::Global);
+    skill.id = skill.id.trim().to_string();
+    if skill.id.is_empty() {
+        skill.id = format!("skill-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    }
+    if SkillManager::load(&app, root.as_deref())?.get(&skill.id).is_some() {
+        return Err(format!("skill already exists: {}", skill.id));
+    }
+    save_skill(&app, root.as_deref(), scope, skill)
 }
 
+/// 更新 skill；对内置 skill 调用即在该层保存一份同 id 的覆盖
 #[tauri::command]
-pub fn get_skill_categories() -> Vec<String> {
-    let manager = SkillManager::new();
-    manager.categories()
+pub fn update_skill(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    skill: Skill,
+    scope: Option<SkillSource>,
+) -> Result<SourcedSkill, String> {
+    let root = get_workspace_root(&state).ok();
+    if SkillManager::load(&app, root.as_deref())?.get(&skill.id).is_none() {
+        return Err(format!("skill not found: {}", skill.id));
+    }
+    save_skill(&app, root.as_deref(), scope.unwrap_or(SkillSource::Global), skill)
 }
 
+/// 从指定层删除 skill；删除覆盖后恢复为下一层（或内置）的版本
 #[tauri::command]
-pub fn get_skills_by_category(category: String) -> Vec<Skill> {
-    let manager = SkillManager::new();
-    manager.get_by_category(&category).into_iter().cloned().collect()
+pub fn delete_skill(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    skill_id: String,
+    scope: Option<SkillSource>,
+) -> Result<(), String> {
+    let root = get_workspace_root(&state).ok();
+    let scope = scope.unwrap_or(SkillSource::Global);
+    let mut layer = skills::load_layer(&app, root.as_deref(), scope)?;
+    let before = layer.len();
+    layer.retain(|s| s.id != skill_id);
+    if layer.len() == before {
+        return Err(format!("skill not found: {skill_id}"));
+    }
+    skills::save_layer(&app, root.as_deref(), scope, &layer)
 }
 
+/// 启用或停用 skill；内置 skill 会在该层保存一份覆盖
 #[tauri::command]
-pub fn apply_skill(skill_id: String, content: String) -> String {
-    let manager = SkillManager::new();
-    manager.apply_skill(&skill_id, &content)
+pub fn set_skill_enabled(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    skill_id: String,
+    enabled: bool,
+    scope: Option<SkillSource>,
+) -> Result<SourcedSkill, String> {
+    let root = get_workspace_root(&state).ok();
+    let mut skill = SkillManager::load(&app, root.as_deref())?
+        .get(&skill_id)
+        .cloned()
+        .ok_or_else(|| format!("skill not found: {skill_id}"))?;
+    skill.enabled = enabled;
+    save_skill(&app, root.as_deref(), scope.unwrap_or(SkillSource::Global), skill)
+}
+
+fn load_skill_manager(app: &AppHandle, state: &State<'_, AppState>) -> Result<SkillManager, String> {
+    let root = get_workspace_root(state).ok();
+    SkillManager::load(app, root.as_deref())
+}
+
+/// 写入指定层后返回合并后的结果（工作区层可能仍覆盖全局层的修改）
+fn save_skill(app: &AppHandle, root: Option<&Path>, scope: SkillSource, skill: Skill) -> Result<SourcedSkill, String> {
+    if skill.name.trim().is_empty() {
+        return Err("skill name is required".to_string());
+    }
+    let id = skill.id.clone();
+    let mut layer = skills::load_layer(app, root, scope)?;
+    skills::upsert(&mut layer, skill);
+    skills::save_layer(app, root, scope, &layer)?;
+    SkillManager::load(app, root)?
+        .sourced()
+        .into_iter()
+        .find(|s| s.skill.id == id)
+        .cloned()
+        .ok_or_else(|| format!("skill not found: {id}"))
 }
 
 // ============ Book Split Commands ============
@@ -1890,3 +2479,323 @@ pub async fn book_extract_techniques(content: String) -> Result<Vec<WritingTechn
     
     Ok(techniques)
 }
+
+// ============ Job Commands ============
+
+#[tauri::command]
+pub fn job_create_draft(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  from_chapter: u32,
+  to_chapter: u32,
+  agent_id: Option<String>,
+  provider_id: Option<String>,
+  instructions: Option<String>,
+) -> Result<jobs::Job, String> {
+  let root = get_workspace_root(&state)?;
+  let spec = spec_kit::load_story_spec(&root.join(".novel"))?;
+  let tasks = jobs::build_draft_tasks(&spec, from_chapter, to_chapter, instructions.as_deref().unwrap_or(""))?;
+
+  let settings = app_settings::load(&app)?;
+  let request = project_settings::SettingsLayer {
+    active_provider_id: provider_id.clone(),
+    active_agent_id: agent_id,
+    ..Default::default()
+  };
+  let effective = project_settings::effective(&app, Some(&root), &request)?;
+  let agent_id = effective.active_agent_id.value;
+  let provider_id = provider_id.unwrap_or(effective.active_provider_id.value);
+  if !settings.providers.iter().any(|p| p.id == provider_id) {
+    return Err(format!("provider not found: {provider_id}"));
+  }
+
+  let job = jobs::new_job(&agent_id, &provider_id, tasks);
+  jobs::save(&root, &job)?;
+  start_job(&app, &state, root, &job.id)?;
+  Ok(job)
+}
+
+#[tauri::command]
+pub fn job_list(state: State<'_, AppState>) -> Result<Vec<jobs::JobSummary>, String> {
+  let root = get_workspace_root(&state)?;
+  Ok(jobs::list(&root)?.iter().map(jobs::summarize).collect())
+}
+
+#[tauri::command]
+pub fn job_get(state: State<'_, AppState>, job_id: String) -> Result<jobs::Job, String> {
+  let root = get_workspace_root(&state)?;
+  jobs::load(&root, &job_id)
+}
+
+#[tauri::command]
+pub fn job_pause(state: State<'_, AppState>, job_id: String) -> Result<(), String> {
+  let running = state.jobs.lock().map_err(|_| "jobs lock poisoned")?.get(&job_id).cloned();
+  match running {
+    Some(control) => {
+      control.pause();
+      Ok(())
+    }
+    None => Err("job is not running".to_string()),
+  }
+}
+
+#[tauri::command]
+pub fn job_resume(app: AppHandle, state: State<'_, AppState>, job_id: String) -> Result<(), String> {
+  let root = get_workspace_root(&state)?;
+  let job = jobs::load(&root, &job_id)?;
+  match job.status {
+    jobs::JobStatus::Paused | jobs::JobStatus::Failed | jobs::JobStatus::Queued => start_job(&app, &state, root, &job_id),
+    _ => Err("job cannot be resumed".to_string()),
+  }
+}
+
+#[tauri::command]
+pub fn job_cancel(app: AppHandle, state: State<'_, AppState>, job_id: String) -> Result<(), String> {
+  let running = state.jobs.lock().map_err(|_| "jobs lock poisoned")?.get(&job_id).cloned();
+  if let Some(control) = running {
+    control.cancel();
+    return Ok(());
+  }
+  // 未在运行的任务直接落盘为 cancelled
+  let root = get_workspace_root(&state)?;
+  let mut job = jobs::load(&root, &job_id)?;
+  if job.status == jobs::JobStatus::Completed {
+    return Err("job already completed".to_string());
+  }
+  job.status = jobs::JobStatus::Cancelled;
+  for t in job.tasks.iter_mut().skip(job.current) {
+    t.status = jobs::TaskStatus::Cancelled;
+  }
+  jobs::save(&root, &job)?;
+  let _ = app.emit(
+    "job_progress",
+    serde_json::json!({ "jobId": job.id, "status": job.status, "current": job.current, "total": job.tasks.len() }),
+  );
+  Ok(())
+}
+
+fn start_job(app: &AppHandle, state: &State<'_, AppState>, root: PathBuf, job_id: &str) -> Result<(), String> {
+  let control = {
+    let mut running = state.jobs.lock().map_err(|_| "jobs lock poisoned")?;
+    if running.contains_key(job_id) {
+      return Err("job is already running".to_string());
+    }
+    let control = Arc::new(jobs::JobControl::default());
+    running.insert(job_id.to_string(), control.clone());
+    control
+  };
+  tauri::async_runtime::spawn(jobs::run(app.clone(), root, job_id.to_string(), control));
+  Ok(())
+}
+
+// ============ MCP Commands ============
+
+#[tauri::command]
+pub fn mcp_list_servers(app: AppHandle) -> Result<Vec<mcp::McpServer>, String> {
+  mcp::load(&app)
+}
+
+#[tauri::command]
+pub fn mcp_add_server(app: AppHandle, server: mcp::McpServer) -> Result<Vec<mcp::McpServer>, String> {
+  let mut servers = mcp::load(&app)?;
+  mcp::validate_server(&server, &servers)?;
+  servers.push(server);
+  mcp::save(&app, &servers)?;
+  Ok(servers)
+}
+
+#[tauri::command]
+pub fn mcp_update_server(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  server: mcp::McpServer,
+) -> Result<Vec<mcp::McpServer>, String> {
+  let mut servers = mcp::load(&app)?;
+  let idx = servers
+    .iter()
+    .position(|s| s.id == server.id)
+    .ok_or_else(|| format!("mcp server not found: {}", server.id))?;
+  let others = servers.iter().enumerate().filter(|(i, _)| *i != idx).map(|(_, s)| s.clone()).collect::<Vec<_>>();
+  mcp::validate_server(&server, &others)?;
+  servers[idx] = server;
+  mcp::save(&app, &servers)?;
+  // 配置变更后旧进程作废，下次使用时按新配置启动
+  state.mcp.lock().map_err(|_| "mcp lock poisoned")?.disconnect(&servers[idx].id);
+  Ok(servers)
+}
+
+#[tauri::command]
+pub fn mcp_set_server_enabled(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  server_id: String,
+  enabled: bool,
+) -> Result<Vec<mcp::McpServer>, String> {
+  let mut servers = mcp::load(&app)?;
+  let server = servers
+    .iter_mut()
+    .find(|s| s.id == server_id)
+    .ok_or_else(|| format!("mcp server not found: {server_id}"))?;
+  server.enabled = enabled;
+  mcp::save(&app, &servers)?;
+  if !enabled {
+    state.mcp.lock().map_err(|_| "mcp lock poisoned")?.disconnect(&server_id);
+  }
+  Ok(servers)
+}
+
+#[tauri::command]
+pub fn mcp_remove_server(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  server_id: String,
+) -> Result<Vec<mcp::McpServer>, String> {
+  let mut servers = mcp::load(&app)?;
+  let before = servers.len();
+  servers.retain(|s| s.id != server_id);
+  if servers.len() == before {
+    return Err(format!("mcp server not found: {server_id}"));
+  }
+  mcp::save(&app, &servers)?;
+  state.mcp.lock().map_err(|_| "mcp lock poisoned")?.disconnect(&server_id);
+  Ok(servers)
+}
+
+/// 重启 server 并立即连接，返回连接后的状态（失败信息在 `error` 中）。
+#[tauri::command]
+pub async fn mcp_restart_server(app: AppHandle, server_id: String) -> Result<mcp::McpServerStatus, String> {
+  let servers = mcp::load(&app)?;
+  let server = servers
+    .into_iter()
+    .find(|s| s.id == server_id)
+    .ok_or_else(|| format!("mcp server not found: {server_id}"))?;
+  if !server.enabled {
+    return Err(format!("mcp server {server_id} is disabled"));
+  }
+  tauri::async_runtime::spawn_blocking(move || {
+    let state = app.state::<AppState>();
+    state.mcp.lock().map_err(|_| "mcp lock poisoned")?.disconnect(&server.id);
+    // 启动与握手不持有锁；失败原因记录在状态里
+    let _ = mcp::connect_server(&state.mcp, &server);
+    let hub = state.mcp.lock().map_err(|_| "mcp lock poisoned")?;
+    Ok(hub.status(&server.id))
+  })
+  .await
+  .map_err(|e| format!("mcp restart failed: {e}"))?
+}
+
+#[tauri::command]
+pub fn mcp_get_status(app: AppHandle, state: State<'_, AppState>) -> Result<Vec<mcp::McpServerStatus>, String> {
+  let servers = mcp::load(&app)?;
+  let hub = state.mcp.lock().map_err(|_| "mcp lock poisoned")?;
+  Ok(servers.iter().map(|s| hub.status(&s.id)).collect())
+}
+
+/// 连接所有启用的 server，返回它们的资源与 prompt 列表，供对话中选择引用。
+#[tauri::command]
+pub async fn mcp_list_context(app: AppHandle) -> Result<Vec<mcp::McpServerStatus>, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let servers = mcp::load(&app)?;
+    let state = app.state::<AppState>();
+    mcp::connect_enabled(&state.mcp, &servers)?;
+    let hub = state.mcp.lock().map_err(|_| "mcp lock poisoned")?;
+    Ok(servers.iter().filter(|s| s.enabled).map(|s| hub.status(&s.id)).collect())
+  })
+  .await
+  .map_err(|e| format!("mcp list context failed: {e}"))?
+}
+
+/// 预览引用的资源 / prompt 展开后会拼入 system 提示的文本。
+#[tauri::command]
+pub async fn mcp_resolve_context(app: AppHandle, refs: Vec<mcp::McpContextRef>) -> Result<String, String> {
+  resolve_mcp_context(app, refs).await
+}
+
+// ============ ChangeSet Commands ============
+
+#[tauri::command]
+pub fn apply_change_set(
+  state: State<'_, AppState>,
+  change_set: ChangeSet,
+  accepted_ids: Vec<String>,
+) -> Result<ChangeSet, String> {
+  let root = get_workspace_root(&state)?;
+  let applied = change_sets::apply_change_set(&root, change_set, &accepted_ids)?;
+  if let Err(e) = change_sets::note_applied(&root, &applied, &accepted_ids) {
+    eprintln!("Failed to record change set: {e}");
+  }
+  Ok(applied)
+}
+
+#[tauri::command]
+pub fn revert_change_set(state: State<'_, AppState>, change_set_id: String) -> Result<Vec<String>, String> {
+  let root = get_workspace_root(&state)?;
+  let files = change_sets::revert_change_set(&root, &change_set_id)?;
+  if let Err(e) = change_sets::note_reverted(&root, &change_set_id) {
+    eprintln!("Failed to record change set: {e}");
+  }
+  Ok(files)
+}
+
+/// 列出保存在工作区中的 ChangeSet；`pending_only` 为 true 时只返回待审阅的。
+#[tauri::command]
+pub fn list_change_sets(
+  state: State<'_, AppState>,
+  pending_only: Option<bool>,
+) -> Result<Vec<change_sets::ChangeSetSummary>, String> {
+  let root = get_workspace_root(&state)?;
+  change_sets::list_change_sets(&root, pending_only.unwrap_or(false))
+}
+
+#[tauri::command]
+pub fn get_change_set(state: State<'_, AppState>, change_set_id: String) -> Result<change_sets::ChangeSetRecord, String> {
+  let root = get_workspace_root(&state)?;
+  change_sets::load_record(&root, &change_set_id)
+}
+
+#[tauri::command]
+pub fn accept_change_set(
+  state: State<'_, AppState>,
+  change_set_id: String,
+  accepted_ids: Vec<String>,
+) -> Result<change_sets::ChangeSetRecord, String> {
+  let root = get_workspace_root(&state)?;
+  change_sets::accept_recorded(&root, &change_set_id, &accepted_ids)
+}
+
+#[tauri::command]
+pub fn reject_change_set(state: State<'_, AppState>, change_set_id: String) -> Result<change_sets::ChangeSetRecord, String> {
+  let root = get_workspace_root(&state)?;
+  change_sets::reject_recorded(&root, &change_set_id)
+}
+
+/// 把保存的 ChangeSet 导出为 unified diff（`.patch`）文本。
+#[tauri::command]
+pub fn export_change_set_patch(state: State<'_, AppState>, change_set_id: String) -> Result<String, String> {
+  let root = get_workspace_root(&state)?;
+  let record = change_sets::load_record(&root, &change_set_id)?;
+  unified_diff::export_change_set(&record.change_set)
+}
+
+/// 导入 `.patch` 文本，生成待审阅的 ChangeSet 并放入收件箱。
+#[tauri::command]
+pub fn import_change_set_patch(state: State<'_, AppState>, patch: String) -> Result<ChangeSet, String> {
+  let root = get_workspace_root(&state)?;
+  let change_set = unified_diff::import_patch(&root, &patch)?;
+  change_sets::record_change_set(&root, &change_set, Some("patch"))?;
+  Ok(change_set)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn loads_every_previous_last_workspace() {
+    let raw = include_str!("../fixtures/schema/last_workspace.v0.json");
+    let (value, from) = LAST_WORKSPACE_SCHEMA.migrate(serde_json::from_str(raw).unwrap()).unwrap();
+    assert_eq!(from, 0);
+    let v: LastWorkspace = serde_json::from_value(value).unwrap();
+    assert_eq!(v.path, "/home/writer/novel");
+  }
+}
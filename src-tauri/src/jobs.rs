@@ -0,0 +1,458 @@
+use crate::agent_system::AgentRuntime;
+use crate::agents;
+use crate::ai_types::ChatMessage;
+use crate::app_settings;
+use crate::chapter_split;
+use crate::commands;
+use crate::project_settings;
+use crate::spec_kit;
+use crate::state::AppState;
+use crate::workspace_agents;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+  Queued,
+  Running,
+  Paused,
+  Cancelled,
+  Completed,
+  Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+  Pending,
+  Running,
+  Done,
+  Failed,
+  Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterTask {
+  pub chapter: u32,
+  pub title: String,
+  pub file_path: String,
+  pub prompt: String,
+  pub status: TaskStatus,
+  #[serde(default)]
+  pub result: String,
+  #[serde(default)]
+  pub error: Option<String>,
+  #[serde(default)]
+  pub started_at: i64,
+  #[serde(default)]
+  pub finished_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+  pub id: String,
+  pub agent_id: String,
+  pub provider_id: String,
+  pub status: JobStatus,
+  /// 下一个待执行任务的下标，即断点续跑的位置
+  pub current: usize,
+  pub tasks: Vec<ChapterTask>,
+  #[serde(default)]
+  pub error: Option<String>,
+  pub created_at: i64,
+  pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSummary {
+  pub id: String,
+  pub agent_id: String,
+  pub provider_id: String,
+  pub status: JobStatus,
+  pub current: usize,
+  pub total: usize,
+  pub updated_at: i64,
+}
+
+const SIGNAL_RUN: u8 = 0;
+const SIGNAL_PAUSE: u8 = 1;
+const SIGNAL_CANCEL: u8 = 2;
+
+/// 运行中任务的控制开关；runner 在每个章节任务之间检查一次。
+#[derive(Default)]
+pub struct JobControl {
+  signal: AtomicU8,
+}
+
+impl JobControl {
+  pub fn pause(&self) {
+    let _ = self
+      .signal
+      .compare_exchange(SIGNAL_RUN, SIGNAL_PAUSE, Ordering::SeqCst, Ordering::SeqCst);
+  }
+
+  pub fn cancel(&self) {
+    self.signal.store(SIGNAL_CANCEL, Ordering::SeqCst);
+  }
+
+  fn signal(&self) -> u8 {
+    self.signal.load(Ordering::SeqCst)
+  }
+}
+
+fn now_secs() -> i64 {
+  std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs() as i64
+}
+
+fn jobs_dir(root: &Path) -> PathBuf {
+  root.join(".novel").join(".jobs")
+}
+
+fn job_path(root: &Path, id: &str) -> Result<PathBuf, String> {
+  if id.trim().is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+    return Err("invalid job id".to_string());
+  }
+  Ok(jobs_dir(root).join(format!("{id}.json")))
+}
+
+pub fn load(root: &Path, id: &str) -> Result<Job, String> {
+  let path = job_path(root, id)?;
+  let raw = fs::read_to_string(&path).map_err(|e| format!("read job failed: {e}"))?;
+  serde_json::from_str(&raw).map_err(|e| format!("parse job failed: {e}"))
+}
+
+pub fn save(root: &Path, job: &Job) -> Result<(), String> {
+  let path = job_path(root, &job.id)?;
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).map_err(|e| format!("create jobs dir failed: {e}"))?;
+  }
+  let raw = serde_json::to_string_pretty(job).map_err(|e| format!("serialize job failed: {e}"))?;
+  // 先写临时文件再替换，避免崩溃时留下半截的检查点
+  let tmp = path.with_extension("json.tmp");
+  fs::write(&tmp, raw).map_err(|e| format!("write job failed: {e}"))?;
+  fs::rename(&tmp, &path).map_err(|e| format!("write job failed: {e}"))
+}
+
+pub fn list(root: &Path) -> Result<Vec<Job>, String> {
+  let dir = jobs_dir(root);
+  if !dir.exists() {
+    return Ok(Vec::new());
+  }
+  let mut out = Vec::new();
+  for e in fs::read_dir(&dir).map_err(|e| format!("read jobs dir failed: {e}"))? {
+    let p = e.map_err(|e| format!("read jobs entry failed: {e}"))?.path();
+    if p.extension().and_then(|x| x.to_str()) != Some("json") {
+      continue;
+    }
+    let Ok(raw) = fs::read_to_string(&p) else { continue };
+    if let Ok(job) = serde_json::from_str::<Job>(&raw) {
+      out.push(job);
+    }
+  }
+  out.sort_by_key(|j| std::cmp::Reverse(j.created_at));
+  Ok(out)
+}
+
+/// 应用重启后，上次处于 running/queued 的任务不会有 runner 接管，
+/// 统一标记为 paused，由用户决定是否继续。`active` 为当前仍在运行的任务 id。
+pub fn recover_interrupted(root: &Path, active: &[String]) -> Result<usize, String> {
+  let mut count = 0usize;
+  for mut job in list(root)? {
+    if job.status != JobStatus::Running && job.status != JobStatus::Queued {
+      continue;
+    }
+    if active.contains(&job.id) {
+      continue;
+    }
+    job.status = JobStatus::Paused;
+    if let Some(task) = job.tasks.get_mut(job.current) {
+      if task.status == TaskStatus::Running {
+        task.status = TaskStatus::Pending;
+      }
+    }
+    job.updated_at = now_secs();
+    save(root, &job)?;
+    count += 1;
+  }
+  Ok(count)
+}
+
+pub fn summarize(job: &Job) -> JobSummary {
+  JobSummary {
+    id: job.id.clone(),
+    agent_id: job.agent_id.clone(),
+    provider_id: job.provider_id.clone(),
+    status: job.status,
+    current: job.current,
+    total: job.tasks.len(),
+    updated_at: job.updated_at,
+  }
+}
+
+/// 根据 `story_spec.json` 中第 `from`..=`to` 章（从 1 开始）生成章节起草任务。
+pub fn build_draft_tasks(
+  spec: &spec_kit::StorySpec,
+  from: u32,
+  to: u32,
+  instructions: &str,
+) -> Result<Vec<ChapterTask>, String> {
+  if from == 0 || to < from {
+    return Err(format!("invalid chapter range: {from}-{to}"));
+  }
+  if to as usize > spec.chapters.len() {
+    return Err(format!("story spec only has {} chapters", spec.chapters.len()));
+  }
+  let mut tasks = Vec::new();
+  for n in from..=to {
+    let ch = &spec.chapters[(n - 1) as usize];
+    let file_path = format!("stories/chapter-{n:03}.txt");
+    let mut prompt = format!(
+      "请根据以下章节规划创作第{n}章《{title}》，目标约 {words} 字，完成后写入 {file_path}。\n\n幕：{act}\n节拍：{beat}\n",
+      title = ch.title,
+      words = ch.target_words,
+      act = ch.act,
+      beat = ch.beat_id,
+    );
+    if !ch.scenes.is_empty() {
+      prompt.push_str("场景：\n");
+      for s in &ch.scenes {
+        prompt.push_str(&format!(
+          "- 目标：{}；冲突：{}；代价：{}；转折：{}；地点：{}；人物：{}\n",
+          s.goal,
+          s.conflict,
+          s.stakes,
+          s.turn,
+          s.location,
+          s.characters.join("、")
+        ));
+      }
+    }
+    if !instructions.trim().is_empty() {
+      prompt.push_str("\n补充要求：\n");
+      prompt.push_str(instructions.trim());
+      prompt.push('\n');
+    }
+    tasks.push(ChapterTask {
+      chapter: n,
+      title: ch.title.clone(),
+      file_path,
+      prompt,
+      status: TaskStatus::Pending,
+      result: String::new(),
+      error: None,
+      started_at: 0,
+      finished_at: 0,
+    });
+  }
+  Ok(tasks)
+}
+
+pub fn new_job(agent_id: &str, provider_id: &str, tasks: Vec<ChapterTask>) -> Job {
+  let now = now_secs();
+  Job {
+    id: format!("job-{}-{}", now, uuid::Uuid::new_v4().to_string().split('-').next().unwrap_or("0")),
+    agent_id: agent_id.to_string(),
+    provider_id: provider_id.to_string(),
+    status: JobStatus::Queued,
+    current: 0,
+    tasks,
+    error: None,
+    created_at: now,
+    updated_at: now,
+  }
+}
+
+fn checkpoint(app: &AppHandle, root: &Path, job: &mut Job) {
+  job.updated_at = now_secs();
+  if let Err(e) = save(root, job) {
+    eprintln!("job_checkpoint_failed: {e}");
+  }
+  let _ = app.emit(
+    "job_progress",
+    serde_json::json!({
+      "jobId": job.id,
+      "status": job.status,
+      "current": job.current,
+      "total": job.tasks.len(),
+      "task": job.tasks.get(job.current),
+      "error": job.error,
+    }),
+  );
+}
+
+/// 依次执行任务中尚未完成的章节，每完成一章写一次检查点。
+pub async fn run(app: AppHandle, root: PathBuf, job_id: String, control: Arc<JobControl>) {
+  let mut job = match load(&root, &job_id) {
+    Ok(v) => v,
+    Err(e) => {
+      let _ = app.emit("job_progress", serde_json::json!({ "jobId": job_id, "status": "failed", "error": e }));
+      release(&app, &job_id);
+      return;
+    }
+  };
+
+  let (provider, agent) = match resolve_provider_and_agent(&app, &root, &job) {
+    Ok(v) => v,
+    Err(e) => {
+      job.status = JobStatus::Failed;
+      job.error = Some(e);
+      checkpoint(&app, &root, &mut job);
+      release(&app, &job_id);
+      return;
+    }
+  };
+  let agent_system = agent.as_ref().map(|a| a.system_prompt.clone()).unwrap_or_default();
+  let agent_temp = agent.as_ref().map(|a| a.temperature);
+  let agent_max = agent.as_ref().map(|a| a.max_tokens);
+  let word_target = project_settings::effective(&app, Some(&root), &Default::default())
+    .map(|e| chapter_split::word_target(agent.as_ref().map(|a| a.chapter_word_target), &e.chapter_word_target))
+    .unwrap_or(0);
+  let client = reqwest::Client::new();
+
+  job.status = JobStatus::Running;
+  job.error = None;
+  checkpoint(&app, &root, &mut job);
+
+  while job.current < job.tasks.len() {
+    match control.signal() {
+      SIGNAL_PAUSE => {
+        job.status = JobStatus::Paused;
+        checkpoint(&app, &root, &mut job);
+        release(&app, &job_id);
+        return;
+      }
+      SIGNAL_CANCEL => {
+        job.status = JobStatus::Cancelled;
+        for t in job.tasks.iter_mut().skip(job.current) {
+          t.status = TaskStatus::Cancelled;
+        }
+        checkpoint(&app, &root, &mut job);
+        release(&app, &job_id);
+        return;
+      }
+      _ => {}
+    }
+
+    let idx = job.current;
+    job.tasks[idx].status = TaskStatus::Running;
+    job.tasks[idx].error = None;
+    job.tasks[idx].started_at = now_secs();
+    checkpoint(&app, &root, &mut job);
+
+    let messages = vec![ChatMessage {
+      role: "user".to_string(),
+      content: job.tasks[idx].prompt.clone(),
+    }];
+    let mut runtime = AgentRuntime::new(root.clone());
+    commands::attach_mcp_tools(&app, &mut runtime).await;
+    let result = runtime
+      .run_react(messages, agent_system.clone(), |msgs| {
+        let provider = provider.clone();
+        let client = client.clone();
+        let app = app.clone();
+        async move { commands::call_model(&app, &client, &provider, msgs, agent_temp, agent_max).await }
+      })
+      .await;
+
+    let task = &mut job.tasks[idx];
+    task.finished_at = now_secs();
+    match result {
+      Ok((text, _perf)) => {
+        // 超出目标字数的章节只生成分章提案，留在收件箱等待审阅
+        chapter_split::propose_for_written(&root, runtime.written_files(), word_target, &job_id);
+        task.status = TaskStatus::Done;
+        task.result = text;
+        job.current += 1;
+        checkpoint(&app, &root, &mut job);
+      }
+      Err(e) => {
+        task.status = TaskStatus::Failed;
+        task.error = Some(e.clone());
+        job.status = JobStatus::Failed;
+        job.error = Some(e);
+        checkpoint(&app, &root, &mut job);
+        release(&app, &job_id);
+        return;
+      }
+    }
+  }
+
+  job.status = JobStatus::Completed;
+  checkpoint(&app, &root, &mut job);
+  release(&app, &job_id);
+}
+
+fn resolve_provider_and_agent(
+  app: &AppHandle,
+  root: &Path,
+  job: &Job,
+) -> Result<(app_settings::ModelProvider, Option<agents::Agent>), String> {
+  let settings = app_settings::load(app)?;
+  let provider = settings
+    .providers
+    .iter()
+    .find(|p| p.id == job.provider_id)
+    .cloned()
+    .ok_or_else(|| format!("provider not found: {}", job.provider_id))?;
+  let agents_list = workspace_agents::load(app, Some(root)).unwrap_or_else(|_| agents::default_agents());
+  let mut agent = agents_list.iter().find(|a| a.id == job.agent_id).cloned();
+  if let Some(a) = agent.as_mut() {
+    agents::flatten(app, a, &agents_list)?;
+  }
+  Ok((provider, agent))
+}
+
+fn release(app: &AppHandle, job_id: &str) {
+  let state = app.state::<AppState>();
+  if let Ok(mut jobs) = state.jobs.lock() {
+    jobs.remove(job_id);
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::TempDir;
+
+  #[test]
+  fn build_draft_tasks_uses_spec_range() {
+    let mut spec = spec_kit::StorySpec::default();
+    let first = spec.chapters[0].clone();
+    for i in 2..=5 {
+      let mut ch = first.clone();
+      ch.id = format!("chapter-{i}");
+      ch.title = format!("第{i}章");
+      spec.chapters.push(ch);
+    }
+    let tasks = build_draft_tasks(&spec, 2, 4, "保持悬念").unwrap();
+    assert_eq!(tasks.len(), 3);
+    assert_eq!(tasks[0].chapter, 2);
+    assert_eq!(tasks[2].file_path, "stories/chapter-004.txt");
+    assert!(tasks[0].prompt.contains("保持悬念"));
+    assert!(build_draft_tasks(&spec, 4, 9, "").is_err());
+    assert!(build_draft_tasks(&spec, 0, 1, "").is_err());
+  }
+
+  #[test]
+  fn recover_interrupted_pauses_running_jobs() {
+    let root = TempDir::new("jobs");
+    let spec = spec_kit::StorySpec::default();
+    let mut job = new_job("general", "openai", build_draft_tasks(&spec, 1, 1, "").unwrap());
+    job.status = JobStatus::Running;
+    job.tasks[0].status = TaskStatus::Running;
+    save(&root, &job).unwrap();
+
+    assert_eq!(recover_interrupted(&root, &[]).unwrap(), 1);
+    let reloaded = load(&root, &job.id).unwrap();
+    assert_eq!(reloaded.status, JobStatus::Paused);
+    assert_eq!(reloaded.tasks[0].status, TaskStatus::Pending);
+  }
+}
@@ -3,22 +3,43 @@
 mod commands;
 mod ai_types;
 mod agent_system;
+mod agent_history;
 mod app_data;
 mod app_settings;
 mod agents;
 mod chat_history;
 mod branding;
+mod bundle;
 mod secrets;
 mod state;
 mod modification_types;
 mod ai_response_parser;
+mod change_sets;
+mod chapter_split;
+mod path_sandbox;
+mod project_settings;
+mod schema;
+mod unified_diff;
+mod workspace_agents;
 mod spec_kit;
 mod spec_kit_export;
 mod skills;
 mod mcp;
 mod book_split;
+mod jobs;
+#[cfg(test)]
+mod test_util;
 
 fn main() {
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  if args.first().map(|a| a.as_str()) == Some("mcp-serve") {
+    if let Err(e) = mcp::server::run_from_args(&args[1..]) {
+      eprintln!("mcp-serve: {e}");
+      std::process::exit(1);
+    }
+    return;
+  }
+
   tauri::Builder::default()
     .plugin(tauri_plugin_dialog::init())
     .manage(state::AppState::default())
@@ -36,12 +57,29 @@ fn main() {
       commands::rename_entry,
       commands::get_app_settings,
       commands::set_app_settings,
+      commands::get_data_dir_info,
+      commands::get_effective_settings,
+      commands::get_project_settings,
+      commands::set_project_settings,
       commands::get_api_key_status,
       commands::set_api_key,
+      commands::delete_api_key,
+      commands::list_api_keys,
+      commands::set_api_key_policy,
+      commands::get_secrets_status,
+      commands::unlock_secrets,
       commands::get_agents,
       commands::set_agents,
       commands::export_agents,
       commands::import_agents,
+      commands::list_agent_versions,
+      commands::diff_agent_versions,
+      commands::restore_agent_version,
+      commands::get_agent_prompt,
+      commands::get_prompt_fragments,
+      commands::set_prompt_fragments,
+      commands::export_bundle,
+      commands::import_bundle,
       commands::save_chat_session,
       commands::list_chat_sessions,
       commands::get_chat_session,
@@ -62,8 +100,36 @@ fn main() {
       commands::get_skill_categories,
       commands::get_skills_by_category,
       commands::apply_skill,
+      commands::run_skill,
+      commands::create_skill,
+      commands::update_skill,
+      commands::delete_skill,
+      commands::set_skill_enabled,
       commands::book_analyze,
-      commands::book_extract_techniques
+      commands::book_extract_techniques,
+      commands::job_create_draft,
+      commands::job_list,
+      commands::job_get,
+      commands::job_pause,
+      commands::job_resume,
+      commands::job_cancel,
+      commands::mcp_list_servers,
+      commands::mcp_add_server,
+      commands::mcp_update_server,
+      commands::mcp_set_server_enabled,
+      commands::mcp_remove_server,
+      commands::mcp_restart_server,
+      commands::mcp_get_status,
+      commands::mcp_list_context,
+      commands::mcp_resolve_context,
+      commands::apply_change_set,
+      commands::revert_change_set,
+      commands::list_change_sets,
+      commands::get_change_set,
+      commands::accept_change_set,
+      commands::reject_change_set,
+      commands::export_change_set_patch,
+      commands::import_change_set_patch
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
@@ -0,0 +1,524 @@
+use super::{McpPrompt, McpPromptArgument, McpResource, McpServer, McpTool};
+use crate::branding;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const INIT_TIMEOUT: Duration = Duration::from_secs(20);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+const STDERR_TAIL_BYTES: usize = 2048;
+
+struct ClientIo {
+    child: Child,
+    stdin: ChildStdin,
+    rx: Receiver<Value>,
+    next_id: u64,
+}
+
+impl Drop for ClientIo {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 通过 stdio 与 MCP Server 通信的 JSON-RPC 客户端。
+///
+/// 消息按行分隔；stdout 上无法解析为 JSON 的行（部分 server 会打印启动横幅）会被忽略。
+pub struct McpClient {
+    server_id: String,
+    io: Mutex<ClientIo>,
+    stderr_tail: Arc<Mutex<String>>,
+    server_info: Value,
+    capabilities: Value,
+}
+
+impl McpClient {
+    /// 启动 server 进程并完成 `initialize` 握手。
+    pub fn connect(server: &McpServer) -> Result<Self, String> {
+        if server.command.trim().is_empty() {
+            return Err(format!("mcp server {} has empty command", server.id));
+        }
+        let mut child = Command::new(&server.command)
+            .args(&server.args)
+            .envs(&server.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("spawn mcp server {} failed: {e}", server.id))?;
+
+        let stdin = child.stdin.take().ok_or("mcp stdin unavailable")?;
+        let stdout = child.stdout.take().ok_or("mcp stdout unavailable")?;
+        let stderr = child.stderr.take().ok_or("mcp stderr unavailable")?;
+
+        let (tx, rx) = mpsc::channel::<Value>();
+        std::thread::spawn(move || {
+            let reader = BufReader::new(stdout);
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if let Ok(v) = serde_json::from_str::<Value>(line) {
+                    if tx.send(v).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let stderr_tail = Arc::new(Mutex::new(String::new()));
+        let tail = stderr_tail.clone();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(stderr);
+            let mut buf = [0u8; 512];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                if let Ok(mut t) = tail.lock() {
+                    t.push_str(&String::from_utf8_lossy(&buf[..n]));
+                    if t.len() > STDERR_TAIL_BYTES {
+                        let mut cut = t.len() - STDERR_TAIL_BYTES;
+                        while !t.is_char_boundary(cut) {
+                            cut += 1;
+                        }
+                        t.drain(..cut);
+                    }
+                }
+            }
+        });
+
+        let mut client = Self {
+            server_id: server.id.clone(),
+            io: Mutex::new(ClientIo {
+                child,
+                stdin,
+                rx,
+                next_id: 1,
+            }),
+            stderr_tail,
+            server_info: Value::Null,
+            capabilities: Value::Null,
+        };
+
+        let init = client.request_with_timeout(
+            "initialize",
+            serde_json::json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": branding::DISPLAY_NAME, "version": env!("CARGO_PKG_VERSION") }
+            }),
+            INIT_TIMEOUT,
+        )?;
+        client.server_info = init.get("serverInfo").cloned().unwrap_or(Value::Null);
+        client.capabilities = init.get("capabilities").cloned().unwrap_or(Value::Null);
+        client.notify("notifications/initialized", serde_json::json!({}))?;
+        Ok(client)
+    }
+
+    pub fn server_id(&self) -> &str {
+        &self.server_id
+    }
+
+    #[allow(dead_code)]
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    /// server 在 initialize 中声明的能力，如 `tools`、`resources`、`prompts`。
+    pub fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.get(name).is_some()
+    }
+
+    /// 最近的 stderr 输出，便于排查 server 启动或调用失败。
+    pub fn stderr_tail(&self) -> String {
+        self.stderr_tail.lock().map(|t| t.trim().to_string()).unwrap_or_default()
+    }
+
+    pub fn is_alive(&self) -> bool {
+        match self.io.lock() {
+            Ok(mut io) => matches!(io.child.try_wait(), Ok(None)),
+            Err(_) => false,
+        }
+    }
+
+    pub fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        self.request_with_timeout(method, params, REQUEST_TIMEOUT)
+    }
+
+    fn request_with_timeout(&self, method: &str, params: Value, timeout: Duration) -> Result<Value, String> {
+        let mut io = self.io.lock().map_err(|_| "mcp client lock poisoned")?;
+        let id = io.next_id;
+        io.next_id += 1;
+        let msg = serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        write_message(&mut io.stdin, &msg).map_err(|e| self.with_stderr(format!("mcp write failed: {e}")))?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let incoming = match io.rx.recv_timeout(remaining) {
+                Ok(v) => v,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("mcp {method} timed out after {}s", timeout.as_secs()))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(self.with_stderr(format!("mcp server {} closed the connection", self.server_id)))
+                }
+            };
+
+            // server 发起的请求（如 ping）：按规范回复，其余一律 method not found
+            if incoming.get("method").is_some() {
+                if let Some(req_id) = incoming.get("id").cloned() {
+                    let reply = if incoming["method"] == "ping" {
+                        serde_json::json!({ "jsonrpc": "2.0", "id": req_id, "result": {} })
+                    } else {
+                        serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": req_id,
+                            "error": { "code": -32601, "message": "method not found" }
+                        })
+                    };
+                    let _ = write_message(&mut io.stdin, &reply);
+                }
+                continue;
+            }
+
+            if incoming.get("id").and_then(|v| v.as_u64()) != Some(id) {
+                continue;
+            }
+            if let Some(err) = incoming.get("error") {
+                let message = err.get("message").and_then(|v| v.as_str()).unwrap_or("unknown error");
+                return Err(format!("mcp {method} failed: {message}"));
+            }
+            return Ok(incoming.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        let mut io = self.io.lock().map_err(|_| "mcp client lock poisoned")?;
+        let msg = serde_json::json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut io.stdin, &msg).map_err(|e| format!("mcp write failed: {e}"))
+    }
+
+    /// 调用 `tools/list`，自动翻页。
+    pub fn list_tools(&self) -> Result<Vec<McpTool>, String> {
+        let mut out = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => serde_json::json!({ "cursor": c }),
+                None => serde_json::json!({}),
+            };
+            let result = self.request("tools/list", params)?;
+            for t in result.get("tools").and_then(|v| v.as_array()).into_iter().flatten() {
+                let Some(name) = t.get("name").and_then(|v| v.as_str()) else { continue };
+                out.push(McpTool {
+                    name: name.to_string(),
+                    description: t.get("description").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    input_schema: t.get("inputSchema").cloned().unwrap_or_else(|| serde_json::json!({})),
+                });
+            }
+            cursor = result.get("nextCursor").and_then(|v| v.as_str()).map(|s| s.to_string());
+            if cursor.is_none() {
+                return Ok(out);
+            }
+        }
+    }
+
+    /// 调用 `resources/list`，自动翻页。
+    pub fn list_resources(&self) -> Result<Vec<McpResource>, String> {
+        let mut out = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => serde_json::json!({ "cursor": c }),
+                None => serde_json::json!({}),
+            };
+            let result = self.request("resources/list", params)?;
+            for r in result.get("resources").and_then(|v| v.as_array()).into_iter().flatten() {
+                let Some(uri) = r.get("uri").and_then(|v| v.as_str()) else { continue };
+                let text = |key: &str| r.get(key).and_then(|v| v.as_str()).unwrap_or("").to_string();
+                out.push(McpResource {
+                    uri: uri.to_string(),
+                    name: text("name"),
+                    description: text("description"),
+                    mime_type: text("mimeType"),
+                });
+            }
+            cursor = result.get("nextCursor").and_then(|v| v.as_str()).map(|s| s.to_string());
+            if cursor.is_none() {
+                return Ok(out);
+            }
+        }
+    }
+
+    /// 调用 `resources/read`，返回各内容片段拼接后的文本；二进制内容以占位说明代替。
+    pub fn read_resource(&self, uri: &str) -> Result<String, String> {
+        let result = self.request("resources/read", serde_json::json!({ "uri": uri }))?;
+        let mut parts: Vec<String> = Vec::new();
+        for c in result.get("contents").and_then(|v| v.as_array()).into_iter().flatten() {
+            match c.get("text").and_then(|v| v.as_str()) {
+                Some(t) => parts.push(t.to_string()),
+                None => parts.push(format!(
+                    "[binary resource {} ({})]",
+                    c.get("uri").and_then(|v| v.as_str()).unwrap_or(uri),
+                    c.get("mimeType").and_then(|v| v.as_str()).unwrap_or("application/octet-stream")
+                )),
+            }
+        }
+        Ok(parts.join("\n"))
+    }
+
+    /// 调用 `prompts/list`，自动翻页。
+    pub fn list_prompts(&self) -> Result<Vec<McpPrompt>, String> {
+        let mut out = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => serde_json::json!({ "cursor": c }),
+                None => serde_json::json!({}),
+            };
+            let result = self.request("prompts/list", params)?;
+            for p in result.get("prompts").and_then(|v| v.as_array()).into_iter().flatten() {
+                let Some(name) = p.get("name").and_then(|v| v.as_str()) else { continue };
+                let arguments = p
+                    .get("arguments")
+                    .and_then(|v| v.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|a| {
+                        Some(McpPromptArgument {
+                            name: a.get("name")?.as_str()?.to_string(),
+                            description: a.get("description").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                            required: a.get("required").and_then(|v| v.as_bool()).unwrap_or(false),
+                        })
+                    })
+                    .collect();
+                out.push(McpPrompt {
+                    name: name.to_string(),
+                    description: p.get("description").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    arguments,
+                });
+            }
+            cursor = result.get("nextCursor").and_then(|v| v.as_str()).map(|s| s.to_string());
+            if cursor.is_none() {
+                return Ok(out);
+            }
+        }
+    }
+
+    /// 调用 `prompts/get`，把返回的消息按顺序拼接成一段文本。
+    pub fn get_prompt(&self, name: &str, arguments: &HashMap<String, String>) -> Result<String, String> {
+        let result = self.request("prompts/get", serde_json::json!({ "name": name, "arguments": arguments }))?;
+        let mut parts: Vec<String> = Vec::new();
+        for m in result.get("messages").and_then(|v| v.as_array()).into_iter().flatten() {
+            let content = &m["content"];
+            let text = match content.get("type").and_then(|v| v.as_str()) {
+                Some("text") => content.get("text").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                Some("resource") => content_text(&serde_json::json!({ "content": [content] })),
+                Some(other) => format!("[{other} content]"),
+                None => String::new(),
+            };
+            if !text.is_empty() {
+                parts.push(text);
+            }
+        }
+        Ok(parts.join("\n\n"))
+    }
+
+    /// 调用 `tools/call`；`isError` 为真时返回 Err，内容为 server 给出的文本。
+    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+        let arguments = if arguments.is_object() { arguments } else { serde_json::json!({}) };
+        let result = self.request("tools/call", serde_json::json!({ "name": name, "arguments": arguments }))?;
+        let text = content_text(&result);
+        if result.get("isError").and_then(|v| v.as_bool()).unwrap_or(false) {
+            return Err(if text.is_empty() { format!("mcp tool {name} failed") } else { text });
+        }
+        Ok(serde_json::json!({
+            "text": text,
+            "structured": result.get("structuredContent").cloned().unwrap_or(Value::Null)
+        }))
+    }
+
+    fn with_stderr(&self, msg: String) -> String {
+        let tail = self.stderr_tail();
+        if tail.is_empty() {
+            msg
+        } else {
+            format!("{msg}; stderr: {tail}")
+        }
+    }
+}
+
+fn write_message(stdin: &mut ChildStdin, msg: &Value) -> std::io::Result<()> {
+    let mut line = msg.to_string();
+    line.push('\n');
+    stdin.write_all(line.as_bytes())?;
+    stdin.flush()
+}
+
+/// 把 MCP `content` 数组中的文本片段拼接起来，非文本片段以占位说明代替。
+pub fn content_text(result: &Value) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for c in result.get("content").and_then(|v| v.as_array()).into_iter().flatten() {
+        match c.get("type").and_then(|v| v.as_str()) {
+            Some("text") => parts.push(c.get("text").and_then(|v| v.as_str()).unwrap_or("").to_string()),
+            Some("resource") => {
+                let r = &c["resource"];
+                if let Some(t) = r.get("text").and_then(|v| v.as_str()) {
+                    parts.push(t.to_string());
+                } else {
+                    parts.push(format!("[resource {}]", r.get("uri").and_then(|v| v.as_str()).unwrap_or("")));
+                }
+            }
+            Some(other) => parts.push(format!("[{other} content]")),
+            None => {}
+        }
+    }
+    parts.join("\n")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const FIXTURE_ENV: &str = "NOVEL_IDE_MCP_FIXTURE";
+
+    /// 以当前测试二进制作为 MCP fixture server 启动。
+    pub(crate) fn fixture_server(id: &str) -> McpServer {
+        let exe = std::env::current_exe().unwrap();
+        let mut env = HashMap::new();
+        env.insert(FIXTURE_ENV.to_string(), "1".to_string());
+        McpServer {
+            id: id.to_string(),
+            name: "fixture".to_string(),
+            command: exe.to_string_lossy().to_string(),
+            args: vec![
+                "--exact".to_string(),
+                "mcp::client::tests::fixture_server_main".to_string(),
+                "--ignored".to_string(),
+                "--nocapture".to_string(),
+            ],
+            env,
+            enabled: true,
+        }
+    }
+
+    fn fixture_reply(req: &Value) -> Option<Value> {
+        let id = req.get("id")?.clone();
+        let params = req.get("params").cloned().unwrap_or(Value::Null);
+        let result = match req.get("method").and_then(|v| v.as_str())? {
+            "initialize" => serde_json::json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {}, "resources": {}, "prompts": {} },
+                "serverInfo": { "name": "fixture", "version": "0.0.1" }
+            }),
+            "tools/list" => serde_json::json!({
+                "tools": [
+                    {
+                        "name": "echo",
+                        "description": "Echo back the given text",
+                        "inputSchema": { "type": "object", "properties": { "text": { "type": "string" } } }
+                    },
+                    { "name": "fail", "description": "Always fails", "inputSchema": { "type": "object" } }
+                ]
+            }),
+            "resources/list" => serde_json::json!({
+                "resources": [{ "uri": "fixture://notes", "name": "notes", "mimeType": "text/plain" }]
+            }),
+            "resources/read" => serde_json::json!({
+                "contents": [{ "uri": params["uri"], "mimeType": "text/plain", "text": "世界观：灵气复苏" }]
+            }),
+            "prompts/list" => serde_json::json!({
+                "prompts": [{
+                    "name": "polish",
+                    "description": "Polish a paragraph",
+                    "arguments": [{ "name": "style", "required": true }]
+                }]
+            }),
+            "prompts/get" => serde_json::json!({
+                "messages": [{
+                    "role": "user",
+                    "content": { "type": "text", "text": format!("按{}风格润色", params["arguments"]["style"].as_str().unwrap_or("")) }
+                }]
+            }),
+            "tools/call" => match params["name"].as_str() {
+                Some("echo") => serde_json::json!({
+                    "content": [{ "type": "text", "text": params["arguments"]["text"].as_str().unwrap_or("") }]
+                }),
+                _ => serde_json::json!({ "content": [{ "type": "text", "text": "boom" }], "isError": true }),
+            },
+            _ => {
+                return Some(serde_json::json!({
+                    "jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": "method not found" }
+                }))
+            }
+        };
+        Some(serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    #[test]
+    #[ignore]
+    fn fixture_server_main() {
+        if std::env::var(FIXTURE_ENV).is_err() {
+            return;
+        }
+        let stdin = std::io::stdin();
+        let mut stdout = std::io::stdout();
+        // libtest 会先输出不带换行的 "test ... "，补一个换行让后续 JSON 独占一行
+        let _ = writeln!(stdout);
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else { break };
+            let Ok(req) = serde_json::from_str::<Value>(&line) else { continue };
+            if let Some(reply) = fixture_reply(&req) {
+                let _ = writeln!(stdout, "{reply}");
+                let _ = stdout.flush();
+            }
+        }
+    }
+
+    #[test]
+    fn connects_lists_and_calls_tools() {
+        let client = McpClient::connect(&fixture_server("fixture")).unwrap();
+        assert!(client.has_capability("tools"));
+        let tools = client.list_tools().unwrap();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].name, "echo");
+
+        let out = client.call_tool("echo", serde_json::json!({ "text": "你好" })).unwrap();
+        assert_eq!(out["text"], "你好");
+        assert_eq!(client.call_tool("fail", serde_json::json!({})).unwrap_err(), "boom");
+        assert!(client.request("nope", serde_json::json!({})).unwrap_err().contains("method not found"));
+    }
+
+    #[test]
+    fn reads_resources_and_prompts() {
+        let client = McpClient::connect(&fixture_server("fixture")).unwrap();
+        let resources = client.list_resources().unwrap();
+        assert_eq!(resources[0].uri, "fixture://notes");
+        assert_eq!(client.read_resource("fixture://notes").unwrap(), "世界观：灵气复苏");
+
+        let prompts = client.list_prompts().unwrap();
+        assert_eq!(prompts[0].name, "polish");
+        assert!(prompts[0].arguments[0].required);
+        let mut args = HashMap::new();
+        args.insert("style".to_string(), "古风".to_string());
+        assert_eq!(client.get_prompt("polish", &args).unwrap(), "按古风风格润色");
+    }
+
+    #[test]
+    fn spawn_failure_is_reported() {
+        let mut server = fixture_server("missing");
+        server.command = "novel-ide-definitely-missing-binary".to_string();
+        assert!(McpClient::connect(&server).is_err());
+    }
+}
@@ -1,20 +1,34 @@
+pub mod client;
+pub mod server;
+
+use crate::app_data;
+use crate::schema::{self, Schema};
+use client::McpClient;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 /// MCP Server 配置
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpServer {
     pub id: String,
     pub name: String,
     pub command: String,
+    #[serde(default)]
     pub args: Vec<String>,
+    #[serde(default)]
     pub env: HashMap<String, String>,
+    #[serde(default = "default_enabled")]
     pub enabled: bool,
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
 /// MCP Tool 定义
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpTool {
     pub name: String,
@@ -23,7 +37,6 @@ pub struct McpTool {
 }
 
 /// MCP Resource 定义
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpResource {
     pub uri: String,
@@ -32,19 +45,49 @@ pub struct McpResource {
     pub mime_type: String,
 }
 
+/// MCP Prompt 定义
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpPrompt {
+    pub name: String,
+    pub description: String,
+    pub arguments: Vec<McpPromptArgument>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpPromptArgument {
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+}
+
+/// 对话中引用的 MCP 上下文：资源内容或 prompt 展开结果会拼入 system 提示。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum McpContextRef {
+    Resource {
+        server_id: String,
+        uri: String,
+    },
+    Prompt {
+        server_id: String,
+        name: String,
+        #[serde(default)]
+        arguments: HashMap<String, String>,
+    },
+}
+
 /// MCP Server 运行时状态
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpServerStatus {
     pub server_id: String,
     pub connected: bool,
     pub tools: Vec<McpTool>,
     pub resources: Vec<McpResource>,
+    pub prompts: Vec<McpPrompt>,
     pub error: Option<String>,
 }
 
 /// 预配置的 MCP Servers
-#[allow(dead_code)]
 pub fn default_mcp_servers() -> Vec<McpServer> {
     vec![
         // 可以添加默认的 MCP 服务器配置
@@ -52,6 +95,60 @@ pub fn default_mcp_servers() -> Vec<McpServer> {
     ]
 }
 
+/// 版本 0：顶层直接是 server 数组。
+pub const SCHEMA: Schema = Schema {
+    name: "mcp servers",
+    current: 1,
+    detect: schema::detect_array,
+    migrations: &[wrap_servers],
+};
+
+fn wrap_servers(value: serde_json::Value) -> Result<serde_json::Value, String> {
+    schema::wrap_array(value, "servers")
+}
+
+#[derive(Serialize, Deserialize)]
+struct ServersFile {
+    servers: Vec<McpServer>,
+}
+
+/// 读取已保存的 MCP Server 列表；文件不存在时使用预配置列表。
+pub fn load(app: &tauri::AppHandle) -> Result<Vec<McpServer>, String> {
+    let path = servers_path(app)?;
+    let Some(value) = schema::read(&path, &SCHEMA)? else {
+        return Ok(default_mcp_servers());
+    };
+    let file: ServersFile = serde_json::from_value(value).map_err(|e| format!("parse mcp servers failed: {e}"))?;
+    Ok(file.servers)
+}
+
+pub fn save(app: &tauri::AppHandle, servers: &[McpServer]) -> Result<(), String> {
+    let path = servers_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create mcp dir failed: {e}"))?;
+    }
+    let raw = SCHEMA.to_string(&ServersFile { servers: servers.to_vec() })?;
+    fs::write(path, raw).map_err(|e| format!("write mcp servers failed: {e}"))
+}
+
+fn servers_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data::data_file_path(app, "mcp_servers.json")
+}
+
+/// 校验新增/编辑的配置：id 与 command 不能为空，id 不能与其它 server 重复。
+pub fn validate_server(server: &McpServer, others: &[McpServer]) -> Result<(), String> {
+    if server.id.trim().is_empty() {
+        return Err("mcp server id is empty".to_string());
+    }
+    if server.command.trim().is_empty() {
+        return Err("mcp server command is empty".to_string());
+    }
+    if others.iter().any(|s| s.id == server.id) {
+        return Err(format!("mcp server {} already exists", server.id));
+    }
+    Ok(())
+}
+
 impl McpServer {
     #[allow(dead_code)]
     pub fn new(id: &str, name: &str, command: &str) -> Self {
@@ -65,3 +162,258 @@ impl McpServer {
         }
     }
 }
+
+/// 已连接的 MCP Server 及其工具列表
+#[derive(Clone)]
+pub struct McpConnection {
+    pub client: Arc<McpClient>,
+    pub tools: Vec<McpTool>,
+    pub resources: Vec<McpResource>,
+    pub prompts: Vec<McpPrompt>,
+}
+
+/// 管理 MCP Server 连接：首次使用时启动进程，进程退出后下次使用时重连。
+#[derive(Default)]
+pub struct McpHub {
+    connections: HashMap<String, McpConnection>,
+    errors: HashMap<String, String>,
+}
+
+impl McpHub {
+    /// 仍存活的已有连接；进程已退出的连接会被移除。
+    fn cached(&mut self, server_id: &str) -> Option<McpConnection> {
+        if let Some(conn) = self.connections.get(server_id) {
+            if conn.client.is_alive() {
+                return Some(conn.clone());
+            }
+            self.connections.remove(server_id);
+        }
+        None
+    }
+
+    /// 登记一次连接结果；其间已有别处连上同一 server 时沿用已有连接。
+    fn register(&mut self, server_id: &str, result: Result<McpConnection, String>) -> Result<McpConnection, String> {
+        match result {
+            Ok(conn) => {
+                self.errors.remove(server_id);
+                if let Some(existing) = self.cached(server_id) {
+                    return Ok(existing);
+                }
+                self.connections.insert(server_id.to_string(), conn.clone());
+                Ok(conn)
+            }
+            Err(e) => {
+                self.errors.insert(server_id.to_string(), e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    fn connect(server: &McpServer) -> Result<McpConnection, String> {
+        let client = McpClient::connect(server)?;
+        let tools = if client.has_capability("tools") {
+            client.list_tools()?
+        } else {
+            Vec::new()
+        };
+        let resources = if client.has_capability("resources") {
+            client.list_resources()?
+        } else {
+            Vec::new()
+        };
+        let prompts = if client.has_capability("prompts") {
+            client.list_prompts()?
+        } else {
+            Vec::new()
+        };
+        Ok(McpConnection {
+            client: Arc::new(client),
+            tools,
+            resources,
+            prompts,
+        })
+    }
+
+    /// 断开并结束某个 server 进程（配置变更、禁用、删除时调用）。
+    pub fn disconnect(&mut self, server_id: &str) {
+        self.connections.remove(server_id);
+        self.errors.remove(server_id);
+    }
+
+    /// 结束所有 server 进程，切换工作区时调用。
+    pub fn shutdown_all(&mut self) {
+        self.connections.clear();
+        self.errors.clear();
+    }
+
+    /// 当前状态；不会为了查询状态而启动 server。
+    pub fn status(&self, server_id: &str) -> McpServerStatus {
+        let conn = self.connections.get(server_id).filter(|c| c.client.is_alive());
+        let error = match (conn, self.errors.get(server_id)) {
+            (_, Some(e)) => Some(e.clone()),
+            (None, None) if self.connections.contains_key(server_id) => {
+                Some("mcp server process exited".to_string())
+            }
+            _ => None,
+        };
+        McpServerStatus {
+            server_id: server_id.to_string(),
+            connected: conn.is_some(),
+            tools: conn.map(|c| c.tools.clone()).unwrap_or_default(),
+            resources: conn.map(|c| c.resources.clone()).unwrap_or_default(),
+            prompts: conn.map(|c| c.prompts.clone()).unwrap_or_default(),
+            error,
+        }
+    }
+}
+
+/// 连接所有启用的 server；单个 server 失败不影响其它 server。
+///
+/// 只在查询与登记连接时持有锁，启动进程和 `initialize` / `tools/list` 握手在锁外进行，
+/// 避免一个慢启动的 server 阻塞其它对话、任务和状态查询。
+pub fn connect_enabled(hub: &Mutex<McpHub>, servers: &[McpServer]) -> Result<Vec<McpConnection>, String> {
+    let mut out = Vec::new();
+    let mut pending = Vec::new();
+    {
+        let mut hub = hub.lock().map_err(|_| "mcp lock poisoned")?;
+        for server in servers.iter().filter(|s| s.enabled) {
+            match hub.cached(&server.id) {
+                Some(conn) => out.push(conn),
+                None => pending.push(server),
+            }
+        }
+    }
+    for server in pending {
+        match connect_server(hub, server) {
+            Ok(conn) => out.push(conn),
+            Err(e) => eprintln!("mcp_connect_failed server={} err={}", server.id, e),
+        }
+    }
+    Ok(out)
+}
+
+/// 取得单个 server 的连接；与 [`connect_enabled`] 一样只在查询与登记时持有锁。
+pub fn connect_server(hub: &Mutex<McpHub>, server: &McpServer) -> Result<McpConnection, String> {
+    if let Some(conn) = hub.lock().map_err(|_| "mcp lock poisoned")?.cached(&server.id) {
+        return Ok(conn);
+    }
+    let result = McpHub::connect(server);
+    hub.lock().map_err(|_| "mcp lock poisoned")?.register(&server.id, result)
+}
+
+/// 按需连接 server，读取引用的资源 / 展开 prompt，拼成一段可放入 system 提示的文本。
+///
+/// `resources/read` 与 `prompts/get` 可能等待很久，调用时不持有锁。
+pub fn resolve_context(hub: &Mutex<McpHub>, servers: &[McpServer], refs: &[McpContextRef]) -> Result<String, String> {
+    let mut blocks: Vec<String> = Vec::new();
+    for r in refs {
+        let server_id = match r {
+            McpContextRef::Resource { server_id, .. } | McpContextRef::Prompt { server_id, .. } => server_id,
+        };
+        let server = servers
+            .iter()
+            .find(|s| &s.id == server_id && s.enabled)
+            .ok_or_else(|| format!("mcp server not found or disabled: {server_id}"))?;
+        let conn = connect_server(hub, server)?;
+        let block = match r {
+            McpContextRef::Resource { uri, .. } => {
+                let text = conn.client.read_resource(uri)?;
+                format!("【参考资料 {server_id} {uri}】\n{text}")
+            }
+            McpContextRef::Prompt { name, arguments, .. } => {
+                let text = conn.client.get_prompt(name, arguments)?;
+                format!("【技能 {server_id}/{name}】\n{text}")
+            }
+        };
+        blocks.push(block);
+    }
+    Ok(blocks.join("\n\n"))
+}
+
+/// 注册到 agent 工具表时使用的名字：`mcp__<server>__<tool>`
+pub fn namespaced_tool_name(server_id: &str, tool: &str) -> String {
+    let server = server_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect::<String>();
+    format!("mcp__{server}__{tool}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::tests::fixture_server;
+
+    #[test]
+    fn hub_reports_status_and_shuts_down() {
+        let shared = Mutex::new(McpHub::default());
+        let server = fixture_server("fixture");
+        assert!(!shared.lock().unwrap().status("fixture").connected);
+
+        connect_server(&shared, &server).unwrap();
+        let status = shared.lock().unwrap().status("fixture");
+        assert!(status.connected);
+        assert_eq!(status.tools.len(), 2);
+        assert_eq!(status.resources[0].uri, "fixture://notes");
+
+        let mut broken = fixture_server("broken");
+        broken.command = "novel-ide-definitely-missing-binary".to_string();
+        assert!(connect_server(&shared, &broken).is_err());
+        let status = shared.lock().unwrap().status("broken");
+        assert!(!status.connected);
+        assert!(status.error.unwrap().contains("spawn mcp server broken failed"));
+
+        let mut hub = shared.lock().unwrap();
+        hub.shutdown_all();
+        assert!(!hub.status("fixture").connected);
+        assert!(hub.status("broken").error.is_none());
+        drop(hub);
+
+        let conns = connect_enabled(&shared, &[server, broken]).unwrap();
+        assert_eq!(conns.len(), 1);
+        let hub = shared.into_inner().unwrap();
+        assert!(hub.status("fixture").connected);
+        assert!(hub.status("broken").error.is_some());
+    }
+
+    #[test]
+    fn resolves_resource_and_prompt_context() {
+        let hub = Mutex::new(McpHub::default());
+        let servers = vec![fixture_server("world")];
+        let refs: Vec<McpContextRef> = serde_json::from_value(serde_json::json!([
+            { "kind": "resource", "server_id": "world", "uri": "fixture://notes" },
+            { "kind": "prompt", "server_id": "world", "name": "polish", "arguments": { "style": "古风" } }
+        ]))
+        .unwrap();
+        let ctx = resolve_context(&hub, &servers, &refs).unwrap();
+        // 读取结束后不再持有锁
+        assert!(hub.try_lock().is_ok());
+        assert!(ctx.contains("【参考资料 world fixture://notes】\n世界观：灵气复苏"));
+        assert!(ctx.contains("【技能 world/polish】\n按古风风格润色"));
+
+        let missing = vec![McpContextRef::Resource {
+            server_id: "nope".to_string(),
+            uri: "x".to_string(),
+        }];
+        assert!(resolve_context(&hub, &servers, &missing).is_err());
+    }
+
+    #[test]
+    fn validate_server_rejects_duplicates_and_blanks() {
+        let existing = vec![McpServer::new("fs", "Files", "mcp-fs")];
+        assert!(validate_server(&McpServer::new("fs", "Files", "mcp-fs"), &existing).is_err());
+        assert!(validate_server(&McpServer::new(" ", "x", "cmd"), &[]).is_err());
+        assert!(validate_server(&McpServer::new("x", "x", ""), &[]).is_err());
+        assert!(validate_server(&McpServer::new("db", "DB", "mcp-db"), &existing).is_ok());
+    }
+
+    #[test]
+    fn loads_every_previous_version() {
+        let raw = include_str!("../../fixtures/schema/mcp_servers.v0.json");
+        let (value, from) = SCHEMA.migrate(serde_json::from_str(raw).unwrap()).unwrap();
+        assert_eq!(from, 0);
+        let file: ServersFile = serde_json::from_value(value).unwrap();
+        assert_eq!(file.servers[0].id, "notes");
+        assert_eq!(file.servers[0].args, vec!["notes-server.js".to_string()]);
+    }
+}
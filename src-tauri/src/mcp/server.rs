@@ -0,0 +1,328 @@
+use super::client::PROTOCOL_VERSION;
+use crate::branding;
+use crate::commands;
+use crate::path_sandbox::PathSandbox;
+use crate::spec_kit_export;
+use serde_json::Value;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const SEARCH_DIRS: [&str; 3] = ["concept", "outline", "stories"];
+const SEARCH_DEFAULT_LIMIT: usize = 50;
+
+/// 以 stdio MCP server 的形式对外暴露一个工作区。
+///
+/// 所有资源与工具都只能访问 `root` 之内的文件。
+pub struct WorkspaceServer {
+    root: PathBuf,
+    sandbox: PathSandbox,
+}
+
+impl WorkspaceServer {
+    pub fn new(root: &Path) -> Result<Self, String> {
+        let root = fs::canonicalize(root).map_err(|e| format!("invalid workspace: {e}"))?;
+        if !root.is_dir() {
+            return Err("workspace is not a directory".to_string());
+        }
+        let sandbox = PathSandbox::new(&root);
+        Ok(Self { root, sandbox })
+    }
+
+    /// 逐行读取 JSON-RPC 请求并写回响应，直到输入结束。
+    pub fn serve<R: BufRead, W: Write>(&self, input: R, mut output: W) -> Result<(), String> {
+        for line in input.lines() {
+            let line = line.map_err(|e| format!("read stdin failed: {e}"))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match serde_json::from_str::<Value>(&line) {
+                Ok(req) => self.handle(&req),
+                Err(e) => Some(error_reply(Value::Null, -32700, &format!("parse error: {e}"))),
+            };
+            if let Some(reply) = reply {
+                writeln!(output, "{reply}").map_err(|e| format!("write stdout failed: {e}"))?;
+                output.flush().map_err(|e| format!("write stdout failed: {e}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 处理单条消息；通知（没有 id）不需要响应。
+    pub fn handle(&self, req: &Value) -> Option<Value> {
+        let id = req.get("id").cloned()?;
+        let method = req.get("method").and_then(|v| v.as_str()).unwrap_or("");
+        let params = req.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => Ok(serde_json::json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "resources": {}, "tools": {} },
+                "serverInfo": { "name": branding::DISPLAY_NAME, "version": env!("CARGO_PKG_VERSION") }
+            })),
+            "ping" => Ok(serde_json::json!({})),
+            "resources/list" => self.list_resources(),
+            "resources/read" => self.read_resource(params.get("uri").and_then(|v| v.as_str()).unwrap_or("")),
+            "tools/list" => Ok(serde_json::json!({ "tools": tool_definitions() })),
+            "tools/call" => Ok(self.call_tool(
+                params.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                params.get("arguments").cloned().unwrap_or(Value::Null),
+            )),
+            _ => return Some(error_reply(id, -32601, &format!("method not found: {method}"))),
+        };
+        Some(match result {
+            Ok(v) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": v }),
+            Err(e) => error_reply(id, -32602, &e),
+        })
+    }
+
+    fn list_resources(&self) -> Result<Value, String> {
+        let mut resources = vec![
+            resource("novel://chapters", "chapters", "章节目录（按 chapters.json 顺序）", "application/json"),
+            resource("novel://characters", "characters", "人物列表", "application/json"),
+            resource("novel://outline", "outline", "大纲事件", "application/json"),
+            resource("novel://story-spec", "story_spec", "story_spec.json", "application/json"),
+        ];
+        for c in spec_kit_export::list_chapter_refs(&self.root)? {
+            resources.push(resource(
+                &format!("novel://chapter/{}", c.file_path),
+                &c.title,
+                &format!("章节正文：{}", c.file_path),
+                "text/plain",
+            ));
+        }
+        Ok(serde_json::json!({ "resources": resources }))
+    }
+
+    fn read_resource(&self, uri: &str) -> Result<Value, String> {
+        let (mime, text) = match uri {
+            "novel://chapters" => {
+                let list = spec_kit_export::list_chapter_refs(&self.root)?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, c)| serde_json::json!({ "order": i + 1, "title": c.title, "filePath": c.file_path }))
+                    .collect::<Vec<_>>();
+                ("application/json", serde_json::to_string_pretty(&list).unwrap_or_default())
+            }
+            "novel://characters" => ("application/json", self.read_workspace_file(".novel/.cache/characters.json")?),
+            "novel://outline" => ("application/json", self.read_workspace_file(".novel/.cache/outline.json")?),
+            "novel://story-spec" => ("application/json", self.read_workspace_file(".novel/.spec-kit/story_spec.json")?),
+            _ => match uri.strip_prefix("novel://chapter/") {
+                Some(rel) => ("text/plain", self.read_workspace_file(rel)?),
+                None => return Err(format!("unknown resource: {uri}")),
+            },
+        };
+        Ok(serde_json::json!({ "contents": [{ "uri": uri, "mimeType": mime, "text": text }] }))
+    }
+
+    fn read_workspace_file(&self, rel: &str) -> Result<String, String> {
+        let target = self.sandbox.resolve(rel)?;
+        fs::read_to_string(&target).map_err(|e| format!("read {rel} failed: {e}"))
+    }
+
+    fn call_tool(&self, name: &str, args: Value) -> Value {
+        let result = match name {
+            "search" => self.search(&args),
+            "validate_spec" => commands::validate_workspace_story_spec(&self.root)
+                .and_then(|r| serde_json::to_value(r).map_err(|e| format!("serialize report failed: {e}"))),
+            "export_markdown" => spec_kit_export::export_markdown(&self.root)
+                .map(|(path, bytes)| serde_json::json!({ "path": path, "bytes": bytes })),
+            "export_epub" => spec_kit_export::export_epub(&self.root)
+                .map(|(path, bytes)| serde_json::json!({ "path": path, "bytes": bytes })),
+            _ => Err(format!("unknown tool: {name}")),
+        };
+        match result {
+            Ok(v) => serde_json::json!({
+                "content": [{ "type": "text", "text": serde_json::to_string_pretty(&v).unwrap_or_default() }],
+                "structuredContent": v,
+                "isError": false
+            }),
+            Err(e) => serde_json::json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+        }
+    }
+
+    fn search(&self, args: &Value) -> Result<Value, String> {
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| "missing arguments.query".to_string())?;
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(SEARCH_DEFAULT_LIMIT);
+
+        let mut files = Vec::new();
+        for dir in SEARCH_DIRS {
+            collect_files(&self.root, &self.root.join(dir), &mut files);
+        }
+        files.sort();
+
+        let mut hits = Vec::new();
+        'outer: for rel in files {
+            let Ok(text) = fs::read_to_string(self.root.join(&rel)) else { continue };
+            for (i, line) in text.lines().enumerate() {
+                if line.to_lowercase().contains(&query) {
+                    hits.push(serde_json::json!({ "path": rel, "line": i + 1, "text": line.trim() }));
+                    if hits.len() >= limit {
+                        break 'outer;
+                    }
+                }
+            }
+        }
+        Ok(serde_json::json!({ "matches": hits }))
+    }
+}
+
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for e in entries.flatten() {
+        let Ok(ft) = e.file_type() else { continue };
+        let p = e.path();
+        // 不跟随符号链接，避免搜索到工作区之外
+        if ft.is_symlink() {
+            continue;
+        }
+        if ft.is_dir() {
+            collect_files(root, &p, out);
+        } else if let Ok(rel) = p.strip_prefix(root) {
+            out.push(rel.to_string_lossy().replace('\\', "/"));
+        }
+    }
+}
+
+fn resource(uri: &str, name: &str, description: &str, mime: &str) -> Value {
+    serde_json::json!({ "uri": uri, "name": name, "description": description, "mimeType": mime })
+}
+
+fn error_reply(id: Value, code: i64, message: &str) -> Value {
+    serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn tool_definitions() -> Vec<Value> {
+    vec![
+        serde_json::json!({
+            "name": "search",
+            "description": "在 concept/、outline/、stories/ 中全文搜索，返回命中的文件、行号与内容",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1 }
+                },
+                "required": ["query"]
+            }
+        }),
+        serde_json::json!({
+            "name": "validate_spec",
+            "description": "校验 story_spec.json 与大纲时间线，返回问题列表",
+            "inputSchema": { "type": "object", "properties": {} }
+        }),
+        serde_json::json!({
+            "name": "export_markdown",
+            "description": "按章节顺序导出 exports/book.md",
+            "inputSchema": { "type": "object", "properties": {} }
+        }),
+        serde_json::json!({
+            "name": "export_epub",
+            "description": "按章节顺序导出 exports/book.epub",
+            "inputSchema": { "type": "object", "properties": {} }
+        }),
+    ]
+}
+
+/// `novel-ide mcp-serve --workspace <dir>`：在 stdio 上运行工作区 MCP server。
+pub fn run_from_args(args: &[String]) -> Result<(), String> {
+    let mut workspace: Option<String> = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--workspace" | "-w" => {
+                workspace = args.get(i + 1).cloned();
+                i += 2;
+            }
+            other => {
+                if workspace.is_none() && !other.starts_with('-') {
+                    workspace = Some(other.to_string());
+                }
+                i += 1;
+            }
+        }
+    }
+    let workspace = workspace.ok_or_else(|| "usage: novel-ide mcp-serve --workspace <dir>".to_string())?;
+    let server = WorkspaceServer::new(Path::new(&workspace))?;
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    server.serve(stdin.lock(), stdout.lock())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn temp_workspace() -> TempDir {
+        let root = TempDir::new("mcp-server");
+        fs::create_dir_all(root.join("stories")).unwrap();
+        fs::create_dir_all(root.join(".novel/.settings")).unwrap();
+        fs::create_dir_all(root.join(".novel/.cache")).unwrap();
+        fs::write(root.join("stories/b.txt"), "第二章，林渊出关。").unwrap();
+        fs::write(root.join("stories/a.txt"), "第一章，林渊闭关。").unwrap();
+        fs::write(
+            root.join(".novel/.settings/chapters.json"),
+            r#"{"chapters":[{"filePath":"stories/b.txt","title":"出关","order":1},{"filePath":"stories/a.txt","title":"闭关","order":0},{"filePath":"../outside.txt","title":"x","order":2}]}"#,
+        )
+        .unwrap();
+        fs::write(root.join(".novel/.cache/characters.json"), r#"{"characters":[{"name":"林渊"}]}"#).unwrap();
+        root
+    }
+
+    fn call(server: &WorkspaceServer, method: &str, params: Value) -> Value {
+        server
+            .handle(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .unwrap()
+    }
+
+    #[test]
+    fn serves_resources_in_chapter_order() {
+        let root = temp_workspace();
+        let server = WorkspaceServer::new(&root).unwrap();
+
+        let list = call(&server, "resources/list", serde_json::json!({}));
+        let uris = list["result"]["resources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["uri"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        let chapters = uris.iter().filter(|u| u.starts_with("novel://chapter/")).collect::<Vec<_>>();
+        assert_eq!(chapters, ["novel://chapter/stories/a.txt", "novel://chapter/stories/b.txt"]);
+
+        let read = call(&server, "resources/read", serde_json::json!({ "uri": "novel://characters" }));
+        assert!(read["result"]["contents"][0]["text"].as_str().unwrap().contains("林渊"));
+
+        let escape = call(&server, "resources/read", serde_json::json!({ "uri": "novel://chapter/../secret.txt" }));
+        assert!(escape.get("error").is_some());
+    }
+
+    #[test]
+    fn serve_loop_answers_requests_and_skips_notifications() {
+        let root = temp_workspace();
+        let server = WorkspaceServer::new(&root).unwrap();
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"search","arguments":{"query":"出关"}}}"#,
+        ]
+        .join("\n");
+        let mut out = Vec::new();
+        server.serve(input.as_bytes(), &mut out).unwrap();
+        let lines = String::from_utf8(out).unwrap();
+        let replies = lines.lines().map(|l| serde_json::from_str::<Value>(l).unwrap()).collect::<Vec<_>>();
+        assert_eq!(replies.len(), 2);
+        assert!(replies[0]["result"]["capabilities"]["resources"].is_object());
+        let matches = &replies[1]["result"]["structuredContent"]["matches"];
+        assert_eq!(matches[0]["path"], "stories/b.txt");
+    }
+}
@@ -23,6 +23,19 @@ pub enum ModificationType {
     Add,
     Delete,
     Modify,
+    /// 整个文件级别的操作，每个文件最多一个，接受/拒绝方式与行级修改相同
+    #[serde(rename = "create_file")]
+    CreateFile,
+    #[serde(rename = "delete_file")]
+    DeleteFile,
+    #[serde(rename = "rename_file")]
+    RenameFile,
+}
+
+impl ModificationType {
+    pub fn is_file_level(&self) -> bool {
+        matches!(self, Self::CreateFile | Self::DeleteFile | Self::RenameFile)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +55,22 @@ pub struct FileModification {
     pub original_content: String,
     pub modifications: Vec<Modification>,
     pub status: FileModificationStatus,
+    #[serde(default)]
+    pub operation: FileOperation,
+    /// 重命名 / 移动的目标路径
+    #[serde(rename = "newPath", default, skip_serializing_if = "Option::is_none")]
+    pub new_path: Option<String>,
+}
+
+/// 文件级操作类型；`Edit` 为在已有文件上的行级修改
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileOperation {
+    #[default]
+    Edit,
+    Create,
+    Delete,
+    Rename,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,225 @@
+use crate::project_settings;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// 默认禁止 AI 访问的目录（相对工作区根目录）。
+pub const DEFAULT_PROTECTED_DIRS: [&str; 4] = [".git", ".novel/.undo", ".novel/.changesets", ".novel/.jobs"];
+
+/// AI 输出中出现的路径（file_edit、工具参数、MCP 等）统一经过这里解析。
+///
+/// 拒绝绝对路径、`..`、通过符号链接跳出工作区的路径，以及受保护目录下的路径。
+#[derive(Debug, Clone)]
+pub struct PathSandbox {
+  root: PathBuf,
+  protected: Vec<String>,
+}
+
+impl PathSandbox {
+  /// 受保护目录为内置目录加上工作区 project.json 中配置的 `protected_dirs`。
+  pub fn new(root: &Path) -> Self {
+    let mut protected: Vec<String> = DEFAULT_PROTECTED_DIRS.iter().map(|d| d.to_string()).collect();
+    let configured = project_settings::load(root).unwrap_or_else(|e| {
+      eprintln!("load protected dirs failed: {e}");
+      Default::default()
+    });
+    for dir in configured.protected_dirs.unwrap_or_default() {
+      let dir = normalize(&dir);
+      if !dir.is_empty() && !protected.contains(&dir) {
+        protected.push(dir);
+      }
+    }
+    Self {
+      root: fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()),
+      protected,
+    }
+  }
+
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  /// 把相对路径解析为工作区内的绝对路径；目标可以尚不存在。
+  pub fn resolve(&self, relative_path: &str) -> Result<PathBuf, String> {
+    let raw = relative_path.trim();
+    if raw.starts_with('/') || raw.starts_with('\\') {
+      return Err("absolute path is not allowed".to_string());
+    }
+    let rel = normalize(raw);
+    if rel.is_empty() {
+      return Err("empty path".to_string());
+    }
+    check_relative(&rel)?;
+    self.check_protected(&rel)?;
+
+    // 展开符号链接后确认目标仍在工作区内，且不在受保护目录下
+    let target = self.root.join(&rel);
+    let (_, inside) = self.real_path(&target)?;
+    self.check_protected(&inside.to_string_lossy())?;
+
+    // 返回的路径只展开父目录：对符号链接删除、重命名时作用于链接本身而不是它指向的文件
+    let (Some(parent), Some(name)) = (target.parent(), target.file_name()) else {
+      return Err("path escapes workspace".to_string());
+    };
+    let (real_parent, _) = self.real_path(parent)?;
+    Ok(real_parent.join(name))
+  }
+
+  /// 找到最深的已存在祖先并展开符号链接，返回展开后的路径及其相对工作区根目录的部分。
+  fn real_path(&self, target: &Path) -> Result<(PathBuf, PathBuf), String> {
+    let mut existing = target;
+    while fs::symlink_metadata(existing).is_err() {
+      existing = existing.parent().ok_or("path escapes workspace")?;
+    }
+    let real = fs::canonicalize(existing).map_err(|e| format!("resolve path failed: {e}"))?;
+    let inside = real.strip_prefix(&self.root).map_err(|_| "path escapes workspace".to_string())?;
+    let rest = target.strip_prefix(existing).map_err(|_| "path escapes workspace".to_string())?;
+    Ok((real.join(rest), inside.join(rest)))
+  }
+
+  fn check_protected(&self, rel: &str) -> Result<(), String> {
+    let rel = normalize(rel);
+    for dir in &self.protected {
+      let hit = if cfg!(windows) {
+        let (r, d) = (rel.to_lowercase(), dir.to_lowercase());
+        r == d || r.starts_with(&format!("{d}/"))
+      } else {
+        rel == *dir || rel.starts_with(&format!("{dir}/"))
+      };
+      if hit {
+        return Err(format!("path is in protected directory: {dir}"));
+      }
+    }
+    Ok(())
+  }
+}
+
+/// 统一分隔符并去掉 `./` 与多余的 `/`。
+fn normalize(path: &str) -> String {
+  path
+    .trim()
+    .replace('\\', "/")
+    .split('/')
+    .filter(|s| !s.is_empty() && *s != ".")
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+/// 用作文件名的 id（如故事模板 id）只能是单个普通路径分量。
+pub fn check_file_name(name: &str) -> Result<(), String> {
+  let mut components = Path::new(name).components();
+  let single = matches!(components.next(), Some(Component::Normal(c)) if c == name) && components.next().is_none();
+  if !single || name.contains(['/', '\\']) || check_relative(name).is_err() {
+    return Err(format!("invalid file name: {name}"));
+  }
+  Ok(())
+}
+
+fn check_relative(rel: &str) -> Result<(), String> {
+  // 在所有平台上都拒绝 `C:` 这类盘符写法
+  if rel.len() >= 2 && rel.as_bytes()[1] == b':' && rel.as_bytes()[0].is_ascii_alphabetic() {
+    return Err("absolute path is not allowed".to_string());
+  }
+  for c in Path::new(rel).components() {
+    match c {
+      Component::Normal(_) | Component::CurDir => {}
+      Component::ParentDir => return Err("parent directory (..) is not allowed".to_string()),
+      Component::RootDir | Component::Prefix(_) => return Err("absolute path is not allowed".to_string()),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::TempDir;
+
+  fn temp_workspace() -> TempDir {
+    let root = TempDir::new("sandbox");
+    fs::create_dir_all(root.join("stories")).unwrap();
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::write(root.join("stories/ch1.txt"), "正文").unwrap();
+    root
+  }
+
+  #[test]
+  fn resolves_paths_inside_workspace() {
+    let root = temp_workspace();
+    let sandbox = PathSandbox::new(&root);
+    let real_root = fs::canonicalize(&root).unwrap();
+    assert_eq!(sandbox.resolve("stories/ch1.txt").unwrap(), real_root.join("stories/ch1.txt"));
+    assert_eq!(sandbox.resolve("./stories\\new/ch2.txt").unwrap(), real_root.join("stories/new/ch2.txt"));
+  }
+
+  #[test]
+  fn rejects_absolute_paths() {
+    let root = temp_workspace();
+    let sandbox = PathSandbox::new(&root);
+    assert!(sandbox.resolve("/etc/passwd").unwrap_err().contains("absolute"));
+    assert!(sandbox.resolve("\\\\server\\share").unwrap_err().contains("absolute"));
+    assert!(sandbox.resolve("C:/Windows/win.ini").unwrap_err().contains("absolute"));
+    assert!(sandbox.resolve("  ").unwrap_err().contains("empty"));
+  }
+
+  #[test]
+  fn rejects_parent_traversal() {
+    let root = temp_workspace();
+    let sandbox = PathSandbox::new(&root);
+    assert!(sandbox.resolve("../../etc/passwd").is_err());
+    assert!(sandbox.resolve("stories/../../outside.txt").is_err());
+    assert!(sandbox.resolve("stories\\..\\..\\outside.txt").is_err());
+  }
+
+  #[test]
+  fn rejects_protected_dirs() {
+    let root = temp_workspace();
+    let sandbox = PathSandbox::new(&root);
+    assert!(sandbox.resolve(".git/config").unwrap_err().contains("protected"));
+    assert!(sandbox.resolve("./.git").unwrap_err().contains("protected"));
+    assert!(sandbox.resolve(".novel/.undo/x.json").is_err());
+    assert!(sandbox.resolve(".gitignore").is_ok());
+    assert!(sandbox.resolve(".novel/.cache/outline.json").is_ok());
+
+    let layer = project_settings::SettingsLayer {
+      protected_dirs: Some(vec!["./notes/private/".to_string()]),
+      ..Default::default()
+    };
+    project_settings::save(&root, &layer).unwrap();
+    let sandbox = PathSandbox::new(&root);
+    assert!(sandbox.resolve("notes/private/plan.md").unwrap_err().contains("protected"));
+    assert!(sandbox.resolve("notes/public.md").is_ok());
+    assert!(sandbox.resolve(".git/config").is_err());
+  }
+
+  #[test]
+  fn file_names_are_single_components() {
+    assert!(check_file_name("xianxia-imported").is_ok());
+    for bad in ["", ".", "..", "../x", "a/b", "a\\b", "/etc", "C:x"] {
+      assert!(check_file_name(bad).is_err(), "{bad}");
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn rejects_symlink_escapes() {
+    let root = temp_workspace();
+    let outside = TempDir::new("outside");
+    fs::write(outside.join("secret.txt"), "秘密").unwrap();
+    std::os::unix::fs::symlink(&outside, root.join("stories/link")).unwrap();
+    std::os::unix::fs::symlink(outside.join("secret.txt"), root.join("stories/secret.txt")).unwrap();
+    std::os::unix::fs::symlink(root.join(".git"), root.join("stories/git")).unwrap();
+
+    let sandbox = PathSandbox::new(&root);
+    assert!(sandbox.resolve("stories/link/secret.txt").unwrap_err().contains("escapes"));
+    assert!(sandbox.resolve("stories/link/new.txt").unwrap_err().contains("escapes"));
+    assert!(sandbox.resolve("stories/secret.txt").unwrap_err().contains("escapes"));
+    assert!(sandbox.resolve("stories/git/config").unwrap_err().contains("protected"));
+
+    // 工作区内的链接解析为链接本身，删除时不会动到它指向的文件
+    std::os::unix::fs::symlink(root.join("stories/ch1.txt"), root.join("stories/alias.txt")).unwrap();
+    let alias = sandbox.resolve("stories/alias.txt").unwrap();
+    assert_eq!(alias, fs::canonicalize(&root).unwrap().join("stories/alias.txt"));
+    fs::remove_file(&alias).unwrap();
+    assert!(root.join("stories/ch1.txt").exists());
+  }
+}
@@ -0,0 +1,185 @@
+use crate::app_settings::{self, AppSettings};
+use crate::schema::{self, Schema};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 新建工作区时写入 project.json 的分章目标字数
+pub const DEFAULT_CHAPTER_WORD_TARGET: u32 = 2000;
+
+/// 版本 0：只有 `chapter_word_target`、没有版本号的文件
+pub const SCHEMA: Schema = Schema {
+  name: "project settings",
+  current: 1,
+  detect: schema::unversioned,
+  migrations: &[schema::unchanged],
+};
+
+/// 可在工作区或单次请求中覆盖的设置；缺省字段沿用下一层的值。
+///
+/// 同一结构既是 `.novel/.settings/project.json` 的内容，也是命令的 `overrides` 参数。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsLayer {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub active_provider_id: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub active_agent_id: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub use_markdown: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub chapter_word_target: Option<u32>,
+  /// 额外禁止 AI 访问的目录（相对工作区根目录），与内置的受保护目录合并；只在 project.json 中生效
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub protected_dirs: Option<Vec<String>>,
+}
+
+/// 设置值来自哪一层，按优先级从低到高排列
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingSource {
+  Default,
+  Global,
+  Workspace,
+  Request,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Sourced<T> {
+  pub value: T,
+  pub source: SettingSource,
+}
+
+impl<T> Sourced<T> {
+  fn new(value: T, source: SettingSource) -> Self {
+    Self { value, source }
+  }
+
+  fn apply(&mut self, value: Option<T>, source: SettingSource) {
+    if let Some(value) = value {
+      *self = Self::new(value, source);
+    }
+  }
+}
+
+/// 逐层合并后的设置，每个值都带有来源
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveSettings {
+  pub active_provider_id: Sourced<String>,
+  pub active_agent_id: Sourced<String>,
+  pub use_markdown: Sourced<bool>,
+  pub chapter_word_target: Sourced<u32>,
+}
+
+/// 按 默认值 → 全局设置 → 工作区 → 单次请求 的顺序合并。
+///
+/// `global` 为 `None` 表示还没有保存过全局设置；指向不存在的 provider 的覆盖会被忽略。
+pub fn resolve(global: Option<&AppSettings>, workspace: &SettingsLayer, request: &SettingsLayer) -> EffectiveSettings {
+  let defaults = AppSettings::default();
+  let mut out = EffectiveSettings {
+    active_provider_id: Sourced::new(defaults.active_provider_id, SettingSource::Default),
+    active_agent_id: Sourced::new(defaults.active_agent_id, SettingSource::Default),
+    use_markdown: Sourced::new(defaults.output.use_markdown, SettingSource::Default),
+    chapter_word_target: Sourced::new(DEFAULT_CHAPTER_WORD_TARGET, SettingSource::Default),
+  };
+  let providers = global.map(|g| g.providers.clone()).unwrap_or(defaults.providers);
+
+  if let Some(g) = global {
+    out.active_provider_id.apply(Some(g.active_provider_id.clone()), SettingSource::Global);
+    out.active_agent_id.apply(Some(g.active_agent_id.clone()), SettingSource::Global);
+    out.use_markdown.apply(Some(g.output.use_markdown), SettingSource::Global);
+  }
+  for (layer, source) in [(workspace, SettingSource::Workspace), (request, SettingSource::Request)] {
+    let provider = layer
+      .active_provider_id
+      .clone()
+      .filter(|id| providers.iter().any(|p| p.id == *id));
+    out.active_provider_id.apply(provider, source);
+    out.active_agent_id.apply(layer.active_agent_id.clone().filter(|id| !id.trim().is_empty()), source);
+    out.use_markdown.apply(layer.use_markdown, source);
+    out.chapter_word_target.apply(layer.chapter_word_target, source);
+  }
+  out
+}
+
+/// 读取全局设置与工作区设置后合并；`root` 为 `None` 时跳过工作区层。
+pub fn effective(app: &tauri::AppHandle, root: Option<&Path>, request: &SettingsLayer) -> Result<EffectiveSettings, String> {
+  let global = if app_settings::is_saved(app)? {
+    Some(app_settings::load(app)?)
+  } else {
+    None
+  };
+  let workspace = match root {
+    Some(root) => load(root)?,
+    None => SettingsLayer::default(),
+  };
+  Ok(resolve(global.as_ref(), &workspace, request))
+}
+
+fn project_path(root: &Path) -> PathBuf {
+  root.join(".novel").join(".settings").join("project.json")
+}
+
+pub fn load(root: &Path) -> Result<SettingsLayer, String> {
+  let Some(value) = schema::read(&project_path(root), &SCHEMA)? else {
+    return Ok(SettingsLayer::default());
+  };
+  serde_json::from_value(value).map_err(|e| format!("parse project settings failed: {e}"))
+}
+
+pub fn save(root: &Path, layer: &SettingsLayer) -> Result<(), String> {
+  let path = project_path(root);
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).map_err(|e| format!("create project settings dir failed: {e}"))?;
+  }
+  let raw = SCHEMA.to_string(layer)?;
+  fs::write(path, raw).map_err(|e| format!("write project settings failed: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn later_layers_win_and_report_their_source() {
+    let empty = SettingsLayer::default();
+    let defaults = resolve(None, &empty, &empty);
+    assert_eq!(defaults.active_provider_id.source, SettingSource::Default);
+    assert_eq!(defaults.chapter_word_target.value, DEFAULT_CHAPTER_WORD_TARGET);
+
+    let global = AppSettings {
+      active_agent_id: "romance".to_string(),
+      output: app_settings::OutputSettings { use_markdown: true },
+      ..Default::default()
+    };
+    let workspace = SettingsLayer {
+      active_agent_id: Some("mystery".to_string()),
+      active_provider_id: Some("missing-provider".to_string()),
+      chapter_word_target: Some(4000),
+      ..Default::default()
+    };
+    let request = SettingsLayer {
+      use_markdown: Some(false),
+      ..Default::default()
+    };
+    let out = resolve(Some(&global), &workspace, &request);
+    assert_eq!(out.active_agent_id.value, "mystery");
+    assert_eq!(out.active_agent_id.source, SettingSource::Workspace);
+    // 工作区指向不存在的 provider 时沿用全局设置
+    assert_eq!(out.active_provider_id.value, "openai");
+    assert_eq!(out.active_provider_id.source, SettingSource::Global);
+    assert!(!out.use_markdown.value);
+    assert_eq!(out.use_markdown.source, SettingSource::Request);
+    assert_eq!(out.chapter_word_target.value, 4000);
+  }
+
+  #[test]
+  fn loads_every_previous_version() {
+    let raw = include_str!("../fixtures/schema/project.v0.json");
+    let (value, from) = SCHEMA.migrate(serde_json::from_str(raw).unwrap()).unwrap();
+    assert_eq!(from, 0);
+    let layer: SettingsLayer = serde_json::from_value(value).unwrap();
+    assert_eq!(layer.chapter_word_target, Some(2000));
+    assert!(layer.active_agent_id.is_none());
+  }
+}
@@ -0,0 +1,165 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 每个持久化 JSON 文件顶层都带这个字段；数组格式的旧文件在迁移时包进对象里。
+pub const VERSION_KEY: &str = "schema_version";
+
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+/// 一种持久化文件的版本信息与迁移链。
+pub struct Schema {
+  /// 用于错误信息与备份文件名
+  pub name: &'static str,
+  pub current: u32,
+  /// 没有 `schema_version` 字段时判断文件属于哪个旧版本
+  pub detect: fn(&Value) -> u32,
+  /// `migrations[i]` 把版本 `i` 的内容升级为版本 `i + 1`，长度必须等于 `current`
+  pub migrations: &'static [Migration],
+}
+
+impl Schema {
+  pub fn version_of(&self, value: &Value) -> u32 {
+    match value.get(VERSION_KEY).and_then(|v| v.as_u64()) {
+      Some(v) => v as u32,
+      None => (self.detect)(value),
+    }
+  }
+
+  /// 依次应用迁移，返回最新版本的内容以及原始版本号。
+  pub fn migrate(&self, mut value: Value) -> Result<(Value, u32), String> {
+    let from = self.version_of(&value);
+    if from > self.current {
+      return Err(format!(
+        "{} was written by a newer version of the app (schema {from}, supported {})",
+        self.name, self.current
+      ));
+    }
+    for (version, step) in self.migrations.iter().enumerate().skip(from as usize) {
+      value = step(value).map_err(|e| format!("migrate {} from schema {version} failed: {e}", self.name))?;
+    }
+    Ok((stamp(value, self.current), from))
+  }
+
+  /// 序列化为带版本号的 JSON 文本。
+  pub fn to_string<T: Serialize + ?Sized>(&self, data: &T) -> Result<String, String> {
+    let value = serde_json::to_value(data).map_err(|e| format!("serialize {} failed: {e}", self.name))?;
+    serde_json::to_string_pretty(&stamp(value, self.current)).map_err(|e| format!("serialize {} failed: {e}", self.name))
+  }
+}
+
+/// 最新版本中不再需要调整内容的迁移步骤，只更新版本号。
+pub fn unchanged(value: Value) -> Result<Value, String> {
+  Ok(value)
+}
+
+/// 把顶层数组包进 `{ key: [...] }`，为写入版本号腾出位置。
+pub fn wrap_array(value: Value, key: &str) -> Result<Value, String> {
+  match value {
+    Value::Array(items) => Ok(serde_json::json!({ key: items })),
+    Value::Object(_) => Ok(value),
+    _ => Err("expected an array".to_string()),
+  }
+}
+
+/// 加入版本号之前的文件一律视为版本 0。
+pub fn unversioned(_: &Value) -> u32 {
+  0
+}
+
+/// 顶层为数组的文件属于版本 0，其余按版本 1 处理。
+pub fn detect_array(value: &Value) -> u32 {
+  if value.is_array() {
+    0
+  } else {
+    1
+  }
+}
+
+fn stamp(mut value: Value, version: u32) -> Value {
+  if let Value::Object(map) = &mut value {
+    map.insert(VERSION_KEY.to_string(), Value::from(version));
+  }
+  value
+}
+
+/// 读取并在需要时迁移文件：文件不存在时返回 `None`；迁移前把原文件备份为
+/// `<文件名>.v<旧版本>-<时间戳>.bak`，再写回迁移后的内容。
+pub fn read(path: &Path, schema: &Schema) -> Result<Option<Value>, String> {
+  if !path.exists() {
+    return Ok(None);
+  }
+  let raw = fs::read_to_string(path).map_err(|e| format!("read {} failed: {e}", schema.name))?;
+  let value: Value = serde_json::from_str(&raw).map_err(|e| format!("parse {} failed: {e}", schema.name))?;
+  let had_version = value.get(VERSION_KEY).is_some();
+  let (migrated, from) = schema.migrate(value)?;
+  if from < schema.current || !had_version {
+    backup(path, from)?;
+    let out = serde_json::to_string_pretty(&migrated).map_err(|e| format!("serialize {} failed: {e}", schema.name))?;
+    fs::write(path, out).map_err(|e| format!("write {} failed: {e}", schema.name))?;
+  }
+  Ok(Some(migrated))
+}
+
+fn backup(path: &Path, version: u32) -> Result<PathBuf, String> {
+  let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+  let stamp = chrono::Utc::now().format("%Y%m%d%H%M%S%3f");
+  let target = path.with_file_name(format!("{file_name}.v{version}-{stamp}.bak"));
+  fs::copy(path, &target).map_err(|e| format!("backup {} failed: {e}", path.display()))?;
+  Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::TempDir;
+
+  fn rename_title(mut value: Value) -> Result<Value, String> {
+    for item in value["items"].as_array_mut().into_iter().flatten() {
+      if let Some(title) = item.as_object_mut().and_then(|o| o.remove("title")) {
+        item["name"] = title;
+      }
+    }
+    Ok(value)
+  }
+
+  const TEST_SCHEMA: Schema = Schema {
+    name: "items",
+    current: 2,
+    detect: detect_array,
+    migrations: &[|v| wrap_array(v, "items"), rename_title],
+  };
+
+  #[test]
+  fn migrates_through_the_chain_and_backs_up() {
+    let dir = TempDir::new("schema");
+    let path = dir.join("items.json");
+    fs::write(&path, r#"[{"title":"甲"}]"#).unwrap();
+
+    let value = read(&path, &TEST_SCHEMA).unwrap().unwrap();
+    assert_eq!(value, serde_json::json!({ "schema_version": 2, "items": [{ "name": "甲" }] }));
+    let backups = fs::read_dir(&dir)
+      .unwrap()
+      .flatten()
+      .map(|e| e.file_name().to_string_lossy().to_string())
+      .filter(|n| n.starts_with("items.json.v0-"))
+      .collect::<Vec<_>>();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(fs::read_to_string(dir.join(&backups[0])).unwrap(), r#"[{"title":"甲"}]"#);
+
+    // 已是最新版本时不再备份
+    read(&path, &TEST_SCHEMA).unwrap();
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+    fs::write(&path, r#"{"schema_version":3,"items":[]}"#).unwrap();
+    assert!(read(&path, &TEST_SCHEMA).unwrap_err().contains("newer version"));
+    assert!(read(&dir.join("missing.json"), &TEST_SCHEMA).unwrap().is_none());
+  }
+
+  #[test]
+  fn stamps_version_when_serializing() {
+    let raw = TEST_SCHEMA.to_string(&serde_json::json!({ "items": [] })).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&raw).unwrap()[VERSION_KEY], 2);
+  }
+}
@@ -1,36 +1,78 @@
+#[cfg(windows)]
 use base64::{engine::general_purpose, Engine as _};
 use crate::app_data;
+use crate::schema::{self, Schema};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 
+/// 强制使用加密文件后端（无桌面会话 / 测试环境），值为 `file`
+const BACKEND_ENV: &str = "NOVEL_IDE_SECRETS_BACKEND";
+/// 文件后端的口令，设置后启动时自动解锁
+const PASSPHRASE_ENV: &str = "NOVEL_IDE_SECRETS_PASSPHRASE";
+
 #[derive(Default, Serialize, Deserialize)]
 struct SecretsFile {
   providers: BTreeMap<String, String>,
+  /// 加密文件后端的口令派生参数；DPAPI 与系统钥匙串不使用
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  vault: Option<vault::VaultHeader>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+  /// Windows DPAPI 加密后存入 secrets.json
+  Dpapi,
+  /// 系统钥匙串（Secret Service / macOS Keychain）
+  Keyring,
+  /// 口令派生密钥（Argon2id）+ XChaCha20-Poly1305 加密后存入 secrets.json
+  File,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretsStatus {
+  pub backend: SecretBackend,
+  /// 文件后端尚未用口令解锁
+  pub locked: bool,
+  /// 文件后端是否已经设置过口令
+  pub initialized: bool,
 }
 
 fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
   app_data::data_file_path(app, "secrets.json")
 }
 
-fn read_secrets_file(app: &AppHandle) -> Result<SecretsFile, String> {
-  let path = secrets_path(app)?;
-  if !path.exists() {
+/// secrets.json 与 api_keys.json 的版本 0 都是没有版本号的对象
+const SECRETS_SCHEMA: Schema = Schema {
+  name: "secrets",
+  current: 1,
+  detect: schema::unversioned,
+  migrations: &[schema::unchanged],
+};
+
+const KEY_INDEX_SCHEMA: Schema = Schema {
+  name: "api keys",
+  current: 1,
+  detect: schema::unversioned,
+  migrations: &[schema::unchanged],
+};
+
+fn read_secrets_file(path: &Path) -> Result<SecretsFile, String> {
+  let Some(value) = schema::read(path, &SECRETS_SCHEMA)? else {
     return Ok(SecretsFile::default());
-  }
-  let raw = fs::read_to_string(&path).map_err(|e| format!("read secrets failed: {e}"))?;
-  serde_json::from_str(&raw).map_err(|e| format!("parse secrets failed: {e}"))
+  };
+  serde_json::from_value(value).map_err(|e| format!("parse secrets failed: {e}"))
 }
 
-fn write_secrets_file(app: &AppHandle, s: &SecretsFile) -> Result<(), String> {
-  let path = secrets_path(app)?;
+fn write_secrets_file(path: &Path, s: &SecretsFile) -> Result<(), String> {
   if let Some(parent) = path.parent() {
     fs::create_dir_all(parent).map_err(|e| format!("create secrets dir failed: {e}"))?;
   }
-  let raw = serde_json::to_string_pretty(s).map_err(|e| format!("serialize secrets failed: {e}"))?;
-  fs::write(&path, raw).map_err(|e| format!("write secrets failed: {e}"))
+  let raw = SECRETS_SCHEMA.to_string(s)?;
+  fs::write(path, raw).map_err(|e| format!("write secrets failed: {e}"))
 }
 
 #[cfg(windows)]
@@ -109,26 +151,19 @@ fn unprotect_bytes(ciphertext: &[u8]) -> Result<Vec<u8>, String> {
   Ok(out)
 }
 
-#[cfg(not(windows))]
-fn protect_bytes(_: &[u8]) -> Result<Vec<u8>, String> {
-  Err("fallback secrets not supported on this OS".to_string())
-}
-
-#[cfg(not(windows))]
-fn unprotect_bytes(_: &[u8]) -> Result<Vec<u8>, String> {
-  Err("fallback secrets not supported on this OS".to_string())
-}
-
+#[cfg(windows)]
 fn store_fallback(app: &AppHandle, provider: &str, api_key: &str) -> Result<(), String> {
-  let mut s = read_secrets_file(app)?;
+  let path = secrets_path(app)?;
+  let mut s = read_secrets_file(&path)?;
   let encrypted = protect_bytes(api_key.as_bytes())?;
   let encoded = general_purpose::STANDARD.encode(encrypted);
   s.providers.insert(provider.to_string(), encoded);
-  write_secrets_file(app, &s)
+  write_secrets_file(&path, &s)
 }
 
+#[cfg(windows)]
 fn load_fallback(app: &AppHandle, provider: &str) -> Result<Option<String>, String> {
-  let s = read_secrets_file(app)?;
+  let s = read_secrets_file(&secrets_path(app)?)?;
   let encoded = match s.providers.get(provider) {
     Some(v) => v,
     None => return Ok(None),
@@ -141,7 +176,241 @@ fn load_fallback(app: &AppHandle, provider: &str) -> Result<Option<String>, Stri
   Ok(Some(v))
 }
 
+/// 当前使用的存储后端；非 Windows 上首次调用时探测系统钥匙串是否可用。
+pub fn backend() -> SecretBackend {
+  if cfg!(windows) {
+    return SecretBackend::Dpapi;
+  }
+  static BACKEND: std::sync::OnceLock<SecretBackend> = std::sync::OnceLock::new();
+  *BACKEND.get_or_init(|| {
+    if std::env::var(BACKEND_ENV).is_ok_and(|v| v.trim().eq_ignore_ascii_case("file")) {
+      return SecretBackend::File;
+    }
+    if keyring_available() {
+      SecretBackend::Keyring
+    } else {
+      SecretBackend::File
+    }
+  })
+}
+
+#[cfg(not(windows))]
+fn keyring_entry(provider: &str) -> Result<keyring::Entry, String> {
+  keyring::Entry::new(crate::branding::DATA_DIR_NAME, provider).map_err(|e| format!("keyring failed: {e}"))
+}
+
+#[cfg(not(windows))]
+fn keyring_available() -> bool {
+  // 读取一个不存在的条目：能得到 NoEntry 说明 Secret Service / Keychain 可用
+  match keyring_entry("__novel_ide_probe__") {
+    Ok(entry) => matches!(entry.get_password(), Ok(_) | Err(keyring::Error::NoEntry)),
+    Err(_) => false,
+  }
+}
+
+#[cfg(windows)]
+fn keyring_available() -> bool {
+  false
+}
+
+pub fn status(app: &AppHandle) -> Result<SecretsStatus, String> {
+  let backend = backend();
+  if backend != SecretBackend::File {
+    return Ok(SecretsStatus { backend, locked: false, initialized: true });
+  }
+  let initialized = read_secrets_file(&secrets_path(app)?)?.vault.is_some();
+  Ok(SecretsStatus { backend, locked: vault::session_key(app).is_none(), initialized })
+}
+
+/// 用口令解锁文件后端；首次调用时以该口令初始化。
+pub fn unlock(app: &AppHandle, passphrase: &str) -> Result<(), String> {
+  // DPAPI 后端由系统账户保护，没有口令；也不能往 DPAPI 的 secrets 文件里写口令头
+  if backend() == SecretBackend::Dpapi {
+    return Ok(());
+  }
+  if passphrase.is_empty() {
+    return Err("passphrase empty".to_string());
+  }
+  let key = vault::open(&secrets_path(app)?, passphrase)?;
+  vault::set_session_key(key);
+  Ok(())
+}
+
+#[cfg(not(windows))]
+fn store_secret(app: &AppHandle, provider: &str, api_key: &str) -> Result<(), String> {
+  match backend() {
+    SecretBackend::Keyring => keyring_entry(provider)?
+      .set_password(api_key)
+      .map_err(|e| format!("keyring store failed: {e}")),
+    _ => {
+      let key = vault::session_key(app).ok_or(vault::LOCKED)?;
+      vault::store(&secrets_path(app)?, &key, provider, api_key)
+    }
+  }
+}
+
+#[cfg(not(windows))]
+fn load_secret(app: &AppHandle, provider: &str) -> Result<Option<String>, String> {
+  match backend() {
+    SecretBackend::Keyring => match keyring_entry(provider)?.get_password() {
+      Ok(v) => Ok(Some(v)),
+      Err(keyring::Error::NoEntry) => Ok(None),
+      Err(e) => Err(format!("keyring load failed: {e}")),
+    },
+    _ => {
+      let key = vault::session_key(app).ok_or(vault::LOCKED)?;
+      vault::load(&secrets_path(app)?, &key, provider)
+    }
+  }
+}
+
+#[cfg(windows)]
+fn store_secret(app: &AppHandle, provider: &str, api_key: &str) -> Result<(), String> {
+  store_fallback(app, provider, api_key)
+}
+
+#[cfg(windows)]
+fn load_secret(app: &AppHandle, provider: &str) -> Result<Option<String>, String> {
+  load_fallback(app, provider)
+}
+
+/// 删除一个条目；条目不存在时视为成功。
+fn delete_secret(app: &AppHandle, account: &str) -> Result<(), String> {
+  #[cfg(not(windows))]
+  if backend() == SecretBackend::Keyring {
+    return match keyring_entry(account)?.delete_credential() {
+      Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+      Err(e) => Err(format!("keyring delete failed: {e}")),
+    };
+  }
+  let path = secrets_path(app)?;
+  let mut s = read_secrets_file(&path)?;
+  if s.providers.remove(account).is_some() {
+    write_secrets_file(&path, &s)?;
+  }
+  Ok(())
+}
+
+// ============ 多 key 管理 ============
+
+/// 未命名的 key 使用这个名字，对应旧版本按 provider id 保存的条目
+pub const DEFAULT_KEY_NAME: &str = "default";
+/// 环境变量覆盖的前缀，完整名称为 `NOVEL_IDE_KEY_<PROVIDER_ID>`
+const KEY_ENV_PREFIX: &str = "NOVEL_IDE_KEY_";
+
+/// 同一 provider 有多个 key 时的使用顺序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyPolicy {
+  /// 总是从第一个 key 开始，鉴权失败或限流时换下一个
+  #[default]
+  Failover,
+  /// 每次请求轮换起始 key，失败时同样继续尝试后面的
+  RoundRobin,
+}
+
+/// 每个 provider 的 key 名称与策略；不含 key 本身，保存在 api_keys.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct ProviderKeys {
+  names: Vec<String>,
+  policy: KeyPolicy,
+}
+
+impl Default for ProviderKeys {
+  fn default() -> Self {
+    Self { names: vec![DEFAULT_KEY_NAME.to_string()], policy: KeyPolicy::default() }
+  }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct KeyIndex {
+  providers: BTreeMap<String, ProviderKeys>,
+}
+
+/// 给前端展示的 key 列表，不含 key 内容
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeysInfo {
+  pub provider_id: String,
+  pub policy: KeyPolicy,
+  pub names: Vec<String>,
+  /// 设置了 `NOVEL_IDE_KEY_<PROVIDER_ID>` 时优先使用环境变量，忽略已保存的 key
+  pub env_override: Option<String>,
+}
+
+fn key_index_path(app: &AppHandle) -> Result<PathBuf, String> {
+  app_data::data_file_path(app, "api_keys.json")
+}
+
+fn load_key_index(app: &AppHandle) -> Result<KeyIndex, String> {
+  let Some(value) = schema::read(&key_index_path(app)?, &KEY_INDEX_SCHEMA)? else {
+    return Ok(KeyIndex::default());
+  };
+  serde_json::from_value(value).map_err(|e| format!("parse api keys failed: {e}"))
+}
+
+fn save_key_index(app: &AppHandle, index: &KeyIndex) -> Result<(), String> {
+  let path = key_index_path(app)?;
+  let raw = KEY_INDEX_SCHEMA.to_string(index)?;
+  fs::write(&path, raw).map_err(|e| format!("write api keys failed: {e}"))
+}
+
+/// 默认 key 沿用 provider id 作为条目名，兼容旧数据；其它 key 为 `<provider>#<name>`
+fn account_name(provider: &str, name: &str) -> String {
+  if name == DEFAULT_KEY_NAME {
+    provider.to_string()
+  } else {
+    format!("{provider}#{name}")
+  }
+}
+
+fn normalize_key_name(name: Option<&str>) -> Result<String, String> {
+  let name = name.map(str::trim).filter(|n| !n.is_empty()).unwrap_or(DEFAULT_KEY_NAME);
+  if name.contains('#') {
+    return Err("key name must not contain '#'".to_string());
+  }
+  Ok(name.to_string())
+}
+
+fn env_var_name(provider: &str) -> String {
+  let id = provider
+    .chars()
+    .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+    .collect::<String>();
+  format!("{KEY_ENV_PREFIX}{id}")
+}
+
+fn env_override(provider: &str) -> Option<String> {
+  std::env::var(env_var_name(provider)).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// 按策略排列 key 名称；`cursor` 为本 provider 已发起的请求数
+fn ordered_names(names: &[String], policy: KeyPolicy, cursor: usize) -> Vec<String> {
+  let mut out = names.to_vec();
+  if policy == KeyPolicy::RoundRobin && !out.is_empty() {
+    let len = out.len();
+    out.rotate_left(cursor % len);
+  }
+  out
+}
+
+fn next_cursor(provider: &str) -> usize {
+  static CURSORS: std::sync::Mutex<BTreeMap<String, usize>> = std::sync::Mutex::new(BTreeMap::new());
+  let Ok(mut cursors) = CURSORS.lock() else {
+    return 0;
+  };
+  let c = cursors.entry(provider.to_string()).or_insert(0);
+  let current = *c;
+  *c = c.wrapping_add(1);
+  current
+}
+
 pub fn set_api_key(app: &AppHandle, provider: &str, api_key: &str) -> Result<(), String> {
+  set_named_api_key(app, provider, None, api_key)
+}
+
+pub fn set_named_api_key(app: &AppHandle, provider: &str, name: Option<&str>, api_key: &str) -> Result<(), String> {
   let provider = provider.trim();
   if provider.is_empty() {
     return Err("provider empty".to_string());
@@ -150,17 +419,283 @@ pub fn set_api_key(app: &AppHandle, provider: &str, api_key: &str) -> Result<(),
   if api_key.is_empty() {
     return Err("api key empty".to_string());
   }
-  store_fallback(app, provider, api_key)
+  let name = normalize_key_name(name)?;
+  store_secret(app, &account_name(provider, &name), api_key)?;
+  let mut index = load_key_index(app)?;
+  let entry = index.providers.entry(provider.to_string()).or_default();
+  if !entry.names.contains(&name) {
+    entry.names.push(name);
+    save_key_index(app, &index)?;
+  }
+  Ok(())
+}
+
+/// 删除 provider 的一个 key；`name` 为空时删除默认 key。
+pub fn delete_api_key(app: &AppHandle, provider: &str, name: Option<&str>) -> Result<(), String> {
+  let provider = provider.trim();
+  if provider.is_empty() {
+    return Err("provider empty".to_string());
+  }
+  let name = normalize_key_name(name)?;
+  delete_secret(app, &account_name(provider, &name))?;
+  let mut index = load_key_index(app)?;
+  let entry = index.providers.entry(provider.to_string()).or_default();
+  entry.names.retain(|n| *n != name);
+  save_key_index(app, &index)
+}
+
+pub fn set_key_policy(app: &AppHandle, provider: &str, policy: KeyPolicy) -> Result<(), String> {
+  let provider = provider.trim();
+  if provider.is_empty() {
+    return Err("provider empty".to_string());
+  }
+  let mut index = load_key_index(app)?;
+  index.providers.entry(provider.to_string()).or_default().policy = policy;
+  save_key_index(app, &index)
+}
+
+/// 列出已保存的 key 名称（只包含确实存有内容的）。
+pub fn list_api_keys(app: &AppHandle, provider: &str) -> Result<ApiKeysInfo, String> {
+  let provider = provider.trim();
+  let entry = load_key_index(app)?.providers.remove(provider).unwrap_or_default();
+  let mut names = Vec::new();
+  for name in entry.names {
+    if load_secret(app, &account_name(provider, &name))?.is_some_and(|v| !v.trim().is_empty()) {
+      names.push(name);
+    }
+  }
+  Ok(ApiKeysInfo {
+    provider_id: provider.to_string(),
+    policy: entry.policy,
+    names,
+    env_override: env_override(provider).map(|_| env_var_name(provider)),
+  })
+}
+
+/// 本次请求依次尝试的 key：环境变量覆盖优先，其次按策略排列的已保存 key。
+pub fn api_key_candidates(app: &AppHandle, provider: &str) -> Result<Vec<String>, String> {
+  let provider = provider.trim();
+  if provider.is_empty() {
+    return Ok(Vec::new());
+  }
+  if let Some(v) = env_override(provider) {
+    return Ok(vec![v]);
+  }
+  let entry = load_key_index(app)?.providers.remove(provider).unwrap_or_default();
+  let cursor = if entry.policy == KeyPolicy::RoundRobin { next_cursor(provider) } else { 0 };
+  let mut keys = Vec::new();
+  for name in ordered_names(&entry.names, entry.policy, cursor) {
+    match load_secret(app, &account_name(provider, &name))? {
+      Some(v) if !v.trim().is_empty() => keys.push(v.trim().to_string()),
+      _ => {}
+    }
+  }
+  Ok(keys)
 }
 
-pub fn get_api_key(app: &AppHandle, provider: &str) -> Result<Option<String>, String> {
+/// 是否有可用的 key（环境变量或任一已保存的 key）。
+pub fn has_api_key(app: &AppHandle, provider: &str) -> Result<bool, String> {
   let provider = provider.trim();
   if provider.is_empty() {
-    return Ok(None);
+    return Ok(false);
+  }
+  if env_override(provider).is_some() {
+    return Ok(true);
+  }
+  Ok(!list_api_keys(app, provider)?.names.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn env_override_names_and_key_order() {
+    assert_eq!(env_var_name("openai"), "NOVEL_IDE_KEY_OPENAI");
+    assert_eq!(env_var_name("my-deepseek.v3"), "NOVEL_IDE_KEY_MY_DEEPSEEK_V3");
+    assert_eq!(account_name("openai", DEFAULT_KEY_NAME), "openai");
+    assert_eq!(account_name("openai", "backup"), "openai#backup");
+    assert!(normalize_key_name(Some("a#b")).is_err());
+    assert_eq!(normalize_key_name(Some("  ")).unwrap(), DEFAULT_KEY_NAME);
+
+    let names = ["a", "b", "c"].map(String::from);
+    assert_eq!(ordered_names(&names, KeyPolicy::Failover, 5), names);
+    assert_eq!(ordered_names(&names, KeyPolicy::RoundRobin, 4), ["b", "c", "a"].map(String::from));
+    assert!(ordered_names(&[], KeyPolicy::RoundRobin, 1).is_empty());
+  }
+
+  #[test]
+  fn loads_every_previous_version() {
+    let raw = include_str!("../fixtures/schema/secrets.v0.json");
+    let (value, from) = SECRETS_SCHEMA.migrate(serde_json::from_str(raw).unwrap()).unwrap();
+    assert_eq!(from, 0);
+    let file: SecretsFile = serde_json::from_value(value).unwrap();
+    assert_eq!(file.providers["openai"], "c2stb3BlbmFp");
+    assert!(file.vault.is_none());
+
+    let raw = include_str!("../fixtures/schema/api_keys.v0.json");
+    let (value, _) = KEY_INDEX_SCHEMA.migrate(serde_json::from_str(raw).unwrap()).unwrap();
+    let index: KeyIndex = serde_json::from_value(value).unwrap();
+    assert_eq!(index.providers["openai"].names, ["default", "backup"].map(String::from));
+    assert_eq!(index.providers["openai"].policy, KeyPolicy::RoundRobin);
+  }
+}
+
+/// 加密文件后端：Argon2id 由口令派生 256 位密钥，每个条目用 XChaCha20-Poly1305 单独加密。
+mod vault {
+  use super::{read_secrets_file, write_secrets_file, PASSPHRASE_ENV};
+  use base64::{engine::general_purpose, Engine as _};
+  use serde::{Deserialize, Serialize};
+  use std::path::Path;
+  use std::sync::Mutex;
+  use tauri::AppHandle;
+
+  #[cfg(not(windows))]
+  pub const LOCKED: &str = "secrets are locked; unlock with your passphrase first";
+  const CHECK_PLAINTEXT: &[u8] = b"novel-ide-vault";
+
+  #[derive(Clone, Serialize, Deserialize)]
+  pub struct VaultHeader {
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    /// 加密后的固定串，用来校验口令
+    check: String,
+  }
+
+  pub type Key = [u8; 32];
+
+  static SESSION_KEY: Mutex<Option<Key>> = Mutex::new(None);
+
+  pub fn set_session_key(key: Key) {
+    if let Ok(mut k) = SESSION_KEY.lock() {
+      *k = Some(key);
+    }
+  }
+
+  /// 当前会话的密钥；未解锁时尝试用环境变量中的口令解锁。
+  pub fn session_key(app: &AppHandle) -> Option<Key> {
+    if let Some(k) = SESSION_KEY.lock().ok().and_then(|k| *k) {
+      return Some(k);
+    }
+    let passphrase = std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty())?;
+    let key = open(&super::secrets_path(app).ok()?, &passphrase).ok()?;
+    set_session_key(key);
+    Some(key)
+  }
+
+  fn derive_key(passphrase: &str, header: &VaultHeader) -> Result<Key, String> {
+    let salt = general_purpose::STANDARD
+      .decode(&header.salt)
+      .map_err(|e| format!("decode vault salt failed: {e}"))?;
+    let params = argon2::Params::new(header.m_cost, header.t_cost, header.p_cost, Some(32))
+      .map_err(|e| format!("invalid vault params: {e}"))?;
+    let argon = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = [0u8; 32];
+    argon
+      .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+      .map_err(|e| format!("derive vault key failed: {e}"))?;
+    Ok(key)
+  }
+
+  /// 校验口令并返回密钥；文件中还没有口令时以此口令初始化。
+  pub fn open(path: &Path, passphrase: &str) -> Result<Key, String> {
+    let mut file = read_secrets_file(path)?;
+    if let Some(header) = &file.vault {
+      let key = derive_key(passphrase, header)?;
+      return match decrypt(&key, &header.check) {
+        Ok(v) if v == CHECK_PLAINTEXT => Ok(key),
+        _ => Err("wrong passphrase".to_string()),
+      };
+    }
+    let mut salt = [0u8; 16];
+    getrandom::getrandom(&mut salt).map_err(|e| format!("generate salt failed: {e}"))?;
+    let defaults = argon2::Params::default();
+    let mut header = VaultHeader {
+      salt: general_purpose::STANDARD.encode(salt),
+      m_cost: defaults.m_cost(),
+      t_cost: defaults.t_cost(),
+      p_cost: defaults.p_cost(),
+      check: String::new(),
+    };
+    let key = derive_key(passphrase, &header)?;
+    header.check = encrypt(&key, CHECK_PLAINTEXT)?;
+    file.vault = Some(header);
+    write_secrets_file(path, &file)?;
+    Ok(key)
+  }
+
+  #[cfg(not(windows))]
+  pub fn store(path: &Path, key: &Key, provider: &str, value: &str) -> Result<(), String> {
+    let mut file = read_secrets_file(path)?;
+    if file.vault.is_none() {
+      return Err(LOCKED.to_string());
+    }
+    file.providers.insert(provider.to_string(), encrypt(key, value.as_bytes())?);
+    write_secrets_file(path, &file)
+  }
+
+  #[cfg(not(windows))]
+  pub fn load(path: &Path, key: &Key, provider: &str) -> Result<Option<String>, String> {
+    let file = read_secrets_file(path)?;
+    let Some(encoded) = file.providers.get(provider) else {
+      return Ok(None);
+    };
+    let plaintext = decrypt(key, encoded)?;
+    String::from_utf8(plaintext).map(Some).map_err(|_| "invalid secrets encoding".to_string())
+  }
+
+  /// base64(nonce ‖ 密文)
+  fn encrypt(key: &Key, plaintext: &[u8]) -> Result<String, String> {
+    use chacha20poly1305::aead::{Aead, KeyInit};
+    let cipher = chacha20poly1305::XChaCha20Poly1305::new(key.into());
+    let mut nonce = [0u8; 24];
+    getrandom::getrandom(&mut nonce).map_err(|e| format!("generate nonce failed: {e}"))?;
+    let ciphertext = cipher
+      .encrypt(&nonce.into(), plaintext)
+      .map_err(|_| "encrypt secret failed".to_string())?;
+    let mut out = nonce.to_vec();
+    out.extend(ciphertext);
+    Ok(general_purpose::STANDARD.encode(out))
   }
 
-  match load_fallback(app, provider)? {
-    Some(v) if !v.trim().is_empty() => Ok(Some(v)),
-    _ => Ok(None),
+  fn decrypt(key: &Key, encoded: &str) -> Result<Vec<u8>, String> {
+    use chacha20poly1305::aead::{Aead, KeyInit};
+    let raw = general_purpose::STANDARD
+      .decode(encoded)
+      .map_err(|e| format!("decode secrets failed: {e}"))?;
+    if raw.len() < 24 {
+      return Err("invalid secret entry".to_string());
+    }
+    let (nonce, ciphertext) = raw.split_at(24);
+    let cipher = chacha20poly1305::XChaCha20Poly1305::new(key.into());
+    cipher
+      .decrypt(chacha20poly1305::XNonce::from_slice(nonce), ciphertext)
+      .map_err(|_| "decrypt secret failed".to_string())
+  }
+
+  #[cfg(all(test, not(windows)))]
+  mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn file_vault_round_trips_and_checks_passphrase() {
+      let dir = TempDir::new("secrets");
+      let path = dir.join("secrets.json");
+      assert!(store(&path, &[0u8; 32], "openai", "sk-x").is_err());
+
+      let key = open(&path, "correct horse").unwrap();
+      store(&path, &key, "openai", "sk-test-123").unwrap();
+      let raw = std::fs::read_to_string(&path).unwrap();
+      assert!(!raw.contains("sk-test-123"));
+
+      let reopened = open(&path, "correct horse").unwrap();
+      assert_eq!(load(&path, &reopened, "openai").unwrap().as_deref(), Some("sk-test-123"));
+      assert_eq!(load(&path, &reopened, "anthropic").unwrap(), None);
+      assert_eq!(open(&path, "wrong").unwrap_err(), "wrong passphrase");
+      assert!(load(&path, &[7u8; 32], "openai").is_err());
+    }
   }
 }
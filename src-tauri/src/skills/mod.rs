@@ -1,5 +1,14 @@
+use crate::ai_types::ChatMessage;
+use crate::app_data;
+use crate::modification_types::{
+    ChangeSet, FileModification, FileModificationStatus, FileOperation, Modification, ModificationStatus, ModificationType,
+};
+use crate::path_sandbox::PathSandbox;
+use crate::schema::{self, Schema};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 /// Skill 定义
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -241,9 +250,186 @@ pub fn builtin_skills() -> Vec<Skill> {
     ]
 }
 
+/// 用户自定义 skill：全局的保存在数据目录的 skills.json，工作区的保存在 `.novel/.settings/skills.json`
+pub const SCHEMA: Schema = Schema {
+    name: "skills",
+    current: 1,
+    detect: schema::unversioned,
+    migrations: &[schema::unchanged],
+};
+
+/// skill 的来源；同 id 时工作区覆盖全局，全局覆盖内置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkillSource {
+    Builtin,
+    Global,
+    Workspace,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourcedSkill {
+    #[serde(flatten)]
+    pub skill: Skill,
+    pub source: SkillSource,
+    /// 是否覆盖了同 id 的内置 skill
+    pub overrides_builtin: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SkillsFile {
+    skills: Vec<Skill>,
+}
+
+fn custom_skills_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data::data_file_path(app, "skills.json")
+}
+
+fn workspace_skills_path(root: &Path) -> PathBuf {
+    root.join(".novel").join(".settings").join("skills.json")
+}
+
+fn read_file(path: &Path) -> Result<Vec<Skill>, String> {
+    let Some(value) = schema::read(path, &SCHEMA)? else {
+        return Ok(Vec::new());
+    };
+    let file: SkillsFile = serde_json::from_value(value).map_err(|e| format!("parse skills failed: {e}"))?;
+    Ok(file.skills)
+}
+
+fn write_file(path: &Path, skills: &[Skill]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create skills dir failed: {e}"))?;
+    }
+    let raw = SCHEMA.to_string(&SkillsFile { skills: skills.to_vec() })?;
+    fs::write(path, raw).map_err(|e| format!("write skills failed: {e}"))
+}
+
+pub fn load_custom(app: &tauri::AppHandle) -> Result<Vec<Skill>, String> {
+    read_file(&custom_skills_path(app)?)
+}
+
+pub fn save_custom(app: &tauri::AppHandle, skills: &[Skill]) -> Result<(), String> {
+    write_file(&custom_skills_path(app)?, skills)
+}
+
+pub fn load_workspace(root: &Path) -> Result<Vec<Skill>, String> {
+    read_file(&workspace_skills_path(root))
+}
+
+pub fn save_workspace(root: &Path, skills: &[Skill]) -> Result<(), String> {
+    write_file(&workspace_skills_path(root), skills)
+}
+
+/// 读取某一层保存的 skill；内置层只读
+pub fn load_layer(app: &tauri::AppHandle, root: Option<&Path>, source: SkillSource) -> Result<Vec<Skill>, String> {
+    match source {
+        SkillSource::Builtin => Ok(builtin_skills()),
+        SkillSource::Global => load_custom(app),
+        SkillSource::Workspace => load_workspace(root.ok_or("workspace not set")?),
+    }
+}
+
+pub fn save_layer(app: &tauri::AppHandle, root: Option<&Path>, source: SkillSource, skills: &[Skill]) -> Result<(), String> {
+    match source {
+        SkillSource::Builtin => Err("built-in skills are read-only".to_string()),
+        SkillSource::Global => save_custom(app, skills),
+        SkillSource::Workspace => save_workspace(root.ok_or("workspace not set")?, skills),
+    }
+}
+
+/// 在某一层新增或替换同 id 的 skill
+pub fn upsert(skills: &mut Vec<Skill>, skill: Skill) {
+    match skills.iter_mut().find(|s| s.id == skill.id) {
+        Some(slot) => *slot = skill,
+        None => skills.push(skill),
+    }
+}
+
+/// `run_skill` 的可选参数；未指定的 provider / 智能体按项目设置解析
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RunSkillOptions {
+    pub stream_id: Option<String>,
+    pub provider_id: Option<String>,
+    pub agent_id: Option<String>,
+    /// 未指定时沿用项目 / 全局设置
+    pub use_markdown: Option<bool>,
+    /// 内容取自文件选区时提供，结果会包装成针对该选区的 ChangeSet
+    pub selection: Option<SkillSelection>,
+}
+
+/// 文件中的选区，行号从 1 开始、含两端
+#[derive(Debug, Clone, Deserialize)]
+pub struct SkillSelection {
+    pub path: String,
+    pub line_start: u32,
+    pub line_end: u32,
+}
+
+/// skill 的 prompt 作为 system，待处理内容作为 user 消息
+pub fn build_messages(agent_prompt: &str, skill: &Skill, content: &str) -> Vec<ChatMessage> {
+    // 智能体的设定在前，skill 的具体要求在后
+    let system = if agent_prompt.trim().is_empty() {
+        skill.prompt.clone()
+    } else {
+        format!("{}\n\n{}", agent_prompt.trim_end(), skill.prompt)
+    };
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: system,
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+        },
+    ]
+}
+
+/// 校验选区的路径与行号范围，在调用模型之前发现错误
+pub fn validate_selection(root: &Path, selection: &SkillSelection) -> Result<(), String> {
+    read_selection(root, selection).map(|_| ())
+}
+
+/// 返回文件全文与选区内的文本
+fn read_selection(root: &Path, selection: &SkillSelection) -> Result<(String, String), String> {
+    let full = PathSandbox::new(root).resolve(&selection.path)?;
+    let original = fs::read_to_string(&full).map_err(|e| format!("read selection file failed: {e}"))?;
+    let lines: Vec<&str> = original.lines().collect();
+    let (start, end) = (selection.line_start as usize, selection.line_end as usize);
+    if start == 0 || start > end || end > lines.len() {
+        return Err(format!("invalid selection {start}-{end} for {}", selection.path));
+    }
+    let selected = lines[start - 1..end].join("\n");
+    Ok((original, selected))
+}
+
+/// 用 skill 的输出替换选区，生成待审阅的 ChangeSet；原文以磁盘上的文件为准
+pub fn selection_change_set(root: &Path, selection: &SkillSelection, output: &str) -> Result<ChangeSet, String> {
+    let (original, selected) = read_selection(root, selection)?;
+    let modification = Modification {
+        id: format!("mod-{}-1", chrono::Utc::now().timestamp_millis()),
+        mod_type: ModificationType::Modify,
+        line_start: selection.line_start,
+        line_end: selection.line_end,
+        original_text: Some(selected),
+        modified_text: Some(output.trim_end_matches('\n').to_string()),
+        status: ModificationStatus::Pending,
+    };
+    Ok(ChangeSet::new(vec![FileModification {
+        file_path: selection.path.clone(),
+        original_content: original,
+        modifications: vec![modification],
+        status: FileModificationStatus::Pending,
+        operation: FileOperation::Edit,
+        new_path: None,
+    }]))
+}
+
 /// Skill 管理器
 pub struct SkillManager {
-    skills: HashMap<String, Skill>,
+    skills: HashMap<String, SourcedSkill>,
 }
 
 impl SkillManager {
@@ -253,23 +439,43 @@ impl SkillManager {
         };
         // 加载内置 skills
         for skill in builtin_skills() {
-            manager.skills.insert(skill.id.clone(), skill);
+            manager.add(skill, SkillSource::Builtin);
         }
         manager
     }
 
-    #[allow(dead_code)]
+    /// 内置、全局与工作区 skill 合并后的管理器；`root` 为 `None` 时没有工作区层
+    pub fn load(app: &tauri::AppHandle, root: Option<&Path>) -> Result<Self, String> {
+        let mut manager = Self::new();
+        for skill in load_custom(app)? {
+            manager.add(skill, SkillSource::Global);
+        }
+        if let Some(root) = root {
+            for skill in load_workspace(root)? {
+                manager.add(skill, SkillSource::Workspace);
+            }
+        }
+        Ok(manager)
+    }
+
     pub fn get(&self, id: &str) -> Option<&Skill> {
-        self.skills.get(id)
+        self.skills.get(id).map(|s| &s.skill)
     }
 
     pub fn get_all(&self) -> Vec<&Skill> {
-        self.skills.values().collect()
+        self.sourced().into_iter().map(|s| &s.skill).collect()
+    }
+
+    /// 按分类、id 排序的全部 skill 及其来源
+    pub fn sourced(&self) -> Vec<&SourcedSkill> {
+        let mut all: Vec<&SourcedSkill> = self.skills.values().collect();
+        all.sort_by(|a, b| (&a.skill.category, &a.skill.id).cmp(&(&b.skill.category, &b.skill.id)));
+        all
     }
 
     pub fn get_by_category(&self, category: &str) -> Vec<&Skill> {
-        self.skills
-            .values()
+        self.get_all()
+            .into_iter()
             .filter(|s| s.category == category && s.enabled)
             .collect()
     }
@@ -277,25 +483,22 @@ impl SkillManager {
     pub fn categories(&self) -> Vec<String> {
         let mut cats: Vec<String> = self.skills
             .values()
-            .map(|s| s.category.clone())
+            .map(|s| s.skill.category.clone())
             .collect();
         cats.sort();
         cats.dedup();
         cats
     }
 
-    #[allow(dead_code)]
-    pub fn add(&mut self, skill: Skill) {
-        self.skills.insert(skill.id.clone(), skill);
-    }
-
-    #[allow(dead_code)]
-    pub fn remove(&mut self, id: &str) {
-        self.skills.remove(id);
+    /// 后加入的同 id skill 覆盖先前的
+    pub fn add(&mut self, skill: Skill, source: SkillSource) {
+        let overrides_builtin = source != SkillSource::Builtin
+            && self.skills.get(&skill.id).is_some_and(|s| s.source == SkillSource::Builtin || s.overrides_builtin);
+        self.skills.insert(skill.id.clone(), SourcedSkill { skill, source, overrides_builtin });
     }
 
     pub fn apply_skill(&self, skill_id: &str, content: &str) -> String {
-        if let Some(skill) = self.skills.get(skill_id) {
+        if let Some(skill) = self.get(skill_id) {
             format!("{}\n\n---\n\n{}", skill.prompt, content)
         } else {
             content.to_string()
@@ -308,3 +511,78 @@ impl Default for SkillManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn workspace_overrides_global_overrides_builtin() {
+        let mut manager = SkillManager::new();
+        let builtin_count = manager.get_all().len();
+
+        let mut tuned = manager.get("plot_twist").unwrap().clone();
+        tuned.prompt = "全局改写".to_string();
+        manager.add(tuned, SkillSource::Global);
+        manager.add(Skill::new("poem", "诗化", "", "自定义", "全局"), SkillSource::Global);
+        let mut disabled = Skill::new("poem", "诗化", "", "自定义", "本书");
+        disabled.enabled = false;
+        manager.add(disabled, SkillSource::Workspace);
+
+        assert_eq!(manager.get_all().len(), builtin_count + 1);
+        assert!(manager.categories().contains(&"自定义".to_string()));
+        assert!(manager.get_by_category("自定义").is_empty());
+        assert!(manager.apply_skill("plot_twist", "正文").starts_with("全局改写"));
+
+        let sourced = manager.sourced();
+        let twist = sourced.iter().find(|s| s.skill.id == "plot_twist").unwrap();
+        assert_eq!((twist.source, twist.overrides_builtin), (SkillSource::Global, true));
+        let poem = sourced.iter().find(|s| s.skill.id == "poem").unwrap();
+        assert_eq!((poem.source, poem.overrides_builtin), (SkillSource::Workspace, false));
+    }
+
+    #[test]
+    fn workspace_skills_round_trip() {
+        let root = TempDir::new("skills");
+        assert!(load_workspace(&root).unwrap().is_empty());
+        let mut skills = Vec::new();
+        upsert(&mut skills, Skill::new("a", "甲", "", "自定义", "一"));
+        upsert(&mut skills, Skill::new("a", "甲", "", "自定义", "二"));
+        save_workspace(&root, &skills).unwrap();
+        let loaded = load_workspace(&root).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].prompt, "二");
+    }
+
+    #[test]
+    fn agent_prompt_precedes_skill_prompt() {
+        let skill = Skill::new("a", "甲", "", "自定义", "润色下文");
+        let messages = build_messages("你是玄幻作者。\n", &skill, "正文");
+        assert_eq!(messages[0].content, "你是玄幻作者。\n\n润色下文");
+        assert_eq!(build_messages("", &skill, "正文")[0].content, "润色下文");
+        assert_eq!(messages[1].content, "正文");
+    }
+
+    #[test]
+    fn selection_result_becomes_change_set() {
+        let root = TempDir::new("skill-run");
+        fs::create_dir_all(root.join("stories")).unwrap();
+        fs::write(root.join("stories/a.txt"), "一\n二\n三\n四\n").unwrap();
+
+        let selection = SkillSelection {
+            path: "stories/a.txt".to_string(),
+            line_start: 2,
+            line_end: 3,
+        };
+        let cs = selection_change_set(&root, &selection, "贰\n叁\n").unwrap();
+        assert_eq!(cs.files[0].modifications[0].original_text.as_deref(), Some("二\n三"));
+        let ids: Vec<String> = cs.files[0].modifications.iter().map(|m| m.id.clone()).collect();
+        crate::change_sets::apply_change_set(&root, cs, &ids).unwrap();
+        assert_eq!(fs::read_to_string(root.join("stories/a.txt")).unwrap(), "一\n贰\n叁\n四\n");
+
+        let out_of_range = SkillSelection { line_end: 9, ..selection };
+        assert!(selection_change_set(&root, &out_of_range, "x").is_err());
+        assert!(validate_selection(&root, &out_of_range).is_err_and(|e| e.contains("invalid selection")));
+    }
+}
@@ -1,6 +1,24 @@
+use crate::path_sandbox::{self, PathSandbox};
+use crate::schema::{self, Schema};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+const fn spec_kit_schema(name: &'static str) -> Schema {
+  Schema {
+    name,
+    current: 1,
+    detect: schema::unversioned,
+    migrations: &[schema::unchanged],
+  }
+}
+
+pub const CONFIG_SCHEMA: Schema = spec_kit_schema("spec-kit config");
+pub const ARCHETYPES_SCHEMA: Schema = spec_kit_schema("archetypes");
+pub const PLOT_NODES_SCHEMA: Schema = spec_kit_schema("plot nodes");
+pub const STORY_SPEC_SCHEMA: Schema = spec_kit_schema("story spec");
+pub const STORY_TEMPLATE_SCHEMA: Schema = spec_kit_schema("story template");
+pub const ARC_MAP_SCHEMA: Schema = spec_kit_schema("arc map");
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SpecKitConfig {
@@ -516,31 +534,14 @@ pub fn ensure_spec_kit_defaults(novel_dir: &Path) -> Result<(), String> {
 
   fs::create_dir_all(&template_dir).map_err(|e| format!("create spec-kit dir failed: {e}"))?;
 
-  write_if_missing(
-    &spec_dir.join("config.json"),
-    &serde_json::to_string_pretty(&SpecKitConfig::default()).map_err(|e| format!("serialize spec-kit config failed: {e}"))?,
-  )?;
-
-  write_if_missing(
-    &spec_dir.join("archetypes.json"),
-    &serde_json::to_string_pretty(&ArchetypeDb::default()).map_err(|e| format!("serialize archetypes failed: {e}"))?,
-  )?;
-
-  write_if_missing(
-    &spec_dir.join("plot_nodes.json"),
-    &serde_json::to_string_pretty(&PlotNodeTemplateDb::default())
-      .map_err(|e| format!("serialize plot nodes failed: {e}"))?,
-  )?;
-
-  write_if_missing(
-    &spec_dir.join("story_spec.json"),
-    &serde_json::to_string_pretty(&StorySpec::default()).map_err(|e| format!("serialize story spec failed: {e}"))?,
-  )?;
+  write_if_missing(&spec_dir.join("config.json"), &CONFIG_SCHEMA.to_string(&SpecKitConfig::default())?)?;
+  write_if_missing(&spec_dir.join("archetypes.json"), &ARCHETYPES_SCHEMA.to_string(&ArchetypeDb::default())?)?;
+  write_if_missing(&spec_dir.join("plot_nodes.json"), &PLOT_NODES_SCHEMA.to_string(&PlotNodeTemplateDb::default())?)?;
+  write_if_missing(&spec_dir.join("story_spec.json"), &STORY_SPEC_SCHEMA.to_string(&StorySpec::default())?)?;
 
   for t in default_story_templates() {
     let path = template_dir.join(format!("{}.json", t.template_id));
-    let raw = serde_json::to_string_pretty(&t).map_err(|e| format!("serialize story template failed: {e}"))?;
-    write_if_missing(&path, &raw)?;
+    write_if_missing(&path, &STORY_TEMPLATE_SCHEMA.to_string(&t)?)?;
   }
 
   Ok(())
@@ -559,10 +560,28 @@ pub struct ValidationReport {
   pub issues: Vec<ValidationIssue>,
 }
 
+/// 读取 `.spec-kit` 下的文件，旧版本会先迁移并备份。
+fn read_spec_kit_file<T: serde::de::DeserializeOwned>(path: &Path, schema: &Schema) -> Result<T, String> {
+  let value = schema::read(path, schema)?.ok_or_else(|| format!("read {} failed: file not found", schema.name))?;
+  serde_json::from_value(value).map_err(|e| format!("parse {} failed: {e}", schema.name))
+}
+
 pub fn load_config(novel_dir: &Path) -> Result<SpecKitConfig, String> {
-  let path = novel_dir.join(".spec-kit").join("config.json");
-  let raw = fs::read_to_string(&path).map_err(|e| format!("read spec-kit config failed: {e}"))?;
-  serde_json::from_str(&raw).map_err(|e| format!("parse spec-kit config failed: {e}"))
+  read_spec_kit_file(&novel_dir.join(".spec-kit").join("config.json"), &CONFIG_SCHEMA)
+}
+
+pub fn load_story_spec(novel_dir: &Path) -> Result<StorySpec, String> {
+  read_spec_kit_file(&novel_dir.join(".spec-kit").join("story_spec.json"), &STORY_SPEC_SCHEMA)
+}
+
+pub fn save_story_spec(novel_dir: &Path, spec: &StorySpec) -> Result<(), String> {
+  let raw = STORY_SPEC_SCHEMA.to_string(spec)?;
+  fs::write(novel_dir.join(".spec-kit").join("story_spec.json"), raw).map_err(|e| format!("write story spec failed: {e}"))
+}
+
+pub fn save_arc_map(novel_dir: &Path, arc_map: &ArcMap) -> Result<(), String> {
+  let raw = ARC_MAP_SCHEMA.to_string(arc_map)?;
+  fs::write(novel_dir.join(".spec-kit").join("arc_map.json"), raw).map_err(|e| format!("write arc map failed: {e}"))
 }
 
 pub fn load_story_template(novel_dir: &Path, template_id: &str) -> Result<StoryTemplate, String> {
@@ -571,8 +590,7 @@ pub fn load_story_template(novel_dir: &Path, template_id: &str) -> Result<StoryT
     .join("story_templates")
     .join(format!("{template_id}.json"));
   if path.exists() {
-    let raw = fs::read_to_string(&path).map_err(|e| format!("read story template failed: {e}"))?;
-    return serde_json::from_str(&raw).map_err(|e| format!("parse story template failed: {e}"));
+    return read_spec_kit_file(&path, &STORY_TEMPLATE_SCHEMA);
   }
   default_story_templates()
     .into_iter()
@@ -580,6 +598,39 @@ pub fn load_story_template(novel_dir: &Path, template_id: &str) -> Result<StoryT
     .ok_or_else(|| format!("story template not found: {template_id}"))
 }
 
+/// 工作区 `story_templates` 目录下的全部模板，按 id 排序
+pub fn list_story_templates(novel_dir: &Path) -> Result<Vec<StoryTemplate>, String> {
+  let dir = novel_dir.join(".spec-kit").join("story_templates");
+  if !dir.exists() {
+    return Ok(Vec::new());
+  }
+  let mut out: Vec<StoryTemplate> = Vec::new();
+  for entry in fs::read_dir(&dir).map_err(|e| format!("read story templates failed: {e}"))? {
+    let path = entry.map_err(|e| format!("read story templates failed: {e}"))?.path();
+    if path.extension().and_then(|e| e.to_str()) == Some("json") {
+      out.push(read_spec_kit_file(&path, &STORY_TEMPLATE_SCHEMA)?);
+    }
+  }
+  out.sort_by(|a, b| a.template_id.cmp(&b.template_id));
+  Ok(out)
+}
+
+/// 模板 id 即文件名，必须是单个普通路径分量；目标路径经 `PathSandbox` 解析，不会写出 `.novel`。
+pub fn save_story_template(novel_dir: &Path, template: &StoryTemplate) -> Result<(), String> {
+  let path = story_template_path(novel_dir, &template.template_id)?;
+  let raw = STORY_TEMPLATE_SCHEMA.to_string(template)?;
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir).map_err(|e| format!("create spec-kit dir failed: {e}"))?;
+  }
+  fs::write(path, raw).map_err(|e| format!("write story template failed: {e}"))
+}
+
+/// 模板文件路径；id 必须是单个文件名且目标位于 `novel_dir` 内。
+pub fn story_template_path(novel_dir: &Path, template_id: &str) -> Result<PathBuf, String> {
+  path_sandbox::check_file_name(template_id)?;
+  PathSandbox::new(novel_dir).resolve(&format!(".spec-kit/story_templates/{template_id}.json"))
+}
+
 pub fn generate_story_spec_from_config(config: &SpecKitConfig, template: &StoryTemplate) -> StorySpec {
   let chapter_count = config.chapter_count.max(1);
   let mut act1_count = ((chapter_count as f32) * config.rhythm.act1_ratio).round() as i32;
@@ -1133,6 +1184,23 @@ fn write_if_missing(path: &Path, content: &str) -> Result<(), String> {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::test_util::TempDir;
+
+  #[test]
+  fn loads_every_previous_version() {
+    let novel_dir = TempDir::new("spec-kit");
+    let spec_dir = novel_dir.join(".spec-kit");
+    fs::create_dir_all(&spec_dir).unwrap();
+    fs::write(spec_dir.join("config.json"), include_str!("../fixtures/schema/spec_kit_config.v0.json")).unwrap();
+    fs::write(spec_dir.join("story_spec.json"), include_str!("../fixtures/schema/story_spec.v0.json")).unwrap();
+
+    assert_eq!(load_config(&novel_dir).unwrap().spec_kit_version, "1.0.0");
+    assert_eq!(load_story_spec(&novel_dir).unwrap().story.title, "星海归途");
+    let raw = fs::read_to_string(spec_dir.join("story_spec.json")).unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&raw).unwrap()[schema::VERSION_KEY], 1);
+    let backups = fs::read_dir(&spec_dir).unwrap().flatten().filter(|e| e.file_name().to_string_lossy().ends_with(".bak")).count();
+    assert_eq!(backups, 2);
+  }
 
   #[test]
   fn generate_default_outline_contains_required_beats() {
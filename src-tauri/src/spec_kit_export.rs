@@ -5,7 +5,7 @@ use epub_builder::{EpubBuilder, EpubContent, ZipLibrary};
 use printpdf::{Mm, Op, ParsedFont, PdfDocument, PdfPage, PdfSaveOptions, Point, Pt, TextItem};
 use serde::Deserialize;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 #[derive(Deserialize, Default)]
 struct ChapterMetaFile {
@@ -152,13 +152,18 @@ fn load_book(root: &Path) -> Result<(String, Vec<BookChapter>), String> {
 }
 
 fn read_story_title(novel_dir: &Path) -> Option<String> {
-  let p = novel_dir.join(".spec-kit").join("story_spec.json");
-  let raw = fs::read_to_string(p).ok()?;
-  let spec: spec_kit::StorySpec = serde_json::from_str(&raw).ok()?;
+  let spec = spec_kit::load_story_spec(novel_dir).ok()?;
   Some(spec.story.title)
 }
 
-fn load_chapters(root: &Path) -> Result<Vec<BookChapter>, String> {
+/// 书中一章在工作区内的位置，按 `chapters.json` 的 order 排序；
+/// 没有 `chapters.json` 时按 `stories/` 下的文件名排序。
+pub(crate) struct ChapterRef {
+  pub title: String,
+  pub file_path: String,
+}
+
+pub(crate) fn list_chapter_refs(root: &Path) -> Result<Vec<ChapterRef>, String> {
   let meta_path = root.join(".novel").join(".settings").join("chapters.json");
   if meta_path.exists() {
     let raw = fs::read_to_string(&meta_path).map_err(|e| format!("read chapters meta failed: {e}"))?;
@@ -169,14 +174,14 @@ fn load_chapters(root: &Path) -> Result<Vec<BookChapter>, String> {
       if c.file_path.trim().is_empty() {
         continue;
       }
-      let abs = root.join(PathBuf::from(c.file_path.replace('\\', "/")));
-      if !abs.exists() {
+      let file_path = c.file_path.replace('\\', "/");
+      let Ok(rel) = validate_relative_path(&file_path) else { continue };
+      if !root.join(rel).exists() {
         continue;
       }
-      let content = fs::read_to_string(abs).unwrap_or_default();
-      out.push(BookChapter {
+      out.push(ChapterRef {
         title: if c.title.trim().is_empty() { c.file_path.clone() } else { c.title },
-        content,
+        file_path,
       });
     }
     return Ok(out);
@@ -190,17 +195,27 @@ fn load_chapters(root: &Path) -> Result<Vec<BookChapter>, String> {
     .map_err(|e| format!("read stories dir failed: {e}"))?
     .filter_map(|e| e.ok())
     .filter(|e| e.path().is_file())
-    .map(|e| e.path())
+    .map(|e| e.file_name().to_string_lossy().to_string())
     .collect::<Vec<_>>();
   files.sort();
 
+  Ok(
+    files
+      .into_iter()
+      .enumerate()
+      .map(|(i, name)| ChapterRef {
+        title: format!("第{}章", i + 1),
+        file_path: format!("stories/{name}"),
+      })
+      .collect(),
+  )
+}
+
+fn load_chapters(root: &Path) -> Result<Vec<BookChapter>, String> {
   let mut out = vec![];
-  for (i, p) in files.iter().enumerate() {
-    let content = fs::read_to_string(p).unwrap_or_default();
-    out.push(BookChapter {
-      title: format!("第{}章", i + 1),
-      content,
-    });
+  for c in list_chapter_refs(root)? {
+    let content = fs::read_to_string(root.join(&c.file_path)).unwrap_or_default();
+    out.push(BookChapter { title: c.title, content });
   }
   Ok(out)
 }
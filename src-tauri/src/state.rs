@@ -1,9 +1,14 @@
+use crate::jobs::JobControl;
+use crate::mcp::McpHub;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 pub struct AppState {
   pub workspace_root: Mutex<Option<PathBuf>>,
   pub fs_watcher: Mutex<Option<notify::RecommendedWatcher>>,
+  pub jobs: Mutex<HashMap<String, Arc<JobControl>>>,
+  pub mcp: Mutex<McpHub>,
 }
 
 impl Default for AppState {
@@ -11,6 +16,8 @@ impl Default for AppState {
     Self {
       workspace_root: Mutex::new(None),
       fs_watcher: Mutex::new(None),
+      jobs: Mutex::new(HashMap::new()),
+      mcp: Mutex::new(McpHub::default()),
     }
   }
 }
@@ -0,0 +1,34 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// 测试用临时目录：位于系统临时目录下，离开作用域（包括断言失败）时自动删除。
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+  pub fn new(prefix: &str) -> Self {
+    let dir = std::env::temp_dir().join(format!("novel-ide-{prefix}-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+  }
+}
+
+impl Deref for TempDir {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl AsRef<Path> for TempDir {
+  fn as_ref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.0);
+  }
+}
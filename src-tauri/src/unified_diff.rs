@@ -0,0 +1,583 @@
+use crate::modification_types::{
+    ChangeSet, FileModification, FileModificationStatus, FileOperation, Modification, ModificationStatus,
+    ModificationType,
+};
+use crate::path_sandbox::PathSandbox;
+use std::fs;
+use std::path::Path;
+
+/// 导出时每个 hunk 前后保留的上下文行数
+const CONTEXT_LINES: usize = 3;
+/// 导入时最多忽略 hunk 首尾各多少行上下文（与 `patch --fuzz` 相同）
+const MAX_FUZZ: usize = 2;
+const NO_EOL_MARKER: &str = "\\ No newline at end of file";
+
+/// 行级修改在原文上的位置：从 `old_start` 行起删除 `removed` 行，再插入 `added`
+struct Change {
+    old_start: usize,
+    removed: usize,
+    added: Vec<String>,
+}
+
+/// 把 ChangeSet 转为 git 风格的 unified diff；已拒绝的修改不导出。
+pub fn export_change_set(change_set: &ChangeSet) -> Result<String, String> {
+    let mut out = String::new();
+    for file in &change_set.files {
+        let mods = file
+            .modifications
+            .iter()
+            .filter(|m| !matches!(m.status, ModificationStatus::Rejected))
+            .collect::<Vec<_>>();
+        if mods.is_empty() {
+            continue;
+        }
+        let file_op = mods.iter().find(|m| m.mod_type.is_file_level()).map(|m| &m.mod_type);
+        let line_mods = mods.iter().filter(|m| !m.mod_type.is_file_level()).copied().collect::<Vec<_>>();
+        let path = file.file_path.as_str();
+        match file_op {
+            Some(ModificationType::CreateFile) => {
+                let content = mods
+                    .iter()
+                    .find(|m| matches!(m.mod_type, ModificationType::CreateFile))
+                    .and_then(|m| m.modified_text.as_deref())
+                    .unwrap_or("");
+                out.push_str(&format!("diff --git a/{path} b/{path}\nnew file mode 100644\n"));
+                out.push_str(&format!("--- /dev/null\n+++ b/{path}\n"));
+                push_whole_file(&mut out, content, '+');
+            }
+            Some(ModificationType::DeleteFile) => {
+                out.push_str(&format!("diff --git a/{path} b/{path}\ndeleted file mode 100644\n"));
+                out.push_str(&format!("--- a/{path}\n+++ /dev/null\n"));
+                push_whole_file(&mut out, &file.original_content, '-');
+            }
+            Some(ModificationType::RenameFile) => {
+                let new_path = file
+                    .new_path
+                    .as_deref()
+                    .ok_or_else(|| format!("{path} is missing the rename target"))?;
+                out.push_str(&format!("diff --git a/{path} b/{new_path}\nrename from {path}\nrename to {new_path}\n"));
+                if !line_mods.is_empty() {
+                    out.push_str(&format!("--- a/{path}\n+++ b/{new_path}\n"));
+                    push_hunks(&mut out, &file.original_content, &line_mods).map_err(|e| format!("{path}: {e}"))?;
+                }
+            }
+            _ => {
+                out.push_str(&format!("diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n"));
+                push_hunks(&mut out, &file.original_content, &line_mods).map_err(|e| format!("{path}: {e}"))?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn push_whole_file(out: &mut String, content: &str, sign: char) {
+    let lines = content.lines().collect::<Vec<_>>();
+    let range = if lines.is_empty() { "0,0".to_string() } else { format!("1,{}", lines.len()) };
+    if sign == '+' {
+        out.push_str(&format!("@@ -0,0 +{range} @@\n"));
+    } else {
+        out.push_str(&format!("@@ -{range} +0,0 @@\n"));
+    }
+    for l in &lines {
+        out.push(sign);
+        out.push_str(l);
+        out.push('\n');
+    }
+    if !lines.is_empty() && !content.ends_with('\n') {
+        out.push_str(NO_EOL_MARKER);
+        out.push('\n');
+    }
+}
+
+fn push_hunks(out: &mut String, original: &str, mods: &[&Modification]) -> Result<(), String> {
+    let lines = original.lines().collect::<Vec<_>>();
+    let n = lines.len();
+    let has_eol = original.is_empty() || original.ends_with('\n');
+
+    let mut changes = Vec::new();
+    for m in mods {
+        let added = m.modified_text.as_deref().unwrap_or("").lines().map(|l| l.to_string()).collect::<Vec<_>>();
+        let start = m.line_start as usize;
+        let removed = match m.mod_type {
+            ModificationType::Add => 0,
+            _ => (m.line_end as usize + 1).saturating_sub(start),
+        };
+        if start == 0 || start + removed > n + 1 {
+            return Err(format!("modification {} is out of range", m.id));
+        }
+        changes.push(Change { old_start: start, removed, added });
+    }
+    changes.sort_by_key(|c| (c.old_start, c.removed));
+
+    // 原文末尾没有换行时，追加到末尾的内容要连同最后一行一起替换，否则无法表达换行的变化
+    if !has_eol && n > 0 {
+        if let Some(pos) = changes.iter().position(|c| c.old_start == n + 1) {
+            let append = changes.remove(pos);
+            match changes.iter_mut().find(|c| c.old_start + c.removed == n + 1 && c.removed > 0) {
+                Some(prev) => prev.added.extend(append.added),
+                None => {
+                    let mut added = vec![lines[n - 1].to_string()];
+                    added.extend(append.added);
+                    changes.push(Change { old_start: n, removed: 1, added });
+                    changes.sort_by_key(|c| (c.old_start, c.removed));
+                }
+            }
+        }
+    }
+
+    // 相邻修改的上下文重叠时合并为一个 hunk
+    let mut groups: Vec<Vec<&Change>> = Vec::new();
+    for c in &changes {
+        match groups.last_mut() {
+            Some(g) if g.last().is_some_and(|p| c.old_start <= p.old_start + p.removed + 2 * CONTEXT_LINES) => g.push(c),
+            _ => groups.push(vec![c]),
+        }
+    }
+
+    let mut delta: isize = 0;
+    for group in groups {
+        let first = group[0];
+        let last = group[group.len() - 1];
+        let ctx_start = first.old_start.saturating_sub(CONTEXT_LINES).max(1);
+        let ctx_end = (last.old_start + last.removed + CONTEXT_LINES - 1).min(n);
+        let mut body: Vec<(char, &str)> = Vec::new();
+        let mut i = ctx_start;
+        let mut removed_total = 0;
+        let mut added_total = 0;
+        for c in &group {
+            while i < c.old_start {
+                body.push((' ', lines[i - 1]));
+                i += 1;
+            }
+            for l in &lines[c.old_start - 1..c.old_start - 1 + c.removed] {
+                body.push(('-', l));
+            }
+            for l in &c.added {
+                body.push(('+', l));
+            }
+            i = c.old_start + c.removed;
+            removed_total += c.removed;
+            added_total += c.added.len();
+        }
+        while i <= ctx_end {
+            body.push((' ', lines[i - 1]));
+            i += 1;
+        }
+
+        let old_count = body.iter().filter(|(k, _)| *k != '+').count();
+        let new_count = old_count - removed_total + added_total;
+        let old_start = if old_count == 0 { ctx_start - 1 } else { ctx_start };
+        let new_first = (ctx_start as isize + delta) as usize;
+        let new_start = if new_count == 0 { new_first - 1 } else { new_first };
+        out.push_str(&format!("@@ -{old_start},{old_count} +{new_start},{new_count} @@\n"));
+
+        // 原文末尾无换行时，在最后一行原文 / 最后一段新增内容之后标注
+        let last_old = body.iter().rposition(|(k, _)| *k != '+');
+        let last_new = body.iter().rposition(|(k, _)| *k != '-');
+        let touches_end = !has_eol && ctx_end == n;
+        for (idx, (kind, text)) in body.iter().enumerate() {
+            out.push(*kind);
+            out.push_str(text);
+            out.push('\n');
+            let old_marker = touches_end && Some(idx) == last_old && *kind != ' ';
+            let new_marker = touches_end && Some(idx) == last_new;
+            if old_marker || new_marker {
+                out.push_str(NO_EOL_MARKER);
+                out.push('\n');
+            }
+        }
+        delta += added_total as isize - removed_total as isize;
+    }
+    Ok(())
+}
+
+struct PatchFile {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    /// 出现了 git 的 `rename from` / `rename to` 头；普通 `diff -u a.orig a` 的新旧路径不同但不是重命名
+    renamed: bool,
+    hunks: Vec<Hunk>,
+    /// 新建文件内容是否以换行结尾
+    new_eol: bool,
+}
+
+struct Hunk {
+    old_start: usize,
+    lines: Vec<(char, String)>,
+}
+
+/// 解析 unified diff（兼容 `git diff` 与 `diff -u` 的输出），按工作区当前内容定位 hunk，
+/// 生成待审阅的 ChangeSet。hunk 位置允许偏移，上下文最多忽略首尾各 [`MAX_FUZZ`] 行。
+pub fn import_patch(root: &Path, patch: &str) -> Result<ChangeSet, String> {
+    let sandbox = PathSandbox::new(root);
+    let mut counter = 0usize;
+    let mut next_id = || {
+        counter += 1;
+        format!("mod-{}-{}", chrono::Utc::now().timestamp_millis(), counter)
+    };
+    let mut files = Vec::new();
+    for pf in parse_patch(patch)? {
+        let modification = |id: String, mod_type, start: usize, end: usize, original: Option<String>, modified: Option<String>| {
+            Modification {
+                id,
+                mod_type,
+                line_start: start as u32,
+                line_end: end as u32,
+                original_text: original,
+                modified_text: modified,
+                status: ModificationStatus::Pending,
+            }
+        };
+        let line_end = |text: &str| text.lines().count().max(1);
+        let (file_path, operation, new_path, original_content, mut modifications) = match (&pf.old_path, &pf.new_path) {
+            (None, None) => continue,
+            (None, Some(path)) => {
+                if sandbox.resolve(path)?.exists() {
+                    return Err(format!("{path} already exists"));
+                }
+                let mut content = pf
+                    .hunks
+                    .iter()
+                    .flat_map(|h| h.lines.iter().filter(|(k, _)| *k == '+').map(|(_, l)| l.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if pf.new_eol && !content.is_empty() {
+                    content.push('\n');
+                }
+                let m = modification(next_id(), ModificationType::CreateFile, 1, line_end(&content), None, Some(content));
+                (path.clone(), FileOperation::Create, None, String::new(), vec![m])
+            }
+            (Some(path), None) => {
+                let original = read_existing(&sandbox, path)?;
+                let m = modification(next_id(), ModificationType::DeleteFile, 1, line_end(&original), Some(original.clone()), None);
+                (path.clone(), FileOperation::Delete, None, original, vec![m])
+            }
+            (Some(old), Some(new)) if pf.renamed && old != new => {
+                if sandbox.resolve(new)?.exists() {
+                    return Err(format!("{new} already exists"));
+                }
+                let original = read_existing(&sandbox, old)?;
+                let m = modification(
+                    next_id(),
+                    ModificationType::RenameFile,
+                    1,
+                    line_end(&original),
+                    Some(old.clone()),
+                    Some(new.clone()),
+                );
+                (old.clone(), FileOperation::Rename, Some(new.clone()), original, vec![m])
+            }
+            (Some(old), Some(new)) => {
+                // `diff -u ch1.txt.orig ch1.txt` 这类补丁修改的是工作区中实际存在的那个文件
+                let path = if old != new && !sandbox.resolve(old)?.exists() { new } else { old };
+                let original = read_existing(&sandbox, path)?;
+                (path.clone(), FileOperation::Edit, None, original, Vec::new())
+            }
+        };
+
+        if operation == FileOperation::Edit || operation == FileOperation::Rename {
+            let lines = original_content.lines().collect::<Vec<_>>();
+            let mut min_pos = 1;
+            for (idx, hunk) in pf.hunks.iter().enumerate() {
+                let (pos, body) = locate_hunk(&lines, hunk, min_pos)
+                    .ok_or_else(|| format!("{file_path}: hunk #{} does not apply", idx + 1))?;
+                let mut cur = pos;
+                let mut k = 0;
+                while k < body.len() {
+                    if body[k].0 == ' ' {
+                        cur += 1;
+                        k += 1;
+                        continue;
+                    }
+                    let mut removed = 0;
+                    let mut added = Vec::new();
+                    while k < body.len() && body[k].0 != ' ' {
+                        if body[k].0 == '-' {
+                            removed += 1;
+                        } else {
+                            added.push(body[k].1.as_str());
+                        }
+                        k += 1;
+                    }
+                    let original_text = (removed > 0).then(|| lines[cur - 1..cur - 1 + removed].join("\n"));
+                    let modified_text = (!added.is_empty()).then(|| added.join("\n"));
+                    let (mod_type, end) = match (removed, added.is_empty()) {
+                        (0, _) => (ModificationType::Add, cur),
+                        (_, true) => (ModificationType::Delete, cur + removed - 1),
+                        _ => (ModificationType::Modify, cur + removed - 1),
+                    };
+                    modifications.push(modification(next_id(), mod_type, cur, end, original_text, modified_text));
+                    cur += removed;
+                }
+                min_pos = cur;
+            }
+        }
+        if modifications.is_empty() {
+            continue;
+        }
+        files.push(FileModification {
+            file_path,
+            original_content,
+            modifications,
+            status: FileModificationStatus::Pending,
+            operation,
+            new_path,
+        });
+    }
+    if files.is_empty() {
+        return Err("patch contains no changes".to_string());
+    }
+    Ok(ChangeSet::new(files))
+}
+
+fn read_existing(sandbox: &PathSandbox, path: &str) -> Result<String, String> {
+    let full = sandbox.resolve(path)?;
+    fs::read_to_string(&full).map_err(|e| format!("read {path} failed: {e}"))
+}
+
+/// 在原文中寻找 hunk 的位置，返回起始行（1 起）以及去掉 fuzz 上下文后的 hunk 行。
+fn locate_hunk<'a>(lines: &[&str], hunk: &'a Hunk, min_pos: usize) -> Option<(usize, &'a [(char, String)])> {
+    let leading = hunk.lines.iter().take_while(|(k, _)| *k == ' ').count();
+    let trailing = hunk.lines.iter().rev().take_while(|(k, _)| *k == ' ').count();
+    for fuzz in 0..=MAX_FUZZ {
+        let (lead, trail) = (fuzz.min(leading), fuzz.min(trailing));
+        if fuzz > 0 && lead + trail == 0 {
+            break;
+        }
+        let body = &hunk.lines[lead..hunk.lines.len() - trail];
+        let old = body.iter().filter(|(k, _)| *k != '+').map(|(_, l)| l.as_str()).collect::<Vec<_>>();
+        // hunk 头中的行号：纯插入时表示插在该行之后
+        let expected = (if old.is_empty() && hunk.old_start > 0 { hunk.old_start + 1 } else { hunk.old_start.max(1) }) + lead;
+        let matches_at = |pos: usize| {
+            pos >= min_pos
+                && pos + old.len() <= lines.len() + 1
+                && old.iter().enumerate().all(|(i, l)| lines[pos - 1 + i].trim_end() == l.trim_end())
+        };
+        // 从期望位置开始向两侧交替搜索
+        for offset in 0..=lines.len() + 1 {
+            if matches_at(expected + offset) {
+                return Some((expected + offset, body));
+            }
+            if offset > 0 && offset < expected && matches_at(expected - offset) {
+                return Some((expected - offset, body));
+            }
+        }
+    }
+    None
+}
+
+fn parse_patch(patch: &str) -> Result<Vec<PatchFile>, String> {
+    let mut files: Vec<PatchFile> = Vec::new();
+    let mut in_git_header = false;
+    let mut lines = patch.lines().map(|l| l.strip_suffix('\r').unwrap_or(l)).peekable();
+    while let Some(line) = lines.next() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let (old, new) = split_git_paths(rest);
+            files.push(PatchFile { old_path: old, new_path: new, renamed: false, hunks: Vec::new(), new_eol: true });
+            in_git_header = true;
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            let old = patch_path(path);
+            match files.last_mut() {
+                Some(f) if in_git_header => f.old_path = old,
+                _ => files.push(PatchFile { old_path: old, new_path: None, renamed: false, hunks: Vec::new(), new_eol: true }),
+            }
+            in_git_header = false;
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            let f = files.last_mut().ok_or("`+++` line without `---`")?;
+            f.new_path = patch_path(path);
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            if let Some(f) = files.last_mut() {
+                f.old_path = Some(path.trim().to_string());
+                f.renamed = true;
+            }
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            if let Some(f) = files.last_mut() {
+                f.new_path = Some(path.trim().to_string());
+                f.renamed = true;
+            }
+        } else if line.starts_with("new file mode") {
+            if let Some(f) = files.last_mut() {
+                f.old_path = None;
+            }
+        } else if line.starts_with("deleted file mode") {
+            if let Some(f) = files.last_mut() {
+                f.new_path = None;
+            }
+        } else if line.starts_with("@@") {
+            let f = files.last_mut().ok_or("hunk without file header")?;
+            let (old_start, mut old_left, mut new_left) = parse_hunk_header(line)?;
+            let mut hunk = Hunk { old_start, lines: Vec::new() };
+            while old_left > 0 || new_left > 0 {
+                let Some(l) = lines.next() else {
+                    return Err(format!("truncated hunk: {line}"));
+                };
+                let (kind, text) = match l.chars().next() {
+                    Some(k @ (' ' | '-' | '+')) => (k, &l[1..]),
+                    Some('\\') => continue,
+                    // 有些编辑器会去掉空上下文行行首的空格
+                    None => (' ', ""),
+                    _ => return Err(format!("unexpected line in hunk: {l}")),
+                };
+                if kind != '+' {
+                    old_left = old_left.checked_sub(1).ok_or_else(|| format!("hunk longer than its header: {line}"))?;
+                }
+                if kind != '-' {
+                    new_left = new_left.checked_sub(1).ok_or_else(|| format!("hunk longer than its header: {line}"))?;
+                }
+                hunk.lines.push((kind, text.to_string()));
+            }
+            if lines.peek().is_some_and(|l| l.starts_with('\\')) {
+                lines.next();
+                if hunk.lines.last().is_some_and(|(k, _)| *k != '-') {
+                    f.new_eol = false;
+                }
+            }
+            f.hunks.push(hunk);
+        }
+    }
+    if files.is_empty() {
+        return Err("no file diffs found in patch".to_string());
+    }
+    Ok(files)
+}
+
+/// `diff --git a/x b/y` 中的两个路径；路径含空格时以 ` b/` 分隔。
+fn split_git_paths(rest: &str) -> (Option<String>, Option<String>) {
+    match rest.find(" b/") {
+        Some(i) => (patch_path(&rest[..i]), patch_path(&rest[i + 1..])),
+        None => (None, None),
+    }
+}
+
+fn patch_path(raw: &str) -> Option<String> {
+    // `diff -u` 会在路径后附带制表符分隔的时间戳
+    let path = raw.split('\t').next().unwrap_or("").trim();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    let path = path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path);
+    Some(path.to_string())
+}
+
+fn parse_hunk_header(line: &str) -> Result<(usize, usize, usize), String> {
+    let invalid = || format!("invalid hunk header: {line}");
+    let inner = line.strip_prefix("@@ ").and_then(|s| s.split(" @@").next()).ok_or_else(invalid)?;
+    let mut parts = inner.split_whitespace();
+    let range = |part: Option<&str>, sign: char| -> Result<(usize, usize), String> {
+        let spec = part.and_then(|p| p.strip_prefix(sign)).ok_or_else(invalid)?;
+        let (start, count) = spec.split_once(',').unwrap_or((spec, "1"));
+        Ok((start.parse().map_err(|_| invalid())?, count.parse().map_err(|_| invalid())?))
+    };
+    let (old_start, old_count) = range(parts.next(), '-')?;
+    let (_, new_count) = range(parts.next(), '+')?;
+    Ok((old_start, old_count, new_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::change_sets::apply_change_set;
+
+    const CHAPTER: &str = "一\n二\n三\n四\n五\n六\n七\n八\n九\n十\n十一\n十二\n";
+
+    fn temp_workspace() -> TempDir {
+        let root = TempDir::new("diff");
+        fs::create_dir_all(root.join("stories")).unwrap();
+        fs::write(root.join("stories/ch1.txt"), CHAPTER).unwrap();
+        root
+    }
+
+    fn all_ids(cs: &ChangeSet) -> Vec<String> {
+        cs.files.iter().flat_map(|f| f.modifications.iter().map(|m| m.id.clone())).collect()
+    }
+
+    #[test]
+    fn exports_hunks_with_context() {
+        let root = temp_workspace();
+        let patch = "--- a/stories/ch1.txt\n+++ b/stories/ch1.txt\n@@ -1,3 +1,4 @@\n 一\n-二\n+贰\n+二点五\n 三\n";
+        let cs = import_patch(&root, patch).unwrap();
+        let exported = export_change_set(&cs).unwrap();
+        assert_eq!(
+            exported,
+            "diff --git a/stories/ch1.txt b/stories/ch1.txt\n--- a/stories/ch1.txt\n+++ b/stories/ch1.txt\n@@ -1,5 +1,6 @@\n 一\n-二\n+贰\n+二点五\n 三\n 四\n 五\n"
+        );
+    }
+
+    #[test]
+    fn round_trips_and_applies_with_offset_and_fuzz() {
+        let root = temp_workspace();
+        let patch = "diff --git a/stories/ch1.txt b/stories/ch1.txt\n--- a/stories/ch1.txt\n+++ b/stories/ch1.txt\n\
+@@ -2,5 +2,4 @@\n 二\n 三\n-四\n 五\n 六\n\
+@@ -8,5 +7,5 @@\n 八\n 九\n-十\n+拾\n 十一\n 十二\n";
+        // 文件开头多了两行，且第二个 hunk 的首行上下文被改过
+        let shifted = format!("序\n引\n{}", CHAPTER.replace("八", "捌"));
+        fs::write(root.join("stories/ch1.txt"), &shifted).unwrap();
+        let cs = import_patch(&root, patch).unwrap();
+        let mods = &cs.files[0].modifications;
+        assert_eq!((mods[0].line_start, mods[0].line_end), (6, 6));
+        assert_eq!((mods[1].line_start, mods[1].modified_text.as_deref()), (12, Some("拾")));
+
+        let exported = export_change_set(&cs).unwrap();
+        let reimported = import_patch(&root, &exported).unwrap();
+        apply_change_set(&root, reimported.clone(), &all_ids(&reimported)).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("stories/ch1.txt")).unwrap(),
+            shifted.replace("四\n", "").replace("十\n", "拾\n")
+        );
+    }
+
+    #[test]
+    fn handles_file_operations() {
+        let root = temp_workspace();
+        fs::write(root.join("stories/old.txt"), "废稿").unwrap();
+        let patch = "diff --git a/stories/new.txt b/stories/new.txt\nnew file mode 100644\n--- /dev/null\n+++ b/stories/new.txt\n@@ -0,0 +1,2 @@\n+新章\n+正文\n\\ No newline at end of file\n\
+diff --git a/stories/old.txt b/stories/old.txt\ndeleted file mode 100644\n--- a/stories/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-废稿\n\\ No newline at end of file\n\
+diff --git a/stories/ch1.txt b/stories/ch01.txt\nsimilarity index 90%\nrename from stories/ch1.txt\nrename to stories/ch01.txt\n--- a/stories/ch1.txt\n+++ b/stories/ch01.txt\n@@ -12 +12 @@\n-十二\n+十二完\n";
+        let cs = import_patch(&root, patch).unwrap();
+        let ops = cs.files.iter().map(|f| f.operation.clone()).collect::<Vec<_>>();
+        assert_eq!(ops, [FileOperation::Create, FileOperation::Delete, FileOperation::Rename]);
+
+        let exported = export_change_set(&cs).unwrap();
+        assert!(exported.contains("rename from stories/ch1.txt\nrename to stories/ch01.txt\n"));
+        assert!(exported.contains("+正文\n\\ No newline at end of file\n"));
+
+        apply_change_set(&root, cs.clone(), &all_ids(&cs)).unwrap();
+        assert_eq!(fs::read_to_string(root.join("stories/new.txt")).unwrap(), "新章\n正文");
+        assert!(!root.join("stories/old.txt").exists());
+        assert!(fs::read_to_string(root.join("stories/ch01.txt")).unwrap().ends_with("十一\n十二完\n"));
+    }
+
+    #[test]
+    fn plain_diff_with_backup_name_is_an_edit() {
+        let root = temp_workspace();
+        let patch = "--- stories/ch1.txt.orig\t2024-05-01 10:00:00\n+++ stories/ch1.txt\t2024-05-02 10:00:00\n@@ -1,2 +1,2 @@\n 一\n-二\n+贰\n";
+        let cs = import_patch(&root, patch).unwrap();
+        assert_eq!(cs.files[0].operation, FileOperation::Edit);
+        assert_eq!(cs.files[0].file_path, "stories/ch1.txt");
+
+        fs::write(root.join("stories/ch2.txt"), "甲\n").unwrap();
+        let reversed = "--- stories/ch2.txt\n+++ stories/ch2.new.txt\n@@ -1 +1 @@\n-甲\n+乙\n";
+        let cs = import_patch(&root, reversed).unwrap();
+        assert_eq!((cs.files[0].operation.clone(), cs.files[0].file_path.as_str()), (FileOperation::Edit, "stories/ch2.txt"));
+        apply_change_set(&root, cs.clone(), &all_ids(&cs)).unwrap();
+        assert_eq!(fs::read_to_string(root.join("stories/ch2.txt")).unwrap(), "乙\n");
+        assert!(!root.join("stories/ch2.new.txt").exists());
+    }
+
+    #[test]
+    fn marks_missing_trailing_newline_and_rejects_bad_patches() {
+        let root = temp_workspace();
+        fs::write(root.join("stories/ch2.txt"), "甲\n乙").unwrap();
+        let patch = "--- a/stories/ch2.txt\n+++ b/stories/ch2.txt\n@@ -2,0 +3 @@\n+丙\n";
+        let cs = import_patch(&root, patch).unwrap();
+        let exported = export_change_set(&cs).unwrap();
+        assert!(exported.ends_with("@@ -1,2 +1,3 @@\n 甲\n-乙\n\\ No newline at end of file\n+乙\n+丙\n\\ No newline at end of file\n"));
+
+        let mismatch = "--- a/stories/ch1.txt\n+++ b/stories/ch1.txt\n@@ -1,3 +1,3 @@\n 甲\n-乙\n+丙\n 丁\n";
+        assert!(import_patch(&root, mismatch).unwrap_err().contains("does not apply"));
+        let escape = "--- a/../etc/passwd\n+++ b/../etc/passwd\n@@ -1 +1 @@\n-root\n+me\n";
+        assert!(import_patch(&root, escape).is_err());
+        assert!(import_patch(&root, "not a patch").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::agents::{self, Agent};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// 工作区内智能体目录（相对工作区根目录），随书稿一起纳入版本管理
+pub const AGENTS_DIR: &str = ".novel/agents";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentSource {
+  Global,
+  Workspace,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourcedAgent {
+  #[serde(flatten)]
+  pub agent: Agent,
+  pub source: AgentSource,
+  /// 工作区智能体的定义文件（相对工作区根目录）
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub path: Option<String>,
+}
+
+/// 读取 `.novel/agents` 下的 `*.json` 与带 frontmatter 的 `*.md`，按文件名排序。
+///
+/// 单个文件解析失败时跳过并记录日志，不影响其它智能体。
+pub fn load_dir(root: &Path) -> Vec<(Agent, String)> {
+  let dir = root.join(AGENTS_DIR);
+  let Ok(entries) = fs::read_dir(&dir) else {
+    return Vec::new();
+  };
+  let mut paths: Vec<_> = entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect();
+  paths.sort();
+
+  let mut out = Vec::new();
+  for path in paths {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    if ext != "json" && ext != "md" {
+      continue;
+    }
+    let rel = format!("{AGENTS_DIR}/{}", path.file_name().unwrap_or_default().to_string_lossy());
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let parsed = fs::read_to_string(&path)
+      .map_err(|e| format!("read failed: {e}"))
+      .and_then(|raw| if ext == "md" { parse_markdown(&raw, &stem) } else { parse_json(&raw, &stem) });
+    match parsed {
+      Ok(agent) => out.push((agent, rel)),
+      Err(e) => eprintln!("skip workspace agent {rel}: {e}"),
+    }
+  }
+  out
+}
+
+fn parse_json(raw: &str, stem: &str) -> Result<Agent, String> {
+  let mut agent: Agent = serde_json::from_str(raw).map_err(|e| format!("parse failed: {e}"))?;
+  if agent.id.trim().is_empty() {
+    agent.id = stem.to_string();
+  }
+  Ok(agent)
+}
+
+/// frontmatter 只支持单行 `key: value`，`fragments` 写作 `[a, b]` 或 `a, b`；正文即 system_prompt。
+fn parse_markdown(raw: &str, stem: &str) -> Result<Agent, String> {
+  let raw = raw.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+  let rest = raw.strip_prefix("---\n").ok_or("missing frontmatter")?;
+  let (front, body) = match rest.split_once("\n---\n") {
+    Some(v) => v,
+    None => (rest.strip_suffix("\n---").ok_or("unterminated frontmatter")?, ""),
+  };
+
+  let mut agent = Agent {
+    id: stem.to_string(),
+    system_prompt: body.trim().to_string(),
+    ..Default::default()
+  };
+  for line in front.lines() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let (key, value) = line.split_once(':').ok_or_else(|| format!("invalid frontmatter line: {line}"))?;
+    let value = value.trim().trim_matches('"').trim_matches('\'');
+    let number = |v: &str| v.parse::<f64>().map_err(|_| format!("invalid number for {}: {v}", key.trim()));
+    match key.trim() {
+      "id" => agent.id = value.to_string(),
+      "name" => agent.name = value.to_string(),
+      "category" => agent.category = value.to_string(),
+      "temperature" => agent.temperature = number(value)? as f32,
+      "max_tokens" => agent.max_tokens = number(value)? as u32,
+      "chapter_word_target" => agent.chapter_word_target = number(value)? as u32,
+      "parent_id" | "parent" => agent.parent_id = Some(value.to_string()).filter(|v| !v.is_empty()),
+      "fragments" => {
+        agent.fragments = value
+          .trim_start_matches('[')
+          .trim_end_matches(']')
+          .split(',')
+          .map(|f| f.trim().trim_matches('"').trim_matches('\'').to_string())
+          .filter(|f| !f.is_empty())
+          .collect()
+      }
+      other => return Err(format!("unknown frontmatter key: {other}")),
+    }
+  }
+  if agent.name.is_empty() {
+    agent.name = agent.id.clone();
+  }
+  Ok(agent)
+}
+
+/// 全局列表在前保持原顺序；同 id 时工作区版本原位替换，其余工作区智能体追加在末尾。
+pub fn merge(global: Vec<Agent>, workspace: Vec<(Agent, String)>) -> Vec<SourcedAgent> {
+  let mut out: Vec<SourcedAgent> = global
+    .into_iter()
+    .map(|agent| SourcedAgent {
+      agent,
+      source: AgentSource::Global,
+      path: None,
+    })
+    .collect();
+  for (agent, path) in workspace {
+    let entry = SourcedAgent {
+      agent,
+      source: AgentSource::Workspace,
+      path: Some(path),
+    };
+    match out.iter_mut().find(|a| a.agent.id == entry.agent.id) {
+      Some(slot) => *slot = entry,
+      None => out.push(entry),
+    }
+  }
+  out
+}
+
+/// 全局智能体与工作区智能体合并后的列表；`root` 为 `None` 时只有全局智能体
+pub fn load_sourced(app: &tauri::AppHandle, root: Option<&Path>) -> Result<Vec<SourcedAgent>, String> {
+  let global = agents::load(app)?;
+  let workspace = root.map(load_dir).unwrap_or_default();
+  Ok(merge(global, workspace))
+}
+
+pub fn load(app: &tauri::AppHandle, root: Option<&Path>) -> Result<Vec<Agent>, String> {
+  Ok(load_sourced(app, root)?.into_iter().map(|a| a.agent).collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::TempDir;
+
+  #[test]
+  fn loads_json_and_markdown_agents() {
+    let root = TempDir::new("agents");
+    let dir = root.join(AGENTS_DIR);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a-world.json"), r#"{"id":"fantasy","name":"本书玄幻","system_prompt":"本书世界观"}"#).unwrap();
+    fs::write(
+      dir.join("b-detective.md"),
+      "---\nname: 侦探\ntemperature: 0.5\nparent: mystery\nfragments: [output_format, chapter_rules]\n---\n\n推理必须公平。\n",
+    )
+    .unwrap();
+    fs::write(dir.join("c-broken.md"), "没有 frontmatter").unwrap();
+    fs::write(dir.join("notes.txt"), "忽略").unwrap();
+
+    let loaded = load_dir(&root);
+    assert_eq!(loaded.len(), 2);
+    let detective = &loaded[1].0;
+    assert_eq!(detective.id, "b-detective");
+    assert_eq!(detective.system_prompt, "推理必须公平。");
+    assert_eq!(detective.parent_id.as_deref(), Some("mystery"));
+    assert_eq!(detective.fragments, ["output_format", "chapter_rules"]);
+    assert!((detective.temperature - 0.5).abs() < f32::EPSILON);
+
+    let global = vec![
+      Agent {
+        id: "fantasy".to_string(),
+        system_prompt: "全局玄幻".to_string(),
+        ..Default::default()
+      },
+      Agent {
+        id: "mystery".to_string(),
+        ..Default::default()
+      },
+    ];
+    let merged = merge(global, loaded);
+    assert_eq!(merged.iter().map(|a| a.agent.id.as_str()).collect::<Vec<_>>(), ["fantasy", "mystery", "b-detective"]);
+    assert_eq!(merged[0].source, AgentSource::Workspace);
+    assert_eq!(merged[0].agent.system_prompt, "本书世界观");
+    assert_eq!(merged[0].path.as_deref(), Some(".novel/agents/a-world.json"));
+    assert_eq!(merged[1].source, AgentSource::Global);
+  }
+}